@@ -0,0 +1,166 @@
+use crate::puzzle::Clear;
+
+/// What a lock did to the board, as logged during the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LockEvent {
+    pub clear: Clear,
+    /// Clears in a row before this one, if it cleared lines.
+    pub combo: Option<usize>,
+    /// Height of the stack afterwards.
+    pub stack_height: usize,
+    /// Holes the piece covered up.
+    pub holes: usize,
+    /// Moves and rotations the piece took.
+    pub inputs: u32,
+    /// Fewest moves and rotations it could have taken, if known.
+    pub optimal: Option<u32>,
+    /// Keys pressed since the last lock, holds and drops included.
+    pub keys: u32,
+}
+
+/// Breakdown of a finished game, worked out from its [`LockEvent`]s.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Analysis {
+    pub singles: u64,
+    pub doubles: u64,
+    pub triples: u64,
+    pub tetrises: u64,
+    /// T-spins that cleared lines, of any size.
+    pub tspins: u64,
+    pub perfect_clears: u64,
+    pub pieces: u64,
+    /// Keys pressed over the game.
+    pub keys: u64,
+    /// Stack heights after every lock added up, for the average.
+    pub total_height: u64,
+    /// Holes pieces covered up over the game, counting those cleared later.
+    pub holes_created: u64,
+    pub longest_combo: usize,
+    /// Pieces placed with more inputs than needed.
+    pub finesse_faults: u64,
+    /// Pieces whose fewest inputs are known, to check for faults.
+    pub finesse_pieces: u64,
+}
+
+impl Analysis {
+    pub fn new(events: &[LockEvent]) -> Self {
+        let mut analysis = Self::default();
+        for event in events {
+            let clear = &event.clear;
+            match (clear.tspin, clear.lines) {
+                (_, 0) => {}
+                (true, _) => analysis.tspins += 1,
+                (false, 1) => analysis.singles += 1,
+                (false, 2) => analysis.doubles += 1,
+                (false, 3) => analysis.triples += 1,
+                (false, _) => analysis.tetrises += 1,
+            }
+            if clear.perfect {
+                analysis.perfect_clears += 1;
+            }
+            analysis.pieces += 1;
+            analysis.keys += u64::from(event.keys);
+            analysis.total_height += event.stack_height as u64;
+            analysis.holes_created += event.holes as u64;
+            if let Some(combo) = event.combo {
+                analysis.longest_combo = analysis.longest_combo.max(combo);
+            }
+            if let Some(optimal) = event.optimal {
+                analysis.finesse_pieces += 1;
+                if event.inputs > optimal {
+                    analysis.finesse_faults += 1;
+                }
+            }
+        }
+        analysis
+    }
+
+    /// Percentage of checked pieces placed without a finesse fault.
+    pub fn finesse_rate(&self) -> f64 {
+        match self.finesse_pieces {
+            0 => 100.0,
+            pieces => 100.0 * (pieces - self.finesse_faults) as f64 / pieces as f64,
+        }
+    }
+
+    /// Keys pressed per piece placed.
+    pub fn kpp(&self) -> f64 {
+        match self.pieces {
+            0 => 0.0,
+            pieces => self.keys as f64 / pieces as f64,
+        }
+    }
+
+    /// Height of the stack after the average lock.
+    pub fn average_height(&self) -> f64 {
+        match self.pieces {
+            0 => 0.0,
+            pieces => self.total_height as f64 / pieces as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analysis() {
+        let lock = |lines, tspin, combo, stack_height, holes| LockEvent {
+            clear: Clear {
+                lines,
+                tspin,
+                ..Default::default()
+            },
+            combo,
+            stack_height,
+            holes,
+            ..Default::default()
+        };
+        let analysis = Analysis::new(&[
+            lock(0, false, None, 2, 1),
+            lock(0, false, None, 4, 3),
+            lock(1, false, Some(0), 3, 1),
+            lock(2, true, Some(1), 1, 0),
+            lock(4, false, Some(2), 2, 1),
+            lock(0, false, None, 0, 0),
+        ]);
+        assert_eq!(
+            analysis,
+            Analysis {
+                singles: 1,
+                doubles: 0,
+                triples: 0,
+                tetrises: 1,
+                tspins: 1,
+                perfect_clears: 0,
+                pieces: 6,
+                keys: 0,
+                total_height: 12,
+                holes_created: 6,
+                longest_combo: 2,
+                finesse_faults: 0,
+                finesse_pieces: 0,
+            }
+        );
+        assert_eq!(analysis.average_height(), 2.0);
+        assert_eq!(Analysis::new(&[]).average_height(), 0.0);
+
+        let placed = |inputs, optimal| LockEvent {
+            inputs,
+            optimal,
+            keys: inputs + 1,
+            ..Default::default()
+        };
+        let analysis = Analysis::new(&[
+            placed(2, Some(2)),
+            placed(3, Some(1)),
+            placed(5, None),
+            placed(0, Some(0)),
+        ]);
+        assert_eq!((analysis.finesse_faults, analysis.finesse_pieces), (1, 3));
+        assert!((analysis.finesse_rate() - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(analysis.keys, 14);
+        assert_eq!(analysis.kpp(), 3.5);
+    }
+}
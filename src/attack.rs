@@ -0,0 +1,200 @@
+use crate::puzzle::Clear;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Lines of garbage sent to the opponent for each kind of clear, the
+/// `[attack]` table of the config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AttackTable {
+    pub single: u32,
+    pub double: u32,
+    pub triple: u32,
+    pub tetris: u32,
+    pub tspin_single: u32,
+    pub tspin_double: u32,
+    pub tspin_triple: u32,
+    /// Sent on top of the clear itself when it empties the board.
+    pub perfect_clear: u32,
+    /// Whether spins of other pieces that can't move afterwards send as much
+    /// as T-spins.
+    pub all_spin: bool,
+    /// Sent on top of the clear by the number of clears in a row before it,
+    /// repeating the last entry for longer combos.
+    pub combo: Vec<u32>,
+}
+
+impl Default for AttackTable {
+    fn default() -> Self {
+        Self {
+            single: 0,
+            double: 1,
+            triple: 2,
+            tetris: 4,
+            tspin_single: 2,
+            tspin_double: 4,
+            tspin_triple: 6,
+            perfect_clear: 10,
+            all_spin: false,
+            combo: vec![0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5],
+        }
+    }
+}
+
+impl AttackTable {
+    /// Lines of garbage `clear` sends after `combo` clears in a row.
+    pub fn lines(&self, clear: &Clear, combo: usize) -> u32 {
+        let spin = clear.tspin || (self.all_spin && clear.spin);
+        let lines = match (spin, clear.lines) {
+            (_, 0) => 0,
+            (false, 1) => self.single,
+            (false, 2) => self.double,
+            (false, 3) => self.triple,
+            (false, _) => self.tetris,
+            (true, 1) => self.tspin_single,
+            (true, 2) => self.tspin_double,
+            (true, _) => self.tspin_triple,
+        };
+        let combo = match clear.lines {
+            0 => 0,
+            _ => self.combo(combo),
+        };
+        let perfect = if clear.perfect { self.perfect_clear } else { 0 };
+        lines + combo + perfect
+    }
+
+    /// Bonus for a clear after `combo` clears in a row.
+    pub fn combo(&self, combo: usize) -> u32 {
+        self.combo
+            .get(combo)
+            .or(self.combo.last())
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// Nets the `outgoing` lines of a clear against the `incoming` lines waiting
+/// to be received: attacking cancels pending garbage first, and only what's
+/// left over on either side is sent or received. Returns the lines to send
+/// and the lines to receive.
+pub fn cancel(outgoing: u32, incoming: u32) -> (u32, u32) {
+    let canceled = outgoing.min(incoming);
+    (outgoing - canceled, incoming - canceled)
+}
+
+/// Columns of the holes in `rows` rows of cheese garbage `width` cells wide.
+/// From one row to the next, the hole moves to another column with a chance
+/// of `messiness` percent: 0 lines the holes up in a well, 100 moves every
+/// one of them.
+pub fn cheese_holes(rows: usize, width: usize, messiness: u32, rng: &mut impl Rng) -> Vec<usize> {
+    let mut holes: Vec<usize> = Vec::with_capacity(rows);
+    for _ in 0..rows {
+        let hole = match holes.last() {
+            Some(&last) if width > 1 && rng.random_ratio(messiness.min(100), 100) => {
+                // any column but the last one
+                let hole = rng.random_range(0..width - 1);
+                if hole >= last {
+                    hole + 1
+                } else {
+                    hole
+                }
+            }
+            Some(&last) => last,
+            None => rng.random_range(0..width),
+        };
+        holes.push(hole);
+    }
+    holes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn test_attack_lines() {
+        let table = AttackTable::default();
+        let clear = |lines, tspin, perfect| Clear {
+            lines,
+            tspin,
+            spin: false,
+            perfect,
+        };
+
+        assert_eq!(table.lines(&clear(0, false, false), 0), 0);
+        assert_eq!(table.lines(&clear(0, true, false), 0), 0);
+        assert_eq!(table.lines(&clear(1, false, false), 0), 0);
+        assert_eq!(table.lines(&clear(3, false, false), 0), 2);
+        assert_eq!(table.lines(&clear(4, false, false), 0), 4);
+        assert_eq!(table.lines(&clear(2, true, false), 0), 4);
+        assert_eq!(table.lines(&clear(4, false, true), 0), 14);
+
+        let table: AttackTable = toml::from_str("single = 1\nperfect_clear = 0").unwrap();
+        assert_eq!(table.lines(&clear(1, false, true), 0), 1);
+        assert_eq!(table.tetris, 4);
+    }
+
+    #[test]
+    fn test_combo() {
+        let single = Clear {
+            lines: 1,
+            ..Default::default()
+        };
+        let table = AttackTable::default();
+        assert_eq!(table.lines(&single, 0), 0);
+        assert_eq!(table.lines(&single, 2), 1);
+        assert_eq!(table.lines(&single, 11), 5);
+        assert_eq!(table.lines(&single, 100), 5);
+        assert_eq!(table.lines(&Clear::default(), 100), 0);
+
+        let table: AttackTable = toml::from_str("combo = []").unwrap();
+        assert_eq!(table.lines(&single, 5), 0);
+    }
+
+    #[test]
+    fn test_cancel() {
+        assert_eq!(cancel(0, 0), (0, 0));
+        assert_eq!(cancel(4, 0), (4, 0));
+        assert_eq!(cancel(0, 3), (0, 3));
+        assert_eq!(cancel(4, 3), (1, 0));
+        assert_eq!(cancel(2, 5), (0, 3));
+        assert_eq!(cancel(3, 3), (0, 0));
+    }
+
+    #[test]
+    fn test_cheese_holes() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let holes = cheese_holes(10, 10, 0, &mut rng);
+        assert_eq!(holes.len(), 10);
+        assert!(holes.iter().all(|&hole| hole == holes[0]));
+
+        let holes = cheese_holes(10, 10, 100, &mut rng);
+        assert!(holes.iter().all(|&hole| hole < 10));
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+
+        // some rows keep the hole where it was, and some move it
+        let holes = cheese_holes(100, 10, 50, &mut rng);
+        let moved = holes.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        assert!((20..80).contains(&moved));
+
+        assert_eq!(cheese_holes(3, 1, 100, &mut rng), [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_all_spin() {
+        let spin = Clear {
+            lines: 2,
+            spin: true,
+            ..Default::default()
+        };
+        assert_eq!(AttackTable::default().lines(&spin, 0), 1);
+
+        let table = AttackTable {
+            all_spin: true,
+            ..Default::default()
+        };
+        assert_eq!(table.lines(&spin, 0), 4);
+    }
+}
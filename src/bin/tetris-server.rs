@@ -0,0 +1,617 @@
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tetris_rust::protocol::{
+    self, Handicap, Handshake, Message, Targeting, DEFAULT_ADDR, MAX_PLAYERS, PROTOCOL_VERSION,
+};
+
+#[derive(Debug)]
+struct Player {
+    id: usize,
+    name: String,
+    handicap: Handicap,
+    stream: TcpStream,
+    /// Whether the player topped out or left a running match.
+    out: bool,
+    /// Rows filled on the player's last board snapshot.
+    rows: usize,
+    /// The player's last board snapshot, for spectators who come in late.
+    board: Vec<String>,
+    targeting: Targeting,
+    /// Index of the opponent the player picked by hand.
+    manual: usize,
+    /// Id of the opponent who last attacked the player.
+    attacker: Option<usize>,
+}
+
+impl Player {
+    fn send(&self, msg: &Message) {
+        // a broken connection is noticed by that player's own thread
+        let _ = protocol::send(&mut &self.stream, msg);
+    }
+}
+
+/// Someone watching a room without playing.
+#[derive(Debug)]
+struct Spectator {
+    id: usize,
+    stream: TcpStream,
+}
+
+impl Spectator {
+    fn send(&self, msg: &Message) {
+        let _ = protocol::send(&mut &self.stream, msg);
+    }
+}
+
+#[derive(Debug)]
+struct Room {
+    handshake: Handshake,
+    seed: u64,
+    /// Players in the order they joined, kept once the match starts so
+    /// their opponents' indices stay put.
+    players: Vec<Player>,
+    spectators: Vec<Spectator>,
+}
+
+impl Room {
+    fn started(&self) -> bool {
+        self.players.len() == self.handshake.players
+    }
+
+    /// Index of the player `id` in the room, which spectators know them by.
+    fn seat(&self, id: usize) -> Option<usize> {
+        self.players.iter().position(|p| p.id == id)
+    }
+
+    /// Index of `sender` among the opponents of `receiver`, or `None` if
+    /// either isn't in the room.
+    fn from(&self, sender: usize, receiver: usize) -> Option<usize> {
+        let (sender, receiver) = (self.seat(sender)?, self.seat(receiver)?);
+        Some(sender - usize::from(sender > receiver))
+    }
+
+    /// Catches `spectator` up on the match: who plays, their boards so far
+    /// and who is already out.
+    fn show(&self, spectator: &Spectator) {
+        spectator.send(&Message::Watching {
+            players: self.players.iter().map(|p| p.name.clone()).collect(),
+            width: self.handshake.width,
+            height: self.handshake.height,
+        });
+        for (from, player) in self.players.iter().enumerate() {
+            if !player.board.is_empty() {
+                spectator.send(&Message::Board {
+                    rows: player.board.clone(),
+                    from,
+                });
+            }
+            if player.out {
+                spectator.send(&Message::Eliminated { from });
+            }
+        }
+    }
+}
+
+type Rooms = Arc<Mutex<HashMap<String, Room>>>;
+
+fn main() -> Result<()> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDR.to_owned());
+    let listener = TcpListener::bind(&addr)?;
+    println!("listening on {addr}");
+
+    let rooms = Rooms::default();
+    for (id, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("failed to accept connection: {e}");
+                continue;
+            }
+        };
+
+        let rooms = rooms.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_client(id, stream, &rooms) {
+                eprintln!("client {id}: {e}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_client(id: usize, stream: TcpStream, rooms: &Rooms) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let (room, name, handshake, handicap) = match protocol::recv(&mut reader).unwrap_or(None) {
+        Some(Message::Join {
+            room,
+            name,
+            handshake,
+            handicap,
+        }) => (room, name, handshake, handicap),
+        Some(Message::Watch { room, version }) => {
+            watch(rooms, &room, version, Spectator { id, stream })?;
+            // spectators have nothing to say, so this only waits for them
+            // to go
+            while let Ok(Some(_)) = protocol::recv(&mut reader) {}
+            unwatch(id, &room, rooms);
+            return Ok(());
+        }
+        _ => {
+            let reason = format!("expected join for protocol v{PROTOCOL_VERSION}");
+            reject(&stream, &reason);
+            bail!(reason);
+        }
+    };
+
+    let player = Player {
+        id,
+        name,
+        handicap,
+        stream,
+        out: false,
+        rows: 0,
+        board: Vec::new(),
+        targeting: Targeting::default(),
+        manual: 0,
+        attacker: None,
+    };
+    join(rooms, &room, handshake, player)?;
+    let result = relay(id, &room, &mut reader, rooms);
+    leave(id, &room, rooms);
+    result
+}
+
+fn reject(mut stream: &TcpStream, reason: &str) {
+    let _ = protocol::send(
+        &mut stream,
+        &Message::Error {
+            reason: reason.to_owned(),
+        },
+    );
+}
+
+fn join(rooms: &Rooms, name: &str, handshake: Handshake, player: Player) -> Result<()> {
+    let server = Handshake {
+        version: PROTOCOL_VERSION,
+        ..handshake.clone()
+    };
+    if let Err(e) = server.check(&handshake) {
+        reject(&player.stream, &e.to_string());
+        return Err(e);
+    }
+    if !(2..=MAX_PLAYERS).contains(&handshake.players) {
+        let reason = format!(
+            "matches are for 2 to {MAX_PLAYERS} players, not {}",
+            handshake.players
+        );
+        reject(&player.stream, &reason);
+        bail!(reason);
+    }
+
+    let mut rooms = rooms.lock().unwrap();
+    let room = rooms.entry(name.to_owned()).or_insert_with(|| Room {
+        handshake: handshake.clone(),
+        seed: rand::random(),
+        players: Vec::new(),
+        spectators: Vec::new(),
+    });
+    if let Err(e) = room.handshake.check(&handshake) {
+        reject(&player.stream, &e.to_string());
+        return Err(e);
+    }
+    if room.started() {
+        reject(&player.stream, "room is full");
+        bail!("room {name} is full");
+    }
+
+    room.players.push(player);
+    if room.started() {
+        for player in &room.players {
+            let opponents = room.players.iter().filter(|p| p.id != player.id);
+            player.send(&Message::Start {
+                opponents: opponents.clone().map(|p| p.name.clone()).collect(),
+                seed: room.seed,
+                handicap: player.handicap.own
+                    + opponents.map(|p| p.handicap.opponent).sum::<usize>(),
+            });
+        }
+        for spectator in &room.spectators {
+            room.show(spectator);
+        }
+    }
+
+    Ok(())
+}
+
+/// Lets `spectator` watch the room `name`, catching them up on its match if
+/// it's already on.
+fn watch(rooms: &Rooms, name: &str, version: u32, spectator: Spectator) -> Result<()> {
+    if version != PROTOCOL_VERSION {
+        let reason =
+            format!("protocol version mismatch: expected v{PROTOCOL_VERSION}, got v{version}");
+        reject(&spectator.stream, &reason);
+        bail!(reason);
+    }
+
+    let mut rooms = rooms.lock().unwrap();
+    let Some(room) = rooms.get_mut(name) else {
+        reject(&spectator.stream, "no such room");
+        bail!("room {name} doesn't exist");
+    };
+    if room.started() {
+        room.show(&spectator);
+    }
+    room.spectators.push(spectator);
+    Ok(())
+}
+
+fn unwatch(id: usize, room: &str, rooms: &Rooms) {
+    if let Some(room) = rooms.lock().unwrap().get_mut(room) {
+        room.spectators.retain(|s| s.id != id);
+    }
+}
+
+/// Forwards board snapshots to every opponent and spectator, and garbage and
+/// items to one opponent still playing, until the player disconnects,
+/// eliminating them when they top out.
+fn relay(id: usize, room: &str, reader: &mut BufReader<TcpStream>, rooms: &Rooms) -> Result<()> {
+    while let Some(msg) = protocol::recv(reader)? {
+        match msg {
+            Message::Board { rows, .. } => {
+                let mut rooms = rooms.lock().unwrap();
+                if let Some(room) = rooms.get_mut(room) {
+                    if let Some(player) = room.players.iter_mut().find(|p| p.id == id) {
+                        player.rows = protocol::filled_rows(&rows);
+                        player.board = rows.clone();
+                    }
+                    for p in room.players.iter().filter(|p| p.id != id) {
+                        if let Some(from) = room.from(id, p.id) {
+                            p.send(&Message::Board {
+                                rows: rows.clone(),
+                                from,
+                            });
+                        }
+                    }
+                    if let Some(from) = room.seat(id) {
+                        for spectator in &room.spectators {
+                            spectator.send(&Message::Board {
+                                rows: rows.clone(),
+                                from,
+                            });
+                        }
+                    }
+                }
+            }
+            Message::Garbage { lines, .. } => {
+                attack(room, id, rooms, |from| Message::Garbage { lines, from })
+            }
+            Message::Item { item, .. } => {
+                attack(room, id, rooms, |from| Message::Item { item, from })
+            }
+            Message::Target {
+                targeting,
+                opponent,
+            } => {
+                let mut rooms = rooms.lock().unwrap();
+                let player = rooms
+                    .get_mut(room)
+                    .and_then(|room| room.players.iter_mut().find(|p| p.id == id));
+                if let Some(player) = player {
+                    player.targeting = targeting;
+                    player.manual = opponent;
+                }
+            }
+            Message::Lost => eliminate(room, id, rooms),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the message made by `msg` to the opponent of `attacker` still
+/// playing that their targeting picks.
+fn attack(room: &str, attacker: usize, rooms: &Rooms, msg: impl Fn(usize) -> Message) {
+    let mut rooms = rooms.lock().unwrap();
+    let Some(room) = rooms.get_mut(room) else {
+        return;
+    };
+    let Some(player) = room.players.iter().find(|p| p.id == attacker) else {
+        return;
+    };
+    let opponents: Vec<&Player> = room.players.iter().filter(|p| p.id != attacker).collect();
+    let rows: Vec<_> = opponents
+        .iter()
+        .map(|p| (!p.out).then_some(p.rows))
+        .collect();
+    let last_attacker = player
+        .attacker
+        .and_then(|id| opponents.iter().position(|p| p.id == id));
+    let Some(target) = player.targeting.pick(
+        &rows,
+        last_attacker,
+        player.manual,
+        rand::random::<u32>() as usize,
+    ) else {
+        return;
+    };
+
+    let target = opponents[target].id;
+    let Some(from) = room.from(attacker, target) else {
+        return;
+    };
+    if let Some(target) = room.players.iter_mut().find(|p| p.id == target) {
+        target.send(&msg(from));
+        target.attacker = Some(attacker);
+    }
+}
+
+/// Takes the player out of the match, which ends for them as lost, and ends
+/// it as won for the last one standing, closing the room. Spectators see
+/// every player go out.
+fn eliminate(room: &str, loser: usize, rooms: &Rooms) {
+    let mut rooms = rooms.lock().unwrap();
+    let Some(r) = rooms.get_mut(room) else {
+        return;
+    };
+    let Some(player) = r.players.iter_mut().find(|p| p.id == loser && !p.out) else {
+        return;
+    };
+    player.out = true;
+    player.send(&Message::GameOver { won: false });
+
+    let playing: Vec<&Player> = r.players.iter().filter(|p| !p.out).collect();
+    for p in &playing {
+        if let Some(from) = r.from(loser, p.id) {
+            p.send(&Message::Eliminated { from });
+        }
+    }
+    if let Some(from) = r.seat(loser) {
+        for spectator in &r.spectators {
+            spectator.send(&Message::Eliminated { from });
+        }
+    }
+    if let [winner] = playing[..] {
+        winner.send(&Message::GameOver { won: true });
+        rooms.remove(room);
+    }
+}
+
+fn leave(id: usize, room: &str, rooms: &Rooms) {
+    let started = {
+        let mut rooms = rooms.lock().unwrap();
+        let Some(r) = rooms.get_mut(room) else {
+            return;
+        };
+        let started = r.started();
+        if !started {
+            r.players.retain(|p| p.id != id);
+            if r.players.is_empty() {
+                for spectator in &r.spectators {
+                    spectator.send(&Message::Error {
+                        reason: "everyone left the room".into(),
+                    });
+                }
+                rooms.remove(room);
+            }
+        }
+        started
+    };
+
+    // leaving a running match forfeits it
+    if started {
+        eliminate(room, id, rooms);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tetris_rust::attack::AttackTable;
+
+    /// Both ends of a connection: the server's, and the client's that its
+    /// messages arrive on.
+    fn connection() -> (TcpStream, BufReader<TcpStream>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        (stream, BufReader::new(client))
+    }
+
+    fn player(id: usize, stream: TcpStream) -> Player {
+        Player {
+            id,
+            name: format!("player {id}"),
+            handicap: Handicap::default(),
+            stream,
+            out: false,
+            rows: 0,
+            board: Vec::new(),
+            targeting: Targeting::default(),
+            manual: 0,
+            attacker: None,
+        }
+    }
+
+    fn handshake(players: usize) -> Handshake {
+        Handshake {
+            version: PROTOCOL_VERSION,
+            ruleset: "classic".into(),
+            width: 10,
+            height: 20,
+            attack: AttackTable::default(),
+            items: false,
+            players,
+        }
+    }
+
+    /// A room of `players` players called `"room"`, each with the end of
+    /// the connection their messages arrive on.
+    fn room(players: usize) -> (Rooms, Vec<BufReader<TcpStream>>) {
+        let mut seats = Vec::new();
+        let mut clients = Vec::new();
+        for id in 0..players {
+            let (stream, client) = connection();
+            seats.push(player(id, stream));
+            clients.push(client);
+        }
+        let room = Room {
+            handshake: handshake(players),
+            seed: 0,
+            players: seats,
+            spectators: Vec::new(),
+        };
+        let rooms = Rooms::default();
+        rooms.lock().unwrap().insert("room".into(), room);
+        (rooms, clients)
+    }
+
+    fn recv(client: &mut BufReader<TcpStream>) -> Message {
+        protocol::recv(client).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_join() {
+        let rooms = Rooms::default();
+        for players in [0, 1, MAX_PLAYERS + 1] {
+            let (stream, mut client) = connection();
+            assert!(join(&rooms, "room", handshake(players), player(0, stream)).is_err());
+            assert!(matches!(recv(&mut client), Message::Error { .. }));
+        }
+        assert!(rooms.lock().unwrap().is_empty());
+
+        let (stream, _client) = connection();
+        join(&rooms, "room", handshake(2), player(0, stream)).unwrap();
+        assert_eq!(rooms.lock().unwrap()["room"].players.len(), 1);
+    }
+
+    #[test]
+    fn test_from() {
+        let (rooms, _clients) = room(3);
+        let rooms = rooms.lock().unwrap();
+        let room = &rooms["room"];
+        assert_eq!(room.from(0, 1), Some(0));
+        assert_eq!(room.from(2, 1), Some(1));
+        assert_eq!(room.from(1, 0), Some(0));
+        assert_eq!(room.from(1, 2), Some(1));
+        // strangers aren't anyone's opponent
+        assert_eq!(room.from(7, 1), None);
+        assert_eq!(room.from(1, 7), None);
+    }
+
+    #[test]
+    fn test_attack() {
+        let (rooms, mut clients) = room(3);
+        {
+            let mut rooms = rooms.lock().unwrap();
+            let player = &mut rooms.get_mut("room").unwrap().players[2];
+            player.targeting = Targeting::Manual;
+            player.manual = 1;
+        }
+        // the second opponent of player 2 is player 1
+        attack("room", 2, &rooms, |from| Message::Garbage {
+            lines: 3,
+            from,
+        });
+        assert_eq!(
+            recv(&mut clients[1]),
+            Message::Garbage { lines: 3, from: 1 }
+        );
+        let attacker = |id: usize| rooms.lock().unwrap()["room"].players[id].attacker;
+        assert_eq!(attacker(1), Some(2));
+
+        // nobody takes an attack from a stranger
+        attack("room", 7, &rooms, |from| Message::Garbage {
+            lines: 3,
+            from,
+        });
+        assert_eq!((attacker(0), attacker(2)), (None, None));
+    }
+
+    #[test]
+    fn test_eliminate() {
+        let (rooms, mut clients) = room(3);
+        eliminate("room", 0, &rooms);
+        assert_eq!(recv(&mut clients[0]), Message::GameOver { won: false });
+        for client in &mut clients[1..] {
+            assert_eq!(recv(client), Message::Eliminated { from: 0 });
+        }
+        // already out
+        eliminate("room", 0, &rooms);
+        assert!(rooms.lock().unwrap().contains_key("room"));
+
+        eliminate("room", 2, &rooms);
+        assert_eq!(recv(&mut clients[2]), Message::GameOver { won: false });
+        assert_eq!(recv(&mut clients[1]), Message::Eliminated { from: 1 });
+        assert_eq!(recv(&mut clients[1]), Message::GameOver { won: true });
+        // the match is over
+        assert!(!rooms.lock().unwrap().contains_key("room"));
+    }
+
+    #[test]
+    fn test_watch() {
+        let (rooms, _clients) = room(3);
+        let board = vec!["....".to_owned(), "##.#".to_owned()];
+        rooms.lock().unwrap().get_mut("room").unwrap().players[1].board = board.clone();
+        eliminate("room", 2, &rooms);
+
+        // coming in late catches the spectator up
+        let (stream, mut spectator) = connection();
+        watch(
+            &rooms,
+            "room",
+            PROTOCOL_VERSION,
+            Spectator { id: 9, stream },
+        )
+        .unwrap();
+        assert_eq!(
+            recv(&mut spectator),
+            Message::Watching {
+                players: vec!["player 0".into(), "player 1".into(), "player 2".into()],
+                width: 10,
+                height: 20,
+            }
+        );
+        assert_eq!(
+            recv(&mut spectator),
+            Message::Board {
+                rows: board,
+                from: 1
+            }
+        );
+        assert_eq!(recv(&mut spectator), Message::Eliminated { from: 2 });
+
+        eliminate("room", 0, &rooms);
+        assert_eq!(recv(&mut spectator), Message::Eliminated { from: 0 });
+
+        let (stream, mut stranger) = connection();
+        assert!(watch(
+            &rooms,
+            "nowhere",
+            PROTOCOL_VERSION,
+            Spectator { id: 10, stream }
+        )
+        .is_err());
+        assert!(matches!(recv(&mut stranger), Message::Error { .. }));
+        let (stream, _) = connection();
+        assert!(watch(
+            &rooms,
+            "room",
+            PROTOCOL_VERSION + 1,
+            Spectator { id: 11, stream }
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,498 @@
+use crate::{
+    attack::{self, AttackTable},
+    block::{Block, PieceKind},
+    board::{Board, Cell},
+    puzzle::Clear,
+    tbp::Engine,
+};
+use clap::ValueEnum;
+use rand::{
+    seq::{IndexedRandom, SliceRandom},
+    Rng, SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::time::Duration;
+use tracing::warn;
+
+/// Rows a piece falls before it's turned, as a player would let it.
+const TURNING_ROOM: usize = 2;
+/// Pieces an [`Engine`] playing for a bot is shown, the falling one
+/// included.
+const ENGINE_QUEUE: usize = 6;
+
+/// Weights of what a bot scores the board left by a placement on, the
+/// higher the better.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    /// Per row of the heights of all columns added up.
+    pub height: f64,
+    /// Per line cleared.
+    pub lines: f64,
+    /// Per empty cell with a filled cell somewhere above it.
+    pub holes: f64,
+    /// Per row of difference in height between neighboring columns.
+    pub bumpiness: f64,
+}
+
+impl Default for Weights {
+    /// Weights found by a genetic search for a bot playing without a
+    /// preview, as described by Yiyuan Lee.
+    fn default() -> Self {
+        Self {
+            height: -0.510066,
+            lines: 0.760666,
+            holes: -0.35663,
+            bumpiness: -0.184483,
+        }
+    }
+}
+
+/// How well bots play, from beginner to expert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    /// Slow, careless about holes and often dropping pieces at random
+    Easy,
+    /// A piece a little under every second, now and then dropped at random
+    #[default]
+    Medium,
+    /// Fast, rarely wrong, and looking ahead at the next piece
+    Hard,
+    /// Several pieces a second, never wrong, looking ahead at the next piece
+    Expert,
+}
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+        }
+    }
+
+    /// How bots of this difficulty play.
+    pub fn skill(self) -> Skill {
+        match self {
+            Difficulty::Easy => Skill {
+                reaction: 1000..2000,
+                mistakes: 0.15,
+                depth: 1,
+                weights: Weights {
+                    height: -0.3,
+                    lines: 0.5,
+                    holes: -0.15,
+                    bumpiness: -0.1,
+                },
+            },
+            Difficulty::Medium => Skill {
+                reaction: 400..1200,
+                mistakes: 0.05,
+                depth: 1,
+                weights: Weights::default(),
+            },
+            Difficulty::Hard => Skill {
+                reaction: 250..600,
+                mistakes: 0.02,
+                depth: 2,
+                weights: Weights::default(),
+            },
+            Difficulty::Expert => Skill {
+                reaction: 100..300,
+                mistakes: 0.0,
+                depth: 2,
+                weights: Weights::default(),
+            },
+        }
+    }
+}
+
+/// How a bot plays.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skill {
+    /// Milliseconds the bot takes for each piece, picked anew every time.
+    pub reaction: Range<u64>,
+    /// Chance of dropping a piece somewhere at random.
+    pub mistakes: f64,
+    /// Pieces the bot plans for, the falling one and those after it.
+    pub depth: usize,
+    pub weights: Weights,
+}
+
+/// A spot a piece can be dropped on, and the score of the board it leaves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    /// The piece where it lands, before any lines are cleared.
+    pub block: Block,
+    pub score: f64,
+}
+
+/// A piece of a [`Plan`], where it lands and the lines it clears.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Move {
+    pub block: Block,
+    pub lines: usize,
+}
+
+/// Where a bot means to drop the pieces it plans for, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    /// Every piece lands on the board the ones before it leave, so the
+    /// rows of a move after a clear are counted after the clear.
+    pub line: Vec<Move>,
+    /// Score of the board the last of them leaves.
+    pub score: f64,
+}
+
+/// Every spot a `kind` piece spawning on `board` can be dropped on, turned
+/// and slid sideways near the top, scored by `weights`. Empty if the piece
+/// can't spawn. The falling block of `board`, if any, is ignored.
+pub fn placements<T: Cell>(
+    board: &Board<T>,
+    kind: PieceKind,
+    value: T,
+    weights: &Weights,
+) -> Vec<Placement> {
+    let mut spawned = board.clone();
+    spawned.remove_block();
+    if spawned.spawn(kind.block(), value).is_err() {
+        return Vec::new();
+    }
+    // room to turn an I upright
+    for _ in 0..TURNING_ROOM {
+        let _ = spawned.down();
+    }
+
+    let mut placements = Vec::new();
+    for turns in 0..4 {
+        let mut turned = spawned.clone();
+        if (0..turns).any(|_| turned.rotate().is_err()) {
+            continue;
+        }
+        while turned.left().is_ok() {}
+        loop {
+            let mut dropped = turned.clone();
+            dropped.hard_drop();
+            let block = dropped.current_block().cloned();
+            let lines = dropped.clear_filled_rows();
+            placements.extend(block.map(|block| Placement {
+                block,
+                score: evaluate(&dropped, lines, weights),
+            }));
+            if turned.right().is_err() {
+                break;
+            }
+        }
+    }
+    placements
+}
+
+/// The best scoring spot to drop a `kind` piece on, as in [`placements`].
+pub fn best_placement<T: Cell>(
+    board: &Board<T>,
+    kind: PieceKind,
+    value: T,
+    weights: &Weights,
+) -> Option<Placement> {
+    placements(board, kind, value, weights)
+        .into_iter()
+        .max_by(|a, b| a.score.total_cmp(&b.score))
+}
+
+/// The best scoring spots to drop `pieces` on one after the other, the
+/// first of them on `board`.
+pub fn plan<T: Cell>(
+    board: &Board<T>,
+    pieces: &[PieceKind],
+    value: T,
+    weights: &Weights,
+) -> Option<Plan> {
+    let (&kind, rest) = pieces.split_first()?;
+    let mut best: Option<Plan> = None;
+    for placement in placements(board, kind, value.clone(), weights) {
+        let mut after = board.clone();
+        after.remove_block();
+        let lines = lock(&mut after, &placement.block, value.clone());
+        let (mut line, score) = if rest.is_empty() {
+            (Vec::new(), placement.score)
+        } else {
+            // placements leaving no room for the pieces after them come last
+            match plan(&after, rest, value.clone(), weights) {
+                Some(next) => (next.line, next.score + weights.lines * lines as f64),
+                None => (Vec::new(), f64::NEG_INFINITY),
+            }
+        };
+        if best.as_ref().is_none_or(|best| score > best.score) {
+            line.insert(
+                0,
+                Move {
+                    block: placement.block,
+                    lines,
+                },
+            );
+            best = Some(Plan { line, score });
+        }
+    }
+    best
+}
+
+/// Fills the cells of `block` on `board` and clears the lines it completes,
+/// returning how many.
+pub fn lock<T: Cell>(board: &mut Board<T>, block: &Block, value: T) -> usize {
+    for &(x, y) in block.coords() {
+        board.set(x as usize, y as usize, value.clone());
+    }
+    board.clear_filled_rows()
+}
+
+/// Scores `board` after a placement that cleared `lines` lines.
+fn evaluate<T: Cell>(board: &Board<T>, lines: usize, weights: &Weights) -> f64 {
+    let heights: Vec<usize> = (0..board.width())
+        .map(|x| {
+            (0..board.height())
+                .position(|y| board.get(x, y).is_some())
+                .map_or(0, |y| board.height() - y)
+        })
+        .collect();
+    let bumpiness: usize = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+
+    weights.height * heights.iter().sum::<usize>() as f64
+        + weights.lines * lines as f64
+        + weights.holes * board.holes() as f64
+        + weights.bumpiness * bumpiness as f64
+}
+
+/// A computer player on a board of its own, dropping each piece from a
+/// 7-bag where it scores best, or now and then somewhere at random, as
+/// well as its skill lets it.
+#[derive(Debug, Clone)]
+pub struct Bot {
+    board: Board<()>,
+    rng: ChaCha8Rng,
+    bag: Vec<PieceKind>,
+    /// Pieces drawn from the bag ahead, to plan for.
+    queue: VecDeque<PieceKind>,
+    skill: Skill,
+    /// Clears in a row before the last one, if the last lock cleared lines.
+    combo: Option<usize>,
+    /// Lines of garbage to rise after the next piece, unless canceled.
+    pending: u32,
+    out: bool,
+}
+
+impl Bot {
+    /// A bot on an empty board `width` by `height` cells, with pieces drawn
+    /// from an RNG seeded with `seed`, playing with `skill`.
+    pub fn new(width: usize, height: usize, seed: u64, skill: Skill) -> Self {
+        Self {
+            board: Board::new(width, height),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            bag: Vec::new(),
+            queue: VecDeque::new(),
+            skill: Skill {
+                mistakes: skill.mistakes.clamp(0.0, 1.0),
+                depth: skill.depth.max(1),
+                ..skill
+            },
+            combo: None,
+            pending: 0,
+            out: false,
+        }
+    }
+
+    pub fn board(&self) -> &Board<()> {
+        &self.board
+    }
+
+    /// Whether the bot has topped out.
+    pub fn is_out(&self) -> bool {
+        self.out
+    }
+
+    /// Time the bot takes for its next piece.
+    pub fn piece_time(&mut self) -> Duration {
+        let reaction = &self.skill.reaction;
+        let ms = if reaction.is_empty() {
+            reaction.start
+        } else {
+            self.rng.random_range(reaction.clone())
+        };
+        Duration::from_millis(ms)
+    }
+
+    /// Lines of garbage waiting to rise.
+    pub fn pending(&self) -> u32 {
+        self.pending
+    }
+
+    /// Queues `lines` of garbage to rise after the bot's next piece.
+    pub fn receive(&mut self, lines: u32) {
+        self.pending += lines;
+    }
+
+    /// Drops the next piece, then lets the garbage its clear didn't cancel
+    /// rise. Returns the lines of garbage the clear sends by `attack`, and
+    /// tops out if the piece can't spawn or the garbage pushes the stack out.
+    pub fn step(&mut self, attack: &AttackTable) -> u32 {
+        self.step_with(attack, None)
+    }
+
+    /// Like [`step`](Self::step), but dropping the piece where `engine`
+    /// suggests, if given, and only by the bot's own plan if it can't.
+    pub fn step_with(&mut self, attack: &AttackTable, engine: Option<&mut Engine>) -> u32 {
+        if self.out {
+            return 0;
+        }
+        let depth = match engine {
+            Some(_) => ENGINE_QUEUE.max(self.skill.depth),
+            None => self.skill.depth,
+        };
+        while self.queue.len() < depth {
+            if self.bag.is_empty() {
+                self.bag = PieceKind::ALL.to_vec();
+                self.bag.shuffle(&mut self.rng);
+            }
+            self.queue.push_back(self.bag.pop().unwrap());
+        }
+        let pieces: Vec<_> = self.queue.iter().copied().collect();
+        let kind = self.queue.pop_front().unwrap();
+
+        let weights = &self.skill.weights;
+        let suggested = engine.and_then(|engine| {
+            engine
+                .suggest(&self.board, &pieces, |_| None)
+                .inspect_err(|err| warn!("{} failed to suggest a move: {err:#}", engine.name()))
+                .ok()
+        });
+        let block = if suggested.is_some() {
+            suggested
+        } else if self.rng.random_bool(self.skill.mistakes) {
+            placements(&self.board, kind, (), weights)
+                .choose(&mut self.rng)
+                .map(|placement| placement.block.clone())
+        } else {
+            let pieces = &pieces[..self.skill.depth];
+            plan(&self.board, pieces, (), weights).map(|plan| plan.line[0].block.clone())
+        };
+        let Some(block) = block else {
+            self.out = true;
+            return 0;
+        };
+
+        let lines = lock(&mut self.board, &block, ());
+        let clear = Clear {
+            lines,
+            perfect: lines > 0 && self.board.is_empty(),
+            ..Clear::default()
+        };
+        self.combo = (lines > 0).then(|| self.combo.map_or(0, |combo| combo + 1));
+        let sent = attack.lines(&clear, self.combo.unwrap_or(0));
+        let (sent, pending) = attack::cancel(sent, std::mem::take(&mut self.pending));
+        let hole = self.rng.random_range(0..self.board.width());
+        if self.board.add_garbage(pending as usize, hole, ()).is_err() {
+            self.out = true;
+        }
+        sent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_placements() {
+        let board: Board<()> = Board::new(10, 20);
+        // an O fits in 9 columns, however it's turned
+        let spots = placements(&board, PieceKind::O, (), &Weights::default());
+        assert_eq!(spots.len(), 4 * 9);
+        // an I lies flat in 7 columns or stands upright in 10
+        let spots = placements(&board, PieceKind::I, (), &Weights::default());
+        assert_eq!(spots.len(), 2 * (7 + 10));
+
+        // the best spot for an upright I is the well it fills
+        let mut board: Board<()> = Board::new(10, 20);
+        for y in 16..20 {
+            for x in 0..9 {
+                board.set(x, y, ());
+            }
+        }
+        let best = best_placement(&board, PieceKind::I, (), &Weights::default()).unwrap();
+        let mut cells = best.block.coords().to_vec();
+        cells.sort_unstable();
+        assert_eq!(cells, [(9, 16), (9, 17), (9, 18), (9, 19)]);
+
+        // nothing can be dropped once the piece can't spawn
+        for x in 0..10 {
+            board.set(x, 0, ());
+        }
+        assert!(best_placement(&board, PieceKind::T, (), &Weights::default()).is_none());
+    }
+
+    #[test]
+    fn test_bot() {
+        let attack = AttackTable::default();
+        let careful = Skill {
+            mistakes: 0.0,
+            ..Difficulty::Medium.skill()
+        };
+        let mut bot = Bot::new(10, 20, 7, careful);
+        for _ in 0..100 {
+            bot.step(&attack);
+        }
+        // a careful bot keeps its stack low
+        assert!(!bot.is_out());
+        assert!(bot.board().stack_height() < 10);
+
+        bot.receive(30);
+        assert_eq!(bot.pending(), 30);
+        bot.step(&attack);
+        assert!(bot.is_out());
+        assert_eq!(bot.step(&attack), 0);
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let attack = AttackTable::default();
+        let mut last = Duration::MAX;
+        for difficulty in [
+            Difficulty::Easy,
+            Difficulty::Medium,
+            Difficulty::Hard,
+            Difficulty::Expert,
+        ] {
+            let mut bot = Bot::new(10, 20, 7, difficulty.skill());
+            // harder bots are faster
+            let time = bot.piece_time();
+            assert!(time < last, "{} is too slow", difficulty.name());
+            last = time;
+            for _ in 0..20 {
+                bot.step(&attack);
+            }
+            assert!(!bot.is_out(), "{} topped out", difficulty.name());
+        }
+
+        // looking ahead, an O goes beside the well an I fills after it
+        let mut board: Board<()> = Board::new(6, 10);
+        for x in 0..5 {
+            board.set(x, 9, ());
+        }
+        let weights = Weights::default();
+        let planned = plan(&board, &[PieceKind::O, PieceKind::I], (), &weights).unwrap();
+        assert_eq!(planned.line.len(), 2);
+        assert!(planned.line[0].block.coords().iter().all(|&(x, _)| x < 5));
+        // the I fills the well to clear the bottom row
+        assert_eq!(planned.line[0].lines, 0);
+        assert_eq!(planned.line[1].lines, 1);
+        // planning for one piece is picking its best placement
+        let best = best_placement(&board, PieceKind::O, (), &weights).unwrap();
+        assert_eq!(
+            plan(&board, &[PieceKind::O], (), &weights).unwrap().score,
+            best.score
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+/// Full speed, in percent.
+pub const FULL_SPEED: u32 = 100;
+
+/// Time of a game: what passed since it started, without pauses, at the
+/// speed it's played at.
+///
+/// Everything timed in the game goes by this clock, so slowing it down slows
+/// gravity, lock delay and animations alike.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    /// Game time when the clock was last started, paused or sped up.
+    base: Duration,
+    /// Since when it's been running at `speed`, `None` while paused.
+    since: Option<Instant>,
+    /// Percent of wall time the game time passes at.
+    speed: u32,
+    /// Whether the game was ever played below full speed.
+    slowed: bool,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            base: Duration::ZERO,
+            since: Some(Instant::now()),
+            speed: FULL_SPEED,
+            slowed: false,
+        }
+    }
+}
+
+impl Clock {
+    /// Time of the game so far.
+    pub fn elapsed(&self) -> Duration {
+        let running = self.since.map_or(Duration::ZERO, |since| {
+            since.elapsed() * self.speed / FULL_SPEED
+        });
+        self.base + running
+    }
+
+    /// Starts over from zero, at the same speed.
+    pub fn restart(&mut self) {
+        self.base = Duration::ZERO;
+        self.since = Some(Instant::now());
+    }
+
+    /// Stops the time until [`Clock::resume`].
+    pub fn pause(&mut self) {
+        self.base = self.elapsed();
+        self.since = None;
+    }
+
+    pub fn resume(&mut self) {
+        if self.since.is_none() {
+            self.since = Some(Instant::now());
+        }
+    }
+
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    /// Plays on at `speed` percent of wall time, from now on.
+    pub fn set_speed(&mut self, speed: u32) {
+        self.base = self.elapsed();
+        if self.since.is_some() {
+            self.since = Some(Instant::now());
+        }
+        self.speed = speed.clamp(1, FULL_SPEED);
+        self.slowed |= self.speed < FULL_SPEED;
+    }
+
+    /// Whether any part of the game was played below full speed.
+    pub fn slowed(&self) -> bool {
+        self.slowed
+    }
+
+    /// Moves the time on by `by`, as if it had passed.
+    pub fn skip(&mut self, by: Duration) {
+        self.base += by;
+    }
+}
+
+/// Which way the time of a mode runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timer {
+    /// Counts the time taken up from zero.
+    Up,
+    /// Counts the time left down to zero, when the game ends.
+    Down(Duration),
+}
+
+impl Timer {
+    /// Time to show once `elapsed` has passed.
+    pub fn shown(self, elapsed: Duration) -> Duration {
+        match self {
+            Timer::Up => elapsed,
+            Timer::Down(limit) => limit.saturating_sub(elapsed),
+        }
+    }
+
+    /// Whether the time is up once `elapsed` has passed.
+    pub fn expired(self, elapsed: Duration) -> bool {
+        match self {
+            Timer::Up => false,
+            Timer::Down(limit) => elapsed >= limit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer() {
+        let elapsed = Duration::from_secs(50);
+        assert_eq!(Timer::Up.shown(elapsed), elapsed);
+        assert!(!Timer::Up.expired(elapsed));
+
+        let down = Timer::Down(Duration::from_secs(60));
+        assert_eq!(down.shown(elapsed), Duration::from_secs(10));
+        assert!(!down.expired(elapsed));
+        assert_eq!(down.shown(Duration::from_secs(70)), Duration::ZERO);
+        assert!(down.expired(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_clock() {
+        let mut clock = Clock::default();
+        clock.pause();
+        let paused = clock.elapsed();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(clock.elapsed(), paused);
+
+        clock.skip(Duration::from_secs(1));
+        assert_eq!(clock.elapsed(), paused + Duration::from_secs(1));
+        clock.set_speed(25);
+        assert!(clock.slowed());
+        assert_eq!(clock.elapsed(), paused + Duration::from_secs(1));
+
+        clock.restart();
+        std::thread::sleep(Duration::from_millis(80));
+        let elapsed = clock.elapsed();
+        assert!(elapsed >= Duration::from_millis(20));
+        assert!(elapsed < Duration::from_millis(60));
+
+        clock.set_speed(FULL_SPEED);
+        assert!(clock.slowed());
+        clock.set_speed(0);
+        assert_eq!(clock.speed(), 1);
+    }
+}
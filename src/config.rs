@@ -0,0 +1,856 @@
+use crate::attack::AttackTable;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use directories::ProjectDirs;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use ratatui::symbols::border;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::OnceLock;
+use std::time::Duration;
+pub use tetris_core::scoring::Scoring;
+
+/// Directory of the executable, once `--portable` keeps everything there.
+static PORTABLE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keeps the settings, statistics and saved files beside the executable
+/// instead of in the directories of the platform.
+pub fn make_portable() -> Result<()> {
+    let exe = std::env::current_exe().context("failed to find the executable")?;
+    let dir = exe.parent().map(Path::to_owned).unwrap_or_default();
+    PORTABLE.get_or_init(|| dir);
+    Ok(())
+}
+
+/// Directory for the statistics and saved files: the platform's data
+/// directory, e.g. `~/.local/share/tetris-rust` on Linux.
+pub fn data_dir() -> Result<PathBuf> {
+    dir(ProjectDirs::data_dir)
+}
+
+/// Directory for the settings: the platform's config directory, e.g.
+/// `~/.config/tetris-rust` on Linux.
+pub fn config_dir() -> Result<PathBuf> {
+    dir(ProjectDirs::config_dir)
+}
+
+/// The directory `pick` chooses of the platform's, as [`choose_dir`] says.
+fn dir(pick: fn(&ProjectDirs) -> &Path) -> Result<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+    let platform = ProjectDirs::from("", "", "tetris-rust").map(|dirs| pick(&dirs).to_owned());
+    choose_dir(PORTABLE.get().cloned(), home, platform)
+        .context("no home directory to keep files in, try --portable")
+}
+
+/// `portable` when playing portable, `~/.tetris-rust` in `home` if older
+/// versions kept everything there, or else the `platform` directory,
+/// falling back to `~/.tetris-rust`. `None` without an absolute `home`
+/// to fall back on.
+fn choose_dir(
+    portable: Option<PathBuf>,
+    home: Option<PathBuf>,
+    platform: Option<PathBuf>,
+) -> Option<PathBuf> {
+    if portable.is_some() {
+        return portable;
+    }
+    let legacy = home
+        .filter(|home| home.is_absolute())
+        .map(|home| home.join(".tetris-rust"));
+    if legacy.as_ref().is_some_and(|legacy| legacy.is_dir()) {
+        return legacy;
+    }
+    platform.or(legacy)
+}
+
+/// Most pieces to come that can be previewed.
+pub const MAX_NEXT: usize = 6;
+
+/// Settings, read from `config.toml` in the [`config_dir`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Ruleset to play by, overriding the settings it bundles.
+    pub preset: Option<Preset>,
+    /// Milliseconds between gravity ticks at the start of a game.
+    pub gravity: u64,
+    /// Gravity speeds up by 20ms per point down to this many milliseconds.
+    pub min_gravity: u64,
+    /// How gravity speeds up as the game goes on.
+    pub speed_curve: SpeedCurve,
+    /// Points cleared lines score.
+    pub scoring: Scoring,
+    /// How the pieces to come are picked.
+    pub randomizer: Randomizer,
+    /// How pieces turn where they don't fit.
+    pub rotation: Rotation,
+    /// Milliseconds to wait for input between updates of the screen.
+    pub poll: u64,
+    /// Milliseconds cleared rows stay on screen before collapsing, holding
+    /// back the next piece.
+    pub line_clear_delay: u64,
+    /// Pieces to come shown beside the board, up to [`MAX_NEXT`].
+    pub next: usize,
+    /// How the spot the falling piece would land on is shown.
+    pub ghost: Ghost,
+    /// Guides drawn on empty cells to line pieces up with the columns.
+    pub grid: Grid,
+    /// How often pieces can be held.
+    pub hold: HoldMode,
+    /// Milliseconds a piece resting on the stack waits before locking, or 0
+    /// to lock it on the next gravity tick.
+    pub lock_delay: u64,
+    /// Moves that restart the lock delay of a piece resting on the stack.
+    pub lock_resets: LockResets,
+    /// Garbage sent to the opponent in versus matches.
+    pub attack: AttackTable,
+    /// Whether clearing lines charges a meter that can be spent to stop
+    /// gravity in single player games.
+    pub zone: bool,
+    /// Characters cells are drawn with.
+    pub skin: Skin,
+    /// Colors the game is drawn in.
+    pub theme: Theme,
+    /// Lines the boxes on screen are drawn with.
+    pub border: Border,
+    /// Title of the board when there's nothing more specific to show, empty
+    /// for none.
+    pub title: String,
+    /// Whether versus matches are drawn in a frame naming both players.
+    pub frame: bool,
+    /// Pace plotted beside the board over the last minute.
+    pub sparkline: Sparkline,
+    /// Whether finesse faults are counted beside the board while playing.
+    pub finesse: bool,
+    /// Whether the points of the last clears are listed under the board.
+    pub score_log: bool,
+    /// Whether the pieces dealt since the last I are counted beside the
+    /// board.
+    pub drought: bool,
+    /// Whether the pieces left in the current bag are shown beside the
+    /// board, when pieces come from bags.
+    pub bag_peek: bool,
+    /// Whether the spots a bot would drop the falling piece and the next
+    /// ones on are marked on the board.
+    pub plan: bool,
+    /// Percent chance that the hole of a row of cheese in a dig moves to
+    /// another column from the row below, up to 100.
+    pub messiness: u32,
+    /// ID of the Discord application to show what's being played as, empty
+    /// to show nothing. Only builds with the `discord` feature use it.
+    pub discord: String,
+    /// Whether beating a personal best also sends a desktop notification.
+    pub notify: bool,
+    /// Overrides for single modes, by [`Mode`](crate::tetris::Mode) name.
+    pub modes: BTreeMap<String, ModeConfig>,
+}
+
+/// Rulesets of other Tetris games, bundling the settings that make them
+/// play the way they do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Classic NES: its scoring and gravity, one preview, no hold, no ghost
+    /// and no lock delay
+    Nes,
+    /// Game Boy: the NES scoring with its own gravity, in the green shades
+    /// of its screen
+    #[value(name = "gameboy")]
+    GameBoy,
+    /// The modern standard: a 7-bag, hold, ghost, six previews, lock delay
+    /// and its scoring and gravity
+    Guideline,
+}
+
+impl Preset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::Nes => "nes",
+            Preset::GameBoy => "gameboy",
+            Preset::Guideline => "guideline",
+        }
+    }
+
+    /// Changes the settings of `config` this ruleset bundles.
+    pub fn apply(self, config: &mut Config) {
+        config.preset = Some(self);
+        match self {
+            Preset::Nes => {
+                config.speed_curve = SpeedCurve::Nes;
+                config.scoring = Scoring::Classic;
+                config.randomizer = Randomizer::Nes;
+                config.rotation = Rotation::Simple;
+                config.next = 1;
+                config.hold = HoldMode::Disabled;
+                config.ghost = Ghost::Off;
+                config.lock_delay = 0;
+                config.lock_resets = LockResets::Limited(0);
+            }
+            Preset::GameBoy => {
+                config.speed_curve = SpeedCurve::GameBoy;
+                config.scoring = Scoring::Classic;
+                config.randomizer = Randomizer::Random;
+                config.rotation = Rotation::Simple;
+                config.next = 1;
+                config.hold = HoldMode::Disabled;
+                config.ghost = Ghost::Off;
+                config.lock_delay = 0;
+                config.lock_resets = LockResets::Limited(0);
+                config.theme = Theme::GameBoy;
+            }
+            Preset::Guideline => {
+                config.speed_curve = SpeedCurve::Guideline;
+                config.scoring = Scoring::Guideline;
+                config.randomizer = Randomizer::Bag;
+                config.rotation = Rotation::Srs;
+                config.next = MAX_NEXT;
+                config.hold = HoldMode::Standard;
+                config.ghost = Ghost::Outline;
+                config.lock_delay = 500;
+                config.lock_resets = LockResets::default();
+                config.theme = Theme::Color;
+            }
+        }
+    }
+}
+
+/// Frames a piece takes to fall a row on the NES, by level from 0.
+const NES_FRAMES: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    1,
+];
+/// Frames per second of the NES.
+const NES_FPS: f64 = 60.0988;
+/// Frames a piece takes to fall a row on the Game Boy, by level from 0.
+const GAME_BOY_FRAMES: [u32; 21] = [
+    53, 49, 45, 41, 37, 33, 28, 22, 17, 11, 10, 9, 8, 7, 6, 6, 5, 5, 4, 4, 3,
+];
+/// Frames per second of the Game Boy.
+const GAME_BOY_FPS: f64 = 59.73;
+
+/// Level past which the guideline gravity stops speeding up.
+const GUIDELINE_LEVELS: usize = 20;
+
+/// How gravity speeds up over a game.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedCurve {
+    /// From `gravity` by 20ms for every point scored down to `min_gravity`.
+    #[default]
+    Score,
+    /// Level by level as on the NES.
+    Nes,
+    /// Level by level as on the Game Boy.
+    GameBoy,
+    /// Level by level by the formula of the guideline.
+    Guideline,
+}
+
+impl SpeedCurve {
+    /// Time between gravity ticks at `level`, counted from 1, for the curves
+    /// that go by level.
+    pub fn interval(self, level: usize) -> Option<Duration> {
+        let (frames, fps) = match self {
+            SpeedCurve::Score => return None,
+            SpeedCurve::Nes => (&NES_FRAMES[..], NES_FPS),
+            SpeedCurve::GameBoy => (&GAME_BOY_FRAMES[..], GAME_BOY_FPS),
+            SpeedCurve::Guideline => {
+                let level = level.clamp(1, GUIDELINE_LEVELS) as f64 - 1.0;
+                return Some(Duration::from_secs_f64((0.8 - level * 0.007).powf(level)));
+            }
+        };
+        let frames = frames[(level.max(1) - 1).min(frames.len() - 1)];
+        Some(Duration::from_secs_f64(f64::from(frames) / fps))
+    }
+}
+
+/// Style of the ghost piece: `"off"`, `"outline"`, `"dim"` or a single
+/// character to fill it with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Ghost {
+    Off,
+    #[default]
+    Outline,
+    Dim,
+    Char(char),
+}
+
+impl TryFrom<String> for Ghost {
+    type Error = String;
+
+    fn try_from(style: String) -> Result<Self, Self::Error> {
+        let mut chars = style.chars();
+        Ok(match style.as_str() {
+            "off" => Ghost::Off,
+            "outline" => Ghost::Outline,
+            "dim" => Ghost::Dim,
+            _ => match (chars.next(), chars.next()) {
+                (Some(c), None) => Ghost::Char(c),
+                _ => return Err(format!("unknown ghost style \"{style}\"")),
+            },
+        })
+    }
+}
+
+impl From<Ghost> for String {
+    fn from(ghost: Ghost) -> Self {
+        match ghost {
+            Ghost::Off => "off".to_owned(),
+            Ghost::Outline => "outline".to_owned(),
+            Ghost::Dim => "dim".to_owned(),
+            Ghost::Char(c) => c.to_string(),
+        }
+    }
+}
+
+/// Guides on the empty cells of the board.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Grid {
+    #[default]
+    Off,
+    /// Faint lines between the columns.
+    Lines,
+    /// Every other cell shaded, like a checkerboard.
+    Checker,
+}
+
+/// What the sparkline beside the board plots, second by second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sparkline {
+    #[default]
+    Off,
+    /// Pieces placed per second.
+    Pps,
+    /// Lines of garbage the clears would send per second, by the attack
+    /// table.
+    Attack,
+}
+
+/// How the pieces to come are picked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Randomizer {
+    /// Any of the seven pieces, every time.
+    #[default]
+    Random,
+    /// All seven pieces in a random order, then all seven again.
+    Bag,
+    /// Any piece, rolled again up to three times while it's one of the last
+    /// four dealt, as in the arcade game Tetris: The Grand Master.
+    Tgm,
+    /// Any piece, rolled again once if it's the last one dealt, as on the
+    /// NES.
+    Nes,
+}
+
+/// How pieces turn where they don't fit turned in place.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// Pushed back inside the board if they stick out of a side.
+    #[default]
+    Simple,
+    /// Kicked by the tables of the Super Rotation System, as in the
+    /// guideline, which lets T-spin triples in.
+    Srs,
+}
+
+/// Colors the game is drawn in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// Every piece in its own color.
+    #[default]
+    Color,
+    /// Four shades of green, by how light the colors would be.
+    GameBoy,
+}
+
+/// Lines the boxes on screen are drawn with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Border {
+    #[default]
+    Thick,
+    Rounded,
+    Double,
+    Plain,
+}
+
+impl Border {
+    pub fn set(self) -> border::Set {
+        match self {
+            Border::Thick => border::THICK,
+            Border::Rounded => border::ROUNDED,
+            Border::Double => border::DOUBLE,
+            Border::Plain => border::PLAIN,
+        }
+    }
+}
+
+/// Characters to fill cells with instead of solid blocks, for every cell or
+/// only those of some pieces. Cells without one stay solid.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Skin {
+    /// Fills the cells of every piece and garbage not given their own.
+    pub fill: Option<char>,
+    pub i: Option<char>,
+    pub o: Option<char>,
+    pub t: Option<char>,
+    pub j: Option<char>,
+    pub l: Option<char>,
+    pub s: Option<char>,
+    pub z: Option<char>,
+    pub garbage: Option<char>,
+}
+
+impl Skin {
+    /// Character to fill the cells of the piece named `piece`, one of
+    /// `IOTJLSZ`, or of garbage for any other name.
+    pub fn glyph(&self, piece: char) -> Option<char> {
+        let glyph = match piece {
+            'I' => self.i,
+            'O' => self.o,
+            'T' => self.t,
+            'J' => self.j,
+            'L' => self.l,
+            'S' => self.s,
+            'Z' => self.z,
+            _ => self.garbage,
+        };
+        glyph.or(self.fill)
+    }
+}
+
+/// How often the falling piece can be swapped with the held one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HoldMode {
+    /// Once per piece, until it locks.
+    #[default]
+    Standard,
+    Unlimited,
+    Disabled,
+}
+
+/// Settings overridden for a single mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModeConfig {
+    pub next: Option<usize>,
+    pub lock_resets: Option<LockResets>,
+}
+
+/// How often moving or rotating a piece resting on the stack delays its
+/// lock until the next gravity tick: a number of times, or `"infinite"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "ResetsRepr", into = "ResetsRepr")]
+pub enum LockResets {
+    Limited(u32),
+    Infinite,
+}
+
+impl Default for LockResets {
+    /// 15 moves, as in the guideline.
+    fn default() -> Self {
+        LockResets::Limited(15)
+    }
+}
+
+impl LockResets {
+    /// Whether a piece that already delayed its lock `resets` times may do so
+    /// again.
+    pub fn allows(self, resets: u32) -> bool {
+        match self {
+            LockResets::Limited(limit) => resets < limit,
+            LockResets::Infinite => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ResetsRepr {
+    Limited(u32),
+    Word(String),
+}
+
+impl TryFrom<ResetsRepr> for LockResets {
+    type Error = String;
+
+    fn try_from(repr: ResetsRepr) -> Result<Self, Self::Error> {
+        match repr {
+            ResetsRepr::Limited(limit) => Ok(LockResets::Limited(limit)),
+            ResetsRepr::Word(word) if word == "infinite" => Ok(LockResets::Infinite),
+            ResetsRepr::Word(word) => Err(format!(
+                "expected a number of resets or \"infinite\", got \"{word}\""
+            )),
+        }
+    }
+}
+
+impl From<LockResets> for ResetsRepr {
+    fn from(resets: LockResets) -> Self {
+        match resets {
+            LockResets::Limited(limit) => ResetsRepr::Limited(limit),
+            LockResets::Infinite => ResetsRepr::Word("infinite".to_owned()),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            preset: None,
+            gravity: 800,
+            min_gravity: 200,
+            speed_curve: SpeedCurve::default(),
+            scoring: Scoring::default(),
+            randomizer: Randomizer::default(),
+            rotation: Rotation::default(),
+            poll: 20,
+            line_clear_delay: 0,
+            next: 5,
+            ghost: Ghost::default(),
+            grid: Grid::default(),
+            hold: HoldMode::default(),
+            lock_delay: 500,
+            lock_resets: LockResets::default(),
+            attack: AttackTable::default(),
+            zone: false,
+            skin: Skin::default(),
+            theme: Theme::default(),
+            border: Border::default(),
+            title: "tetris".to_owned(),
+            frame: false,
+            sparkline: Sparkline::default(),
+            finesse: false,
+            score_log: false,
+            drought: false,
+            bag_peek: false,
+            plan: false,
+            messiness: 30,
+            discord: String::new(),
+            notify: false,
+            modes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(config_dir()?.join("config.toml"))
+    }
+
+    /// Loads the settings, using the defaults if there is no config file,
+    /// with those of the preset it names on top.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut config: Config =
+            toml::from_str(&text).with_context(|| format!("invalid config {}", path.display()))?;
+        if let Some(preset) = config.preset {
+            preset.apply(&mut config);
+        }
+        Ok(config)
+    }
+
+    /// How messy the cheese of a dig is, from 0 to 100.
+    pub fn messiness(&self) -> u32 {
+        self.messiness.min(100)
+    }
+
+    /// Pieces to preview in the mode named `mode`.
+    pub fn next(&self, mode: &str) -> usize {
+        let next = self.modes.get(mode).and_then(|m| m.next);
+        next.unwrap_or(self.next).min(MAX_NEXT)
+    }
+
+    /// Lock delay resets in the mode named `mode`.
+    pub fn lock_resets(&self, mode: &str) -> LockResets {
+        self.modes
+            .get(mode)
+            .and_then(|m| m.lock_resets)
+            .unwrap_or(self.lock_resets)
+    }
+
+    /// Takes on the settings of `config` that only change how the game
+    /// looks, which are safe to change in the middle of one. The preset
+    /// played by still rules over them, so it goes on top again.
+    pub fn reload(&mut self, config: &Config) {
+        let mut config = config.clone();
+        if let Some(preset) = self.preset {
+            preset.apply(&mut config);
+        }
+        self.ghost = config.ghost;
+        self.grid = config.grid;
+        self.skin = config.skin;
+        self.theme = config.theme;
+        self.border = config.border;
+        self.title.clone_from(&config.title);
+        self.frame = config.frame;
+        self.sparkline = config.sparkline;
+        self.finesse = config.finesse;
+        self.score_log = config.score_log;
+        self.drought = config.drought;
+        self.bag_peek = config.bag_peek;
+        self.plan = config.plan;
+    }
+}
+
+/// Watches a config file, loading it again whenever it changes.
+///
+/// The file is watched on a background thread, and the settings loaded
+/// there are picked up with [`Watcher::try_recv`] so the game loop never
+/// blocks on the disk.
+pub struct Watcher {
+    /// Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+    incoming: Receiver<Result<Config>>,
+}
+
+impl fmt::Debug for Watcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watcher").finish_non_exhaustive()
+    }
+}
+
+impl Watcher {
+    /// Watches the config file at `path`. Its directory is watched rather
+    /// than the file, as editors often save by replacing it.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_owned(),
+            _ => PathBuf::from("."),
+        };
+        let (tx, incoming) = mpsc::channel();
+        let file = path.file_name().map(ToOwned::to_owned);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                let changed = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event.paths.iter().any(|p| p.file_name() == file.as_deref());
+                if changed {
+                    let _ = tx.send(Config::load(&path));
+                }
+            })?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("failed to watch {}", dir.display()))?;
+        Ok(Self {
+            _watcher: watcher,
+            incoming,
+        })
+    }
+
+    /// Returns the settings last loaded since the last call, if the file
+    /// changed, or why they couldn't be.
+    pub fn try_recv(&self) -> Option<Result<Config>> {
+        // saving often changes the file several times, only the last counts
+        self.incoming.try_iter().last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_parse() {
+        let config: Config = toml::from_str("gravity = 1000\npoll = 50\n").unwrap();
+        assert_eq!(
+            config,
+            Config {
+                gravity: 1000,
+                poll: 50,
+                ..Default::default()
+            }
+        );
+        assert_eq!(toml::from_str::<Config>("").unwrap(), Config::default());
+        assert!(toml::from_str::<Config>("gravty = 1000").is_err());
+
+        let config: Config = toml::from_str("hold = \"disabled\"").unwrap();
+        assert_eq!(config.hold, HoldMode::Disabled);
+        assert!(toml::from_str::<Config>("hold = \"twice\"").is_err());
+
+        let config: Config = toml::from_str("border = \"rounded\"\ntitle = \"\"").unwrap();
+        assert_eq!(config.border, Border::Rounded);
+        assert!(config.title.is_empty());
+        assert!(toml::from_str::<Config>("border = \"dashed\"").is_err());
+
+        let config: Config = toml::from_str("sparkline = \"attack\"").unwrap();
+        assert_eq!(config.sparkline, Sparkline::Attack);
+        assert!(toml::from_str::<Config>("sparkline = \"apm\"").is_err());
+
+        let config: Config = toml::from_str("grid = \"checker\"").unwrap();
+        assert_eq!(config.grid, Grid::Checker);
+        assert!(toml::from_str::<Config>("grid = \"dots\"").is_err());
+
+        let config: Config = toml::from_str("ghost = \"off\"").unwrap();
+        assert_eq!(config.ghost, Ghost::Off);
+        let config: Config = toml::from_str("ghost = \"*\"").unwrap();
+        assert_eq!(config.ghost, Ghost::Char('*'));
+        assert!(toml::from_str::<Config>("ghost = \"fancy\"").is_err());
+
+        let config: Config = toml::from_str("[skin]\nfill = \"▓\"\ni = \"░\"\n").unwrap();
+        assert_eq!(config.skin.glyph('I'), Some('░'));
+        assert_eq!(config.skin.glyph('T'), Some('▓'));
+        assert_eq!(config.skin.glyph('?'), Some('▓'));
+        assert_eq!(Skin::default().glyph('I'), None);
+        assert!(toml::from_str::<Config>("[skin]\nq = \"x\"").is_err());
+        assert!(toml::from_str::<Config>("[skin]\nfill = \"xx\"").is_err());
+
+        let config: Config = toml::from_str("next = 10\n[modes.ultra]\nnext = 0\n").unwrap();
+        assert_eq!(config.next("marathon"), MAX_NEXT);
+        assert_eq!(config.next("ultra"), 0);
+    }
+
+    #[test]
+    fn test_preset() {
+        let mut config: Config = toml::from_str("preset = \"nes\"").unwrap();
+        assert_eq!(config.preset, Some(Preset::Nes));
+        Preset::Nes.apply(&mut config);
+        assert_eq!(config.hold, HoldMode::Disabled);
+        assert_eq!(config.ghost, Ghost::Off);
+        assert_eq!(config.randomizer, Randomizer::Nes);
+        assert!(toml::from_str::<Config>("preset = \"snes\"").is_err());
+
+        assert_eq!(SpeedCurve::Score.interval(1), None);
+        let level_1 = SpeedCurve::Nes.interval(1).unwrap();
+        assert_eq!(level_1.as_millis(), 798);
+        assert!(SpeedCurve::Nes.interval(10).unwrap() < level_1);
+        assert_eq!(SpeedCurve::Nes.interval(30), SpeedCurve::Nes.interval(99));
+        assert!(SpeedCurve::GameBoy.interval(1) > SpeedCurve::Nes.interval(1));
+
+        let config: Config = toml::from_str("preset = \"gameboy\"").unwrap();
+        assert_eq!(config.preset, Some(Preset::GameBoy));
+        assert!(toml::from_str::<Config>("theme = \"sepia\"").is_err());
+
+        let mut config = Config::default();
+        Preset::GameBoy.apply(&mut config);
+        Preset::Guideline.apply(&mut config);
+        assert_eq!(config.randomizer, Randomizer::Bag);
+        assert_eq!(config.rotation, Rotation::Srs);
+        // the Game Boy's lack of one doesn't carry over
+        assert_eq!(
+            (config.lock_delay, config.lock_resets),
+            (500, LockResets::Limited(15))
+        );
+        assert_eq!((config.next, config.hold), (MAX_NEXT, HoldMode::Standard));
+        assert_eq!(config.theme, Theme::Color);
+        assert_eq!(
+            SpeedCurve::Guideline.interval(1),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(Scoring::Guideline.points(4, 2), 1600);
+
+        assert_eq!(Scoring::Level.points(2, 3), 6);
+        assert_eq!(Scoring::Classic.points(4, 1), 1200);
+        assert_eq!(Scoring::Classic.points(1, 3), 120);
+    }
+
+    #[test]
+    fn test_config_lock_resets() {
+        let config: Config =
+            toml::from_str("lock_resets = 0\n[modes.sprint]\nlock_resets = \"infinite\"\n")
+                .unwrap();
+        assert_eq!(config.lock_resets("marathon"), LockResets::Limited(0));
+        assert_eq!(config.lock_resets("sprint"), LockResets::Infinite);
+        assert!(toml::from_str::<Config>("lock_resets = \"forever\"").is_err());
+
+        assert!(LockResets::Limited(15).allows(14));
+        assert!(!LockResets::Limited(15).allows(15));
+        assert!(!LockResets::Limited(0).allows(0));
+        assert!(LockResets::Infinite.allows(u32::MAX));
+    }
+
+    #[test]
+    fn test_config_reload() {
+        // only how the game looks changes
+        let mut config = Config::default();
+        config.reload(&toml::from_str("gravity = 100\ntheme = \"gameboy\"\n").unwrap());
+        assert_eq!(config.theme, Theme::GameBoy);
+        assert_eq!(config.gravity, Config::default().gravity);
+
+        // a preset from the command line outlasts the file
+        let mut config = Config::default();
+        Preset::Nes.apply(&mut config);
+        config.reload(&toml::from_str("ghost = \"dim\"\ngrid = \"lines\"\n").unwrap());
+        assert_eq!(config.ghost, Ghost::Off);
+        assert_eq!(config.grid, Grid::Lines);
+    }
+
+    #[test]
+    #[ignore = "waits on the file system watcher of the platform"]
+    fn test_config_watcher() {
+        let dir = std::env::temp_dir().join(format!("tetris-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let watcher = Watcher::new(&path).unwrap();
+        assert!(watcher.try_recv().is_none());
+
+        std::fs::write(&path, "theme = \"gameboy\"\n").unwrap();
+        // the file may be seen empty as it's created, before it's written
+        let mut theme = None;
+        for _ in 0..50 {
+            if let Some(Ok(loaded)) = watcher.try_recv() {
+                theme = Some(loaded.theme);
+            }
+            if theme == Some(Theme::GameBoy) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(theme, Some(Theme::GameBoy));
+    }
+
+    #[test]
+    fn test_choose_dir() {
+        let home = std::env::temp_dir().join(format!("tetris-home-{}", std::process::id()));
+        let platform = PathBuf::from("/platform/tetris-rust");
+        let choose = |portable: Option<&str>, home: Option<&Path>| {
+            choose_dir(
+                portable.map(PathBuf::from),
+                home.map(Path::to_owned),
+                Some(platform.clone()),
+            )
+        };
+
+        assert_eq!(choose(None, Some(&home)), Some(platform.clone()));
+        // a portable game keeps everything beside the executable
+        assert_eq!(
+            choose(Some("/games/tetris"), Some(&home)),
+            Some(PathBuf::from("/games/tetris"))
+        );
+        // what older versions kept stays where it is
+        let legacy = home.join(".tetris-rust");
+        std::fs::create_dir_all(&legacy).unwrap();
+        assert_eq!(choose(None, Some(&home)), Some(legacy.clone()));
+        std::fs::remove_dir_all(&home).unwrap();
+
+        // without the platform's directories
+        assert_eq!(choose_dir(None, Some(home.clone()), None), Some(legacy));
+        assert_eq!(choose_dir(None, None, None), None);
+        // never a directory relative to wherever the game is started
+        assert_eq!(choose_dir(None, Some(PathBuf::new()), None), None);
+        assert_eq!(choose_dir(None, Some("home".into()), None), None);
+    }
+}
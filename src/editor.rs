@@ -0,0 +1,243 @@
+use crate::{puzzle::Scenario, tetris::fill_square};
+use anyhow::{bail, Result};
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
+    execute,
+};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Stylize},
+    symbols::{border, Marker},
+    text::Line,
+    widgets::{canvas::Canvas, Block, Widget},
+    DefaultTerminal,
+};
+use std::path::PathBuf;
+
+/// How the editor was left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// Play a game starting from the edited field.
+    Play,
+    Quit,
+}
+
+/// Paints a field cell by cell, to play from or to save as a puzzle.
+#[derive(Debug)]
+pub struct Editor {
+    /// Rows from top to bottom, `true` for filled cells.
+    cells: Vec<Vec<bool>>,
+    cursor: (usize, usize),
+    scale: u16,
+    /// Where the field is saved, keeping the goal and queue of `scenario`.
+    path: PathBuf,
+    scenario: Scenario,
+    saved: bool,
+    exit: Option<Exit>,
+}
+
+impl Editor {
+    /// Edits the field of `scenario`, which is saved to `path`.
+    pub fn new(
+        width: usize,
+        height: usize,
+        scale: u16,
+        scenario: Scenario,
+        path: PathBuf,
+    ) -> Result<Self> {
+        if scenario.board.len() > height {
+            bail!("field is too tall for the board");
+        }
+        if scenario.board.iter().any(|row| row.len() != width) {
+            bail!("rows have to be {width} cells wide");
+        }
+
+        let mut cells = vec![vec![false; width]; height - scenario.board.len()];
+        cells.extend(scenario.board.iter().cloned());
+        Ok(Self {
+            cells,
+            cursor: (width / 2, height - 1),
+            scale,
+            path,
+            scenario,
+            saved: false,
+            exit: None,
+        })
+    }
+
+    fn width(&self) -> usize {
+        self.cells[0].len()
+    }
+
+    fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// The edited field without the empty rows at the top.
+    pub fn field(&self) -> Vec<Vec<bool>> {
+        let top = self
+            .cells
+            .iter()
+            .position(|row| row.contains(&true))
+            .unwrap_or(self.height());
+        self.cells[top..].to_vec()
+    }
+
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<Exit> {
+        execute!(std::io::stdout(), EnableMouseCapture)?;
+        let result = self.event_loop(terminal);
+        execute!(std::io::stdout(), DisableMouseCapture)?;
+        result
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<Exit> {
+        loop {
+            terminal.draw(|frame| frame.render_widget(&*self, frame.area()))?;
+            match event::read()? {
+                Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                    self.handle_key(key_event.code)?
+                }
+                Event::Mouse(mouse_event) => self.handle_mouse(mouse_event),
+                _ => {}
+            }
+            if let Some(exit) = self.exit {
+                return Ok(exit);
+            }
+        }
+    }
+
+    fn handle_key(&mut self, code: KeyCode) -> Result<()> {
+        let (x, y) = self.cursor;
+        match code {
+            KeyCode::Left => self.cursor.0 = x.saturating_sub(1),
+            KeyCode::Right => self.cursor.0 = (x + 1).min(self.width() - 1),
+            KeyCode::Up => self.cursor.1 = y.saturating_sub(1),
+            KeyCode::Down => self.cursor.1 = (y + 1).min(self.height() - 1),
+            KeyCode::Char(' ') => self.paint(x, y, !self.cells[y][x]),
+            KeyCode::Char('c') => self.cells.iter_mut().flatten().for_each(|c| *c = false),
+            KeyCode::Char('s') => {
+                self.scenario.board = self.field();
+                self.scenario.save(&self.path)?;
+                self.saved = true;
+            }
+            KeyCode::Char('p') => self.exit = Some(Exit::Play),
+            KeyCode::Char('q') | KeyCode::Esc => self.exit = Some(Exit::Quit),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Paints with the left button and erases with the right one.
+    fn handle_mouse(&mut self, mouse_event: MouseEvent) {
+        let filled =
+            match mouse_event.kind {
+                MouseEventKind::Down(MouseButton::Left)
+                | MouseEventKind::Drag(MouseButton::Left) => true,
+                MouseEventKind::Down(MouseButton::Right)
+                | MouseEventKind::Drag(MouseButton::Right) => false,
+                _ => return,
+            };
+
+        // the board is drawn at the top left, inside a border
+        let x = (mouse_event.column as usize).checked_sub(1);
+        let y = (mouse_event.row as usize).checked_sub(1);
+        let (Some(x), Some(y)) = (x, y) else {
+            return;
+        };
+        let (x, y) = (x / (2 * self.scale as usize), y / self.scale as usize);
+        if x < self.width() && y < self.height() {
+            self.cursor = (x, y);
+            self.paint(x, y, filled);
+        }
+    }
+
+    fn paint(&mut self, x: usize, y: usize, filled: bool) {
+        self.cells[y][x] = filled;
+        self.saved = false;
+    }
+}
+
+impl Widget for &Editor {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = Rect {
+            width: self.width() as u16 * self.scale * 2 + 2,
+            height: self.height() as u16 * self.scale + 2,
+            ..area
+        }
+        .intersection(area);
+
+        let title_bottom = if self.saved {
+            Line::from(format!(" saved to {} ", self.path.display()).green())
+        } else {
+            Line::from(vec![
+                " <P>".blue().bold(),
+                " play ".into(),
+                "<S>".blue().bold(),
+                " save ".into(),
+                "<Q>".blue().bold(),
+                " quit ".into(),
+            ])
+        };
+        let block = Block::bordered()
+            .title(Line::from(" editor ".bold()).centered())
+            .title_bottom(title_bottom.centered())
+            .border_set(border::THICK);
+
+        Canvas::default()
+            .block(block)
+            .x_bounds([0.0, self.width() as f64])
+            .y_bounds([0.0, self.height() as f64])
+            .marker(Marker::HalfBlock)
+            .paint(|ctx| {
+                for (y, row) in self.cells.iter().enumerate() {
+                    for (x, &filled) in row.iter().enumerate() {
+                        let color = match (filled, (x, y) == self.cursor) {
+                            (true, true) => Color::White,
+                            (false, true) => Color::DarkGray,
+                            (true, false) => Color::Gray,
+                            (false, false) => Color::Reset,
+                        };
+                        fill_square(ctx, x, y, self.height(), self.scale, color);
+                    }
+                }
+            })
+            .render(area, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_field() {
+        let scenario: Scenario = "goal: lines 1\nqueue: I\nboard:\nX...\n.XX.\n"
+            .parse()
+            .unwrap();
+        let mut editor = Editor::new(4, 6, 1, scenario.clone(), PathBuf::new()).unwrap();
+        assert_eq!(editor.field(), scenario.board);
+
+        editor.handle_key(KeyCode::Char('c')).unwrap();
+        assert!(editor.field().is_empty());
+
+        // the cursor starts at the bottom
+        editor.handle_key(KeyCode::Left).unwrap();
+        editor.handle_key(KeyCode::Char(' ')).unwrap();
+        editor.handle_key(KeyCode::Up).unwrap();
+        editor.handle_key(KeyCode::Char(' ')).unwrap();
+        assert_eq!(
+            editor.field(),
+            vec![
+                vec![false, true, false, false],
+                vec![false, true, false, false]
+            ]
+        );
+
+        assert!(Editor::new(3, 6, 1, scenario, PathBuf::new()).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    block::{Block, PieceKind},
+    board,
+};
+use std::collections::{HashMap, VecDeque};
+
+/// Fewest moves and rotations taking each piece from where it spawns to
+/// every spot it can be dropped from on an empty board.
+///
+/// Spots are keyed by the cells the piece covers with its topmost row moved
+/// to the top, which tell the piece, its column and its rotation apart.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    optimal: HashMap<Vec<(i32, i32)>, u32>,
+}
+
+impl Table {
+    /// Searches every placement on a board `width` cells wide.
+    pub fn new(width: usize) -> Self {
+        let mut optimal = HashMap::new();
+        for kind in PieceKind::ALL {
+            let start = to_top(&board::spawned(&kind.block(), width));
+            let mut inputs = HashMap::from([(start.clone(), 0)]);
+            let mut queue = VecDeque::from([start]);
+            // breadth first, so every spot is first reached the fastest way
+            while let Some(block) = queue.pop_front() {
+                let n = inputs[&block];
+                optimal.entry(key(block.coords())).or_insert(n);
+
+                for next in [block.left(), block.right(), board::rotated(&block, width)] {
+                    let inside = next
+                        .coords()
+                        .iter()
+                        .all(|&(x, _)| x >= 0 && x < width as i32);
+                    let next = to_top(&next);
+                    if inside && !inputs.contains_key(&next) {
+                        inputs.insert(next.clone(), n + 1);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        Self { optimal }
+    }
+
+    /// Fewest moves and rotations that place a piece on `cells`, or `None`
+    /// if it can't be dropped there.
+    pub fn optimal(&self, cells: &[(i32, i32)]) -> Option<u32> {
+        self.optimal.get(&key(cells)).copied()
+    }
+}
+
+/// `block` moved up until its topmost cell is in row 0.
+fn to_top(block: &Block) -> Block {
+    let ((_, top), _) = block.bounding_box();
+    block.translate(0, -top)
+}
+
+fn key(coords: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let top = coords.iter().map(|&(_, y)| y).min().unwrap_or(0);
+    let mut key: Vec<_> = coords.iter().map(|&(x, y)| (x, y - top)).collect();
+    key.sort_unstable();
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_optimal() {
+        let table = Table::new(10);
+        let spawn = board::spawned(&PieceKind::T.block(), 10);
+        assert_eq!(table.optimal(spawn.coords()), Some(0));
+        // dropped from lower down makes no difference
+        assert_eq!(table.optimal(spawn.translate(0, 12).coords()), Some(0));
+        assert_eq!(table.optimal(spawn.rotate().rotate().coords()), Some(2));
+
+        // an I spawns on columns 4 to 7
+        assert_eq!(
+            table.optimal(&[(0, 19), (1, 19), (2, 19), (3, 19)]),
+            Some(4)
+        );
+        assert_eq!(
+            table.optimal(&[(6, 19), (7, 19), (8, 19), (9, 19)]),
+            Some(2)
+        );
+        // and turns upright on column 6
+        assert_eq!(
+            table.optimal(&[(9, 16), (9, 17), (9, 18), (9, 19)]),
+            Some(4)
+        );
+
+        assert_eq!(table.optimal(&[(0, 0), (2, 0), (4, 0), (6, 0)]), None);
+    }
+}
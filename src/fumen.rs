@@ -0,0 +1,142 @@
+use anyhow::{anyhow, bail, Result};
+
+/// Width of every fumen field.
+pub const WIDTH: usize = 10;
+
+const POKE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Contents of a filled fumen cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    I,
+    L,
+    O,
+    Z,
+    T,
+    J,
+    S,
+    Gray,
+}
+
+impl Cell {
+    fn from_number(n: u32) -> Option<Self> {
+        Some(match n {
+            1 => Cell::I,
+            2 => Cell::L,
+            3 => Cell::O,
+            4 => Cell::Z,
+            5 => Cell::T,
+            6 => Cell::J,
+            7 => Cell::S,
+            8 => Cell::Gray,
+            _ => return None,
+        })
+    }
+}
+
+/// Reads little-endian base 64 numbers from the encoded data.
+struct Values<'a> {
+    data: &'a [u8],
+}
+
+impl Values<'_> {
+    fn poll(&mut self, digits: usize) -> Result<u32> {
+        if self.data.len() < digits {
+            bail!("fumen data ends unexpectedly");
+        }
+
+        let (head, rest) = self.data.split_at(digits);
+        self.data = rest;
+        head.iter().rev().try_fold(0, |value, &c| {
+            let digit = POKE
+                .iter()
+                .position(|&p| p == c)
+                .ok_or_else(|| anyhow!("invalid fumen character {:?}", c as char))?;
+            Ok(value * 64 + digit as u32)
+        })
+    }
+}
+
+/// Decodes the field of the first page of a [fumen](https://harddrop.com/fumen/)
+/// diagram, given either as the bare data (`v115@...`) or as a full URL.
+///
+/// Returns the rows from top to bottom, without the row below the floor.
+pub fn decode(fumen: &str) -> Result<Vec<[Option<Cell>; WIDTH]>> {
+    let (version, data) = ["115", "110"]
+        .iter()
+        .find_map(|v| {
+            let (_, data) = fumen.split_once(&format!("v{v}@"))?;
+            Some((*v, data))
+        })
+        .ok_or_else(|| anyhow!("not a v115 or v110 fumen"))?;
+
+    // v110 fields are two rows shorter
+    let height = if version == "115" { 23 } else { 21 };
+    let blocks = (height + 1) * WIDTH;
+
+    // links may have `?` inserted to allow line breaks
+    let data: Vec<u8> = data.bytes().filter(|&c| c != b'?').collect();
+    let mut values = Values { data: &data };
+
+    let mut cells = Vec::with_capacity(blocks);
+    while cells.len() < blocks {
+        let value = values.poll(2)? as usize;
+        let (diff, count) = (value / blocks, value % blocks + 1);
+
+        // the first page is a diff against an empty field
+        let cell = match diff.checked_sub(8) {
+            Some(0) => None,
+            Some(n) => {
+                Some(Cell::from_number(n as u32).ok_or_else(|| anyhow!("invalid fumen cell"))?)
+            }
+            None => bail!("invalid fumen cell"),
+        };
+        if cells.len() + count > blocks {
+            bail!("fumen field is too large");
+        }
+        cells.extend(std::iter::repeat_n(cell, count));
+    }
+
+    Ok(cells[..height * WIDTH]
+        .chunks_exact(WIDTH)
+        .map(|row| row.try_into().unwrap())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_empty() {
+        let field = decode("v115@vhAAgH").unwrap();
+        assert_eq!(field.len(), 23);
+        assert!(field.iter().flatten().all(|c| c.is_none()));
+
+        let field = decode("https://harddrop.com/fumen/?v115@vhAAgH").unwrap();
+        assert!(field.iter().flatten().all(|c| c.is_none()));
+    }
+
+    #[test]
+    fn test_decode_field() {
+        // bottom row filled with garbage except the rightmost column
+        let field = decode("v115@bhI8KeAgH").unwrap();
+        assert!(field[..22].iter().flatten().all(|c| c.is_none()));
+        assert_eq!(field[22][..9], [Some(Cell::Gray); 9]);
+        assert_eq!(field[22][9], None);
+
+        // T piece on the floor, with `?` inserted
+        let field = decode("v115@Sh?wwHeywQeAgH").unwrap();
+        assert!(field[..21].iter().flatten().all(|c| c.is_none()));
+        assert_eq!(field[21][..3], [None, Some(Cell::T), None]);
+        assert_eq!(field[22][..3], [Some(Cell::T); 3]);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(decode("v115@").is_err());
+        assert!(decode("v115@vh").is_ok());
+        assert!(decode("v115@!!").is_err());
+        assert!(decode("v999@vhAAgH").is_err());
+    }
+}
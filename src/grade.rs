@@ -0,0 +1,93 @@
+use std::fmt;
+use std::time::Duration;
+
+const NAMES: [&str; 19] = [
+    "9", "8", "7", "6", "5", "4", "3", "2", "1", "S1", "S2", "S3", "S4", "S5", "S6", "S7", "S8",
+    "S9", "GM",
+];
+
+/// Score needed for each grade up to S9.
+const THRESHOLDS: [u64; 18] = [
+    0, 4, 8, 14, 20, 30, 40, 55, 70, 90, 110, 130, 150, 175, 200, 225, 250, 300,
+];
+
+/// Reaching S9 within this time earns GM.
+pub const GM_TIME: Duration = Duration::from_secs(13 * 60 + 30);
+
+/// Arcade style grade, from 9 up through 1 and S1 to S9, topped by GM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Grade(usize);
+
+impl Grade {
+    pub const GM: Grade = Grade(NAMES.len() - 1);
+    pub const S9: Grade = Grade(NAMES.len() - 2);
+
+    /// The highest grade `score` is enough for. GM takes speed as well, so it
+    /// is never returned here.
+    pub fn for_score(score: u64) -> Self {
+        Grade(THRESHOLDS.iter().rposition(|&t| t <= score).unwrap_or(0))
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(NAMES[self.0])
+    }
+}
+
+/// Grade earned so far in a game. Grades only ever go up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Grading {
+    grade: Grade,
+}
+
+impl Grading {
+    pub fn grade(&self) -> Grade {
+        self.grade
+    }
+
+    /// Updates the grade after reaching `score` at `elapsed`.
+    pub fn update(&mut self, score: u64, elapsed: Duration) {
+        if self.grade == Grade::GM {
+            return;
+        }
+
+        let grade = Grade::for_score(score);
+        if grade == Grade::S9 && self.grade < Grade::S9 && elapsed <= GM_TIME {
+            self.grade = Grade::GM;
+        } else {
+            self.grade = self.grade.max(grade);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grade_for_score() {
+        assert_eq!(Grade::for_score(0).to_string(), "9");
+        assert_eq!(Grade::for_score(5).to_string(), "8");
+        assert_eq!(Grade::for_score(70).to_string(), "1");
+        assert_eq!(Grade::for_score(90).to_string(), "S1");
+        assert_eq!(Grade::for_score(10_000), Grade::S9);
+    }
+
+    #[test]
+    fn test_grading() {
+        let mut grading = Grading::default();
+        grading.update(40, Duration::from_secs(60));
+        assert_eq!(grading.grade().to_string(), "3");
+        grading.update(20, Duration::from_secs(90));
+        assert_eq!(grading.grade().to_string(), "3");
+        grading.update(300, Duration::from_secs(600));
+        assert_eq!(grading.grade(), Grade::GM);
+
+        let mut slow = Grading::default();
+        slow.update(300, GM_TIME + Duration::from_secs(1));
+        assert_eq!(slow.grade(), Grade::S9);
+        slow.update(310, Duration::ZERO);
+        assert_eq!(slow.grade(), Grade::S9);
+    }
+}
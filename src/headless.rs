@@ -0,0 +1,196 @@
+use crate::modes;
+use crate::tetris::{Action, Mode, Tetris};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+/// Commands of a program driving a game, a JSON object a line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Command {
+    /// Applies `action` to the game. Time doesn't pass, so gravity only
+    /// ticks on `tick`.
+    Action {
+        action: Action,
+    },
+    /// Asks for the state of the game.
+    State,
+    Quit,
+}
+
+/// Answers to commands, one line each.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    /// What an action led to, in order.
+    Events {
+        events: Vec<Event>,
+    },
+    State(State),
+    Error {
+        message: String,
+    },
+}
+
+/// Things that happen in a game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A piece locked, clearing `lines` lines.
+    Lock {
+        lines: usize,
+        tspin: bool,
+        perfect: bool,
+        /// Clears in a row before this one, if it cleared lines.
+        combo: Option<usize>,
+    },
+    Level {
+        level: usize,
+    },
+    GameOver {
+        reason: String,
+    },
+}
+
+/// A game as a program driving it sees it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct State {
+    /// Rows of the board from the top without the falling piece, a digit a
+    /// cell numbered as in [`Tetris::board_text`].
+    pub board: Vec<String>,
+    /// The falling piece, by its letter.
+    pub piece: Option<char>,
+    /// Cells of the falling piece, from the top left.
+    pub cells: Vec<(i32, i32)>,
+    /// Pieces previewed, the next first.
+    pub queue: Vec<char>,
+    pub hold: Option<char>,
+    pub score: i32,
+    pub lines: usize,
+    pub level: usize,
+    pub game_over: Option<String>,
+}
+
+/// Whether games of `mode` can be played headless. Time doesn't pass in
+/// them, so modes going by the clock can't.
+pub fn playable(mode: Mode) -> bool {
+    modes::game_mode(mode).playable_headless()
+}
+
+/// Spawns the first piece of `tetris`, if it hasn't yet. Time doesn't pass,
+/// so resting pieces lock on the next tick rather than after a delay.
+pub fn start(tetris: &mut Tetris) {
+    tetris.lock_on_tick();
+    if tetris.headless_state().piece.is_none() {
+        tetris.apply(Action::Tick);
+    }
+}
+
+/// Plays `tetris` by the commands read from `reader`, answering each on
+/// `writer`, until told to quit or the commands run out.
+pub fn run(tetris: &mut Tetris, reader: impl BufRead, mut writer: impl Write) -> Result<()> {
+    start(tetris);
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str(&line) {
+            Ok(Command::Action { .. }) if tetris.game_over().is_some() => Reply::Error {
+                message: "the game is over".to_owned(),
+            },
+            Ok(Command::Action { action }) => Reply::Events {
+                events: step(tetris, action),
+            },
+            Ok(Command::State) => Reply::State(tetris.headless_state()),
+            Ok(Command::Quit) => break,
+            Err(err) => Reply::Error {
+                message: err.to_string(),
+            },
+        };
+        let mut line = serde_json::to_string(&reply)?;
+        line.push('\n');
+        writer.write_all(line.as_bytes())?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Applies `action` to `tetris`, returning what it led to.
+pub fn step(tetris: &mut Tetris, action: Action) -> Vec<Event> {
+    let (locks, level) = (tetris.locks().len(), tetris.level());
+    tetris.apply(action);
+
+    let mut events: Vec<_> = tetris.locks()[locks..]
+        .iter()
+        .map(|lock| Event::Lock {
+            lines: lock.clear.lines,
+            tspin: lock.clear.tspin,
+            perfect: lock.clear.perfect,
+            combo: lock.combo,
+        })
+        .collect();
+    if tetris.level() != level {
+        events.push(Event::Level {
+            level: tetris.level(),
+        });
+    }
+    if let Some(reason) = tetris.game_over() {
+        events.push(Event::GameOver {
+            reason: reason.to_string(),
+        });
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_headless() {
+        let mut tetris = Tetris::new(10, 20, 1);
+        let commands = [
+            r#"{"type":"state"}"#,
+            r#"{"type":"action","action":"left"}"#,
+            "",
+            r#"{"type":"action","action":"drop"}"#,
+            r#"{"type":"jump"}"#,
+            r#"{"type":"quit"}"#,
+            r#"{"type":"state"}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        run(&mut tetris, Cursor::new(commands), &mut output).unwrap();
+        let replies: Vec<Reply> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 4);
+
+        let Reply::State(state) = &replies[0] else {
+            panic!("expected the state, got {:?}", replies[0]);
+        };
+        assert_eq!(state.board.len(), 20);
+        assert!(state.board.iter().all(|row| row == "0000000000"));
+        assert!(state.piece.is_some());
+        assert_eq!(state.cells.len(), 4);
+        assert_eq!(state.game_over, None);
+        assert_eq!(replies[1], Reply::Events { events: Vec::new() });
+        assert_eq!(
+            replies[2],
+            Reply::Events {
+                events: vec![Event::Lock {
+                    lines: 0,
+                    tspin: false,
+                    perfect: false,
+                    combo: None,
+                }]
+            }
+        );
+        assert!(matches!(replies[3], Reply::Error { .. }));
+        assert_ne!(tetris.headless_state().board[19], "0000000000");
+    }
+}
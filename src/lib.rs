@@ -1,3 +1,33 @@
-pub mod block;
-pub mod board;
+pub use tetris_core::{block, board, scoring};
+
+pub mod analysis;
+pub mod attack;
+pub mod bot;
+pub mod clipboard;
+pub mod clock;
+pub mod config;
+pub mod editor;
+pub mod finesse;
+pub mod fumen;
+pub mod grade;
+pub mod headless;
+pub mod map;
+pub mod modes;
+pub mod net;
+pub mod opener;
+#[cfg(feature = "discord")]
+pub mod presence;
+pub mod protocol;
+pub mod puzzle;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod rating;
+pub mod replay;
+pub mod screenshot;
+pub mod script;
+pub mod spectate;
+pub mod stats;
+pub mod tbp;
+pub mod tetrinet;
 pub mod tetris;
+pub mod tournament;
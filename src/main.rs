@@ -1,9 +1,678 @@
-use anyhow::Result;
-use tetris_rust::tetris::Tetris;
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use ratatui::DefaultTerminal;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tetris_rust::{
+    bot::Difficulty,
+    config::{self, Config, Preset},
+    editor::{Editor, Exit},
+    headless,
+    map::Map,
+    net::Client,
+    opener::Opener,
+    protocol::{Handicap, Handshake, Targeting, MAX_PLAYERS},
+    puzzle::Scenario,
+    rating::MAX_HANDICAP,
+    replay::Replay,
+    screenshot,
+    script::Script,
+    spectate::Spectator,
+    stats::Stats,
+    tbp::Engine,
+    tetrinet,
+    tetris::{Mode, Tetris, B_TYPE_HEIGHTS, MAX_BOTS, MAX_START_LEVEL},
+    tournament::Tournament,
+};
+use tracing::info;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Play a versus match through the tetris-server at this address
+    #[arg(long, value_name = "ADDR")]
+    connect: Option<String>,
+
+    /// Watch the versus match in --room on the tetris-server at this
+    /// address without playing
+    #[arg(
+        long,
+        value_name = "ADDR",
+        conflicts_with_all = ["connect", "tetrinet", "replay", "record", "headless", "tournament"],
+    )]
+    watch: Option<String>,
+
+    /// Play on the TetriNet server at this address, on port 31457 unless
+    /// given
+    #[arg(
+        long,
+        value_name = "ADDR",
+        conflicts_with_all = [
+            "connect", "record", "replay", "items", "fumen", "puzzle", "mode", "grades", "edit",
+            "map", "board", "opener", "step",
+        ],
+    )]
+    tetrinet: Option<String>,
+
+    /// Start the match with this many rows of garbage
+    #[arg(
+        long,
+        value_name = "ROWS",
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u32).range(..=MAX_HANDICAP as i64),
+        requires = "connect",
+    )]
+    handicap: u32,
+
+    /// Have the opponent start the match with this many rows of garbage
+    #[arg(
+        long,
+        value_name = "ROWS",
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u32).range(..=MAX_HANDICAP as i64),
+        requires = "connect",
+    )]
+    give_handicap: u32,
+
+    /// Room to join or watch on the server
+    #[arg(long, default_value = "default")]
+    room: String,
+
+    /// Name shown to your opponent
+    #[arg(long, default_value = "player")]
+    name: String,
+
+    /// Play an item battle: clearing lines wins items, used on yourself
+    /// with 1 and on your opponent with 2
+    #[arg(long, requires = "connect")]
+    items: bool,
+
+    /// Play a free-for-all between this many players, each sending garbage
+    /// to a random opponent still standing
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u8).range(2..=MAX_PLAYERS as i64),
+        requires = "connect",
+    )]
+    players: u8,
+
+    /// Whom your garbage and items go to in a free-for-all; <T> picks an
+    /// opponent by hand during the match
+    #[arg(long, value_enum, default_value_t, requires = "connect")]
+    target: Targeting,
+
+    /// Save a replay of the game to this file, gzipped if it ends in .gz
+    #[arg(long, value_name = "FILE", conflicts_with = "connect")]
+    record: Option<PathBuf>,
+
+    /// Start from the field of a fumen diagram (v115@... or a fumen URL)
+    #[arg(long, value_name = "DATA", conflicts_with_all = ["connect", "record", "replay"])]
+    fumen: Option<String>,
+
+    /// Solve the puzzle described by a scenario file
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["connect", "record", "replay", "fumen"])]
+    puzzle: Option<PathBuf>,
+
+    /// Start from the board of a map file, or pick one of the maps in the
+    /// maps directory in the data directory if none is given
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        conflicts_with_all = ["connect", "record", "replay", "fumen", "puzzle", "edit"],
+    )]
+    map: Option<Option<PathBuf>>,
+
+    /// Start from a board saved as text with D, aligned to the bottom
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["connect", "record", "replay", "fumen", "puzzle", "edit", "map"],
+    )]
+    board: Option<PathBuf>,
+
+    /// Train the opener described by a file, placement by placement
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["connect", "record", "replay", "fumen", "puzzle", "edit", "map"],
+    )]
+    opener: Option<PathBuf>,
+
+    /// Single player mode to play
+    #[arg(long, value_enum, default_value_t, conflicts_with_all = ["connect", "replay", "puzzle"])]
+    mode: Mode,
+
+    /// Award arcade style grades, from 9 up to S9 and GM
+    #[arg(long, conflicts_with_all = ["connect", "replay", "puzzle"])]
+    grades: bool,
+
+    /// Paint a field to play from, saved as a scenario file for --puzzle
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["connect", "replay", "fumen", "puzzle"])]
+    edit: Option<PathBuf>,
+
+    /// Debug mode: pause gravity and advance one tick at a time with N
+    #[arg(long, conflicts_with_all = ["connect", "replay"])]
+    step: bool,
+
+    /// Play without a terminal, by JSON commands read a line at a time from
+    /// standard input, answered on standard output
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "connect", "tetrinet", "replay", "tournament", "screenshot", "step", "engine",
+        ],
+    )]
+    headless: bool,
+
+    /// Race to clear this many lines instead of 40, in a sprint
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["connect", "tetrinet", "replay", "puzzle", "opener"],
+    )]
+    lines: Option<u32>,
+
+    /// Start a marathon or B-type game at this level, with its gravity and
+    /// scoring
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=MAX_START_LEVEL as i64),
+        conflicts_with_all = ["connect", "tetrinet", "replay", "puzzle", "opener"],
+    )]
+    level: Option<u32>,
+
+    /// Bots to play a royale against
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=MAX_BOTS as i64),
+    )]
+    bots: Option<u32>,
+
+    /// How well the bots of a royale play
+    #[arg(long, value_enum)]
+    difficulty: Option<Difficulty>,
+
+    /// Launch this bot, speaking the Tetris Bot Protocol, to play the first
+    /// bot of a royale, or else to mark where it would drop each piece
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        conflicts_with_all = ["connect", "tetrinet", "replay", "tournament"],
+    )]
+    engine: Option<String>,
+
+    /// Run this Rhai script, hooking on_spawn, on_clear and on_tick to change
+    /// the score, gravity and garbage
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["connect", "tetrinet", "replay", "record", "tournament"],
+    )]
+    script: Option<PathBuf>,
+
+    /// Height of the garbage a B-type game starts on, from 0 to 5
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u32).range(..B_TYPE_HEIGHTS.len() as i64),
+    )]
+    height: u32,
+
+    /// Hold a tournament between these players, taking turns on this
+    /// machine, e.g. alice,bob,carol
+    #[arg(
+        long,
+        value_name = "NAMES",
+        value_delimiter = ',',
+        conflicts_with_all = [
+            "connect", "tetrinet", "replay", "record", "screenshot", "gif", "speed", "step",
+        ],
+    )]
+    tournament: Option<Vec<String>>,
+
+    /// Rounds of the tournament
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..), requires = "tournament")]
+    rounds: u32,
+
+    /// Play the pieces of this seed, as shown on the pause and results
+    /// screens
+    #[arg(long, value_name = "N", conflicts_with_all = ["connect", "tetrinet", "replay"])]
+    seed: Option<u64>,
+
+    /// Play by the rules of another Tetris, overriding the config
+    #[arg(long, value_enum, conflicts_with_all = ["connect", "tetrinet", "replay"])]
+    preset: Option<Preset>,
+
+    /// Milliseconds between gravity ticks at the start, overriding the config
+    #[arg(long, value_name = "MS")]
+    gravity: Option<u64>,
+
+    /// Play at this percent of full speed, to study kicks and locks in slow
+    /// motion (games played slower don't count towards the statistics)
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        value_parser = clap::value_parser!(u32).range(1..=100),
+        conflicts_with_all = ["connect", "tetrinet"],
+    )]
+    speed: Option<u32>,
+
+    /// Milliseconds to wait for input between screen updates, overriding
+    /// the config
+    #[arg(long, value_name = "MS")]
+    poll: Option<u64>,
+
+    /// Percent chance that the hole of each row of cheese in a dig moves,
+    /// overriding the config
+    #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u32).range(..=100))]
+    messiness: Option<u32>,
+
+    /// Save the starting board to this image file, an SVG if it ends in .svg
+    /// and a PNG otherwise, instead of playing
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["connect", "tetrinet", "replay", "record"])]
+    screenshot: Option<PathBuf>,
+
+    /// Log engine events to this file
+    #[arg(long, value_name = "FILE")]
+    log: Option<PathBuf>,
+
+    /// How much to log: error, warn, info, debug or trace
+    #[arg(long, value_name = "LEVEL", default_value = "info", requires = "log")]
+    log_level: tracing::Level,
+
+    /// Show lifetime statistics instead of playing
+    #[arg(long)]
+    stats: bool,
+
+    /// Keep the settings, statistics and saved files beside the executable
+    #[arg(long)]
+    portable: bool,
+
+    /// Watch a previously recorded replay
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["connect", "record", "mode", "lines"],
+    )]
+    replay: Option<PathBuf>,
+
+    /// Render the replay to an animated GIF of the board instead of watching
+    /// it
+    #[arg(long, value_name = "FILE", requires = "replay")]
+    gif: Option<PathBuf>,
+
+    /// Frames per second of the GIF
+    #[arg(
+        long,
+        default_value_t = 10,
+        value_parser = clap::value_parser!(u32).range(1..=50),
+        requires = "gif",
+    )]
+    fps: u32,
+}
 
 fn main() -> Result<()> {
+    let args = Args::parse();
+    if let Some(path) = &args.log {
+        // logging to the terminal would garble the game
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        tracing_subscriber::fmt()
+            .with_writer(Mutex::new(file))
+            .with_ansi(false)
+            .with_max_level(args.log_level)
+            .init();
+    }
+    if args.portable {
+        config::make_portable()?;
+    }
+    let stats_path = Stats::default_path()?;
+
+    if args.stats {
+        let stats = Stats::load(&stats_path)?;
+        let mut terminal = ratatui::init();
+        let result = stats.run(&mut terminal);
+        ratatui::restore();
+        return result;
+    }
+
+    if let Some(addr) = &args.watch {
+        let mut spectator = Spectator::new(Client::watch(addr, &args.room)?, &args.room);
+        let mut terminal = ratatui::init();
+        let result = spectator.run(&mut terminal);
+        ratatui::restore();
+        return result;
+    }
+
+    let mut field = None;
+    if let Some(path) = &args.edit {
+        let scenario = if path.exists() {
+            Scenario::load(path)?
+        } else {
+            Scenario::default()
+        };
+        let mut editor = Editor::new(10, 20, 2, scenario, path.clone())?;
+        let mut terminal = ratatui::init();
+        let exit = editor.run(&mut terminal);
+        ratatui::restore();
+        if exit? == Exit::Quit {
+            return Ok(());
+        }
+        field = Some(editor.field());
+    }
+
+    let map = match &args.map {
+        Some(Some(path)) => Some(Map::load(path)?),
+        Some(None) => {
+            let dir = Map::default_dir()?;
+            let mut maps = Map::load_all(&dir)?;
+            if maps.is_empty() {
+                bail!("no maps in {}", dir.display());
+            }
+            let mut terminal = ratatui::init();
+            let selected = tetris_rust::map::select(&mut terminal, &maps);
+            ratatui::restore();
+            match selected? {
+                Some(i) => Some(maps.swap_remove(i)),
+                None => return Ok(()),
+            }
+        }
+        None => None,
+    };
+
+    let mode = match (args.mode, args.lines) {
+        (Mode::Marathon | Mode::Sprint, Some(_)) => Mode::Sprint,
+        (mode, Some(_)) => bail!("--lines sets the goal of a sprint, not of {}", mode.name()),
+        (mode, None) => mode,
+    };
+    if args.level.is_some() && !matches!(mode, Mode::Marathon | Mode::BType) {
+        bail!(
+            "--level sets where a marathon or B-type starts, not {}",
+            mode.name()
+        );
+    }
+    if args.bots.is_some() && mode != Mode::Royale {
+        bail!(
+            "--bots sets who a royale is played against, not {}",
+            mode.name()
+        );
+    }
+    if args.difficulty.is_some() && mode != Mode::Royale {
+        bail!(
+            "--difficulty sets how well the bots of a royale play, not {}",
+            mode.name()
+        );
+    }
+    if mode == Mode::Royale && args.tetrinet.is_some() {
+        bail!("royales are played against bots, not over TetriNet");
+    }
+    if args.headless && !headless::playable(mode) {
+        bail!("{} games can't be played headless", mode.name());
+    }
+    if args.height > 0 && mode != Mode::BType {
+        bail!(
+            "--height sets the garbage of a B-type, not of {}",
+            mode.name()
+        );
+    }
+    if args.mode == Mode::Practice && args.tournament.is_some() {
+        bail!("practice games don't count towards a tournament");
+    }
+    if args.mode == Mode::Practice && args.record.is_some() {
+        bail!("practice games can't be recorded");
+    }
+    // replays don't keep the garbage of a survival, B-type or royale
+    if matches!(args.mode, Mode::Survival | Mode::BType | Mode::Royale) && args.record.is_some() {
+        bail!("{} games can't be recorded", args.mode.name());
+    }
+
+    let mut config = Config::load(Config::default_path()?)?;
+    if let Some(preset) = args.preset {
+        preset.apply(&mut config);
+    }
+    config.gravity = args.gravity.unwrap_or(config.gravity);
+    config.poll = args.poll.unwrap_or(config.poll);
+    config.messiness = args.messiness.unwrap_or(config.messiness);
+
+    let mut stats = Stats::load(&stats_path)?;
+    if let Some(players) = &args.tournament {
+        let tournament = Tournament::new(players.clone(), args.rounds as usize)?;
+        let mut terminal = ratatui::init();
+        let result = play_tournament(
+            &mut terminal,
+            &args,
+            tournament,
+            &config,
+            mode,
+            field.as_deref(),
+            map.as_ref(),
+        );
+        ratatui::restore();
+        return result;
+    }
+
+    let mut tetris = new_game(
+        &args,
+        config,
+        mode,
+        field.as_deref(),
+        map.as_ref(),
+        args.seed,
+    )?;
+    if let Some(path) = &args.screenshot {
+        return tetris.save_screenshot(path);
+    }
+    if let Some(path) = &args.gif {
+        return screenshot::save_gif(&tetris.replay_frames(args.fps), path);
+    }
+    if args.items {
+        tetris = tetris.with_items();
+    }
+    if let Some(command) = &args.engine {
+        tetris = tetris.with_engine(Engine::launch(command)?);
+    }
+    if let Some(path) = &args.script {
+        tetris = tetris.with_script(Script::load(path)?);
+    }
+    // headless games have no look to change
+    if !args.headless {
+        match Config::default_path().and_then(config::Watcher::new) {
+            Ok(watcher) => tetris = tetris.with_watcher(watcher),
+            Err(err) => info!("not watching the config: {err:#}"),
+        }
+    }
+    if let Some(addr) = &args.tetrinet {
+        let addr = if addr.contains(':') {
+            addr.clone()
+        } else {
+            format!("{addr}:{}", tetrinet::PORT)
+        };
+        tetris = tetris.with_client(Client::connect_tetrinet(addr, &args.name)?);
+    }
+    if let Some(addr) = &args.connect {
+        let handicap = Handicap {
+            own: args.handicap as usize,
+            opponent: args.give_handicap as usize,
+        };
+        let handshake = Handshake {
+            players: args.players as usize,
+            ..tetris.handshake()
+        };
+        let client = Client::connect(addr, &args.room, &args.name, handshake, handicap)?;
+        tetris = tetris.with_client(client).with_targeting(args.target);
+    }
+    if args.record.is_some() {
+        tetris = tetris.record();
+    }
+    // headless games don't count towards the statistics
+    if args.headless {
+        headless::run(
+            &mut tetris,
+            std::io::stdin().lock(),
+            std::io::stdout().lock(),
+        )?;
+        if let (Some(path), Some(replay)) = (&args.record, tetris.replay()) {
+            replay.save(path)?;
+        }
+        return Ok(());
+    }
+    // games a script changed can't set a personal best, as they don't count
+    if args.script.is_none() {
+        let best = stats.modes.get(&tetris.stats_name());
+        tetris = tetris.with_personal_best(best);
+    }
+
     let mut terminal = ratatui::init();
-    let result = Tetris::default().run(&mut terminal);
+    let mut result = tetris.run(&mut terminal);
+    let game = tetris.result();
+    if let (Ok(()), Some(game)) = (&result, &game) {
+        result = game.run(&mut terminal);
+    }
     ratatui::restore();
+
+    if let (Some(path), Some(replay)) = (&args.record, tetris.replay()) {
+        replay.save(path)?;
+    }
+    // versus matches always count, even without a result of the game
+    let mut changed = false;
+    if let Some((player, opponent, won)) = tetris.match_result() {
+        stats.record_match(player, opponent, won);
+        changed = true;
+    }
+    // games a script changed don't count towards the statistics
+    if let Some(game) = game.filter(|_| args.script.is_none()) {
+        stats.record(&game);
+        changed = true;
+    }
+    if changed {
+        stats.save(&stats_path)?;
+    }
     result
 }
+
+/// Sets up the game `args` describe, with pieces from `seed` if given.
+fn new_game(
+    args: &Args,
+    config: Config,
+    mode: Mode,
+    field: Option<&[Vec<bool>]>,
+    map: Option<&Map>,
+    seed: Option<u64>,
+) -> Result<Tetris> {
+    let mut tetris = match (&args.replay, &args.tetrinet) {
+        (Some(path), _) => Tetris::from_replay(Replay::load(path)?, 2)?,
+        // TetriNet fields are bigger, drawn small to fit next to each other
+        (_, Some(_)) => Tetris::new(tetrinet::WIDTH, tetrinet::HEIGHT, 1),
+        (None, None) => Tetris::default(),
+    };
+    // the cheese of a dig comes from the seed too
+    if let Some(seed) = seed {
+        tetris = tetris.with_seed(seed);
+    }
+    #[cfg(feature = "discord")]
+    if !config.discord.is_empty() && args.replay.is_none() {
+        // Discord not running shouldn't keep anyone from playing
+        match tetris_rust::presence::Presence::connect(&config.discord) {
+            Ok(presence) => tetris = tetris.with_presence(presence),
+            Err(err) => tracing::warn!("failed to connect to Discord: {err:#}"),
+        }
+    }
+    // digs are dug from cheese as messy as configured, replays set up the
+    // mode they were recorded in
+    tetris = tetris.with_config(config);
+    if args.replay.is_none() {
+        tetris = tetris.with_mode(mode);
+    }
+    if let Some(lines) = args.lines {
+        tetris = tetris.with_line_goal(lines as usize);
+    }
+    if let Some(level) = args.level {
+        tetris = tetris.with_start_level(level as usize);
+    }
+    if mode == Mode::BType {
+        tetris = tetris.with_garbage_height(args.height as usize);
+    }
+    if let Some(bots) = args.bots {
+        tetris = tetris.with_bots(bots as usize);
+    }
+    if let Some(difficulty) = args.difficulty {
+        tetris = tetris.with_difficulty(difficulty);
+    }
+    if args.step {
+        tetris = tetris.with_stepping();
+    }
+    if let Some(speed) = args.speed {
+        tetris = tetris.with_speed(speed);
+    }
+    if args.grades {
+        tetris = tetris.with_grades();
+    }
+    if let Some(field) = field {
+        tetris = tetris.with_field(field)?;
+    }
+    if let Some(map) = map {
+        tetris = tetris.with_map(map)?;
+    }
+    if let Some(fumen) = &args.fumen {
+        tetris = tetris.with_fumen(fumen)?;
+    }
+    if let Some(path) = &args.board {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        tetris = tetris
+            .with_board_text(&text)
+            .with_context(|| format!("invalid board {}", path.display()))?;
+    }
+    if let Some(path) = &args.puzzle {
+        tetris = tetris.with_puzzle(&Scenario::load(path)?)?;
+    }
+    if let Some(path) = &args.opener {
+        tetris = tetris.with_opener(&Opener::load(path)?)?;
+    }
+    Ok(tetris)
+}
+
+/// Has the players of `tournament` take turns at games set up by `args`,
+/// the same seed for everyone each round, until every round is played or
+/// they quit.
+fn play_tournament(
+    terminal: &mut DefaultTerminal,
+    args: &Args,
+    mut tournament: Tournament,
+    config: &Config,
+    mode: Mode,
+    field: Option<&[Vec<bool>]>,
+    map: Option<&Map>,
+) -> Result<()> {
+    // every round has its own pieces, the same for everyone
+    let first_seed = args.seed.unwrap_or_else(rand::random);
+    // the final standings stay up until dismissed too
+    while tournament.intermission(terminal)? {
+        let Some((round, player)) = tournament.next_turn() else {
+            break;
+        };
+        let seed = first_seed.wrapping_add(round as u64);
+        let mut tetris = new_game(args, config.clone(), mode, field, map, Some(seed))?;
+        info!(
+            round,
+            player = tournament.player(player),
+            seed,
+            "tournament turn"
+        );
+        tetris.run(terminal)?;
+        let Some(game) = tetris.result() else {
+            bail!("tournament games have to count");
+        };
+        game.run(terminal)?;
+        tournament.record(game);
+    }
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use crate::config;
+use anyhow::{anyhow, bail, Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+    DefaultTerminal,
+};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// What a map places in a cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fill {
+    /// Garbage, cleared like any other cell.
+    Garbage,
+    /// Indestructible: rows containing it are never cleared.
+    Solid,
+}
+
+/// A preset starting board.
+///
+/// Map files look like this, with the board aligned to the bottom:
+///
+/// ```text
+/// # comment
+/// name: pillars
+/// board:
+/// #........#
+/// #XX....XX#
+/// #XXX..XXX#
+/// ```
+///
+/// `X` is garbage and `#` an indestructible cell. Without a name, the map is
+/// named after its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Map {
+    pub name: String,
+    /// Rows from top to bottom, `None` for empty cells.
+    pub board: Vec<Vec<Option<Fill>>>,
+}
+
+impl Map {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut map: Map = text
+            .parse()
+            .with_context(|| format!("invalid map {}", path.display()))?;
+        if map.name.is_empty() {
+            if let Some(stem) = path.file_stem() {
+                map.name = stem.to_string_lossy().into_owned();
+            }
+        }
+        Ok(map)
+    }
+
+    /// Where maps to choose from are kept: `maps` in the [`config::data_dir`].
+    pub fn default_dir() -> Result<PathBuf> {
+        Ok(config::data_dir()?.join("maps"))
+    }
+
+    /// Loads every `.txt` file in `dir`, sorted by file name. There are none
+    /// if `dir` doesn't exist.
+    pub fn load_all(dir: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let dir = dir.as_ref();
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = Vec::new();
+        for entry in
+            std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "txt") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        paths.iter().map(Self::load).collect()
+    }
+}
+
+/// Writes the map file format read by [`Map::from_str`].
+///
+/// [`Map::from_str`]: std::str::FromStr::from_str
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.name.is_empty() {
+            writeln!(f, "name: {}", self.name)?;
+        }
+        writeln!(f, "board:")?;
+        for row in &self.board {
+            writeln!(f, "{}", row_text(row))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Map {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut name = String::new();
+        let mut lines = s.lines();
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("expected `key: value`, got {line:?}"))?;
+            match key.trim() {
+                "name" => name = value.trim().to_owned(),
+                "board" => break,
+                key => bail!("unknown key {key:?}"),
+            }
+        }
+
+        let board = lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.chars()
+                    .map(|c| match c {
+                        '.' => Ok(None),
+                        'X' => Ok(Some(Fill::Garbage)),
+                        '#' => Ok(Some(Fill::Solid)),
+                        c => bail!("unknown cell {c:?}"),
+                    })
+                    .collect()
+            })
+            .collect::<Result<Vec<Vec<_>>>>()?;
+        if board.is_empty() {
+            bail!("missing board");
+        }
+
+        Ok(Self { name, board })
+    }
+}
+
+fn row_text(row: &[Option<Fill>]) -> String {
+    row.iter()
+        .map(|cell| match cell {
+            None => '.',
+            Some(Fill::Garbage) => 'X',
+            Some(Fill::Solid) => '#',
+        })
+        .collect()
+}
+
+/// Lets the player pick one of `maps`, returning its index or `None` if they
+/// quit instead.
+pub fn select(terminal: &mut DefaultTerminal, maps: &[Map]) -> Result<Option<usize>> {
+    let mut menu = Menu { maps, selected: 0 };
+    loop {
+        terminal.draw(|frame| frame.render_widget(&menu, frame.area()))?;
+        let Event::Key(key_event) = event::read()? else {
+            continue;
+        };
+        if key_event.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key_event.code {
+            KeyCode::Up => menu.selected = menu.selected.saturating_sub(1),
+            KeyCode::Down => menu.selected = (menu.selected + 1).min(maps.len() - 1),
+            KeyCode::Enter => return Ok(Some(menu.selected)),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// The map select screen: names on the left, the selected board on the
+/// right.
+#[derive(Debug)]
+struct Menu<'a> {
+    maps: &'a [Map],
+    selected: usize,
+}
+
+impl Widget for &Menu<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(Line::from(" maps ".bold()).centered())
+            .title_bottom(
+                Line::from(vec![
+                    " ".into(),
+                    "<Enter>".blue().bold(),
+                    " to play, ".into(),
+                    "<Q>".blue().bold(),
+                    " to quit ".into(),
+                ])
+                .centered(),
+            )
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [names, preview] =
+            Layout::horizontal([Constraint::Length(24), Constraint::Fill(1)]).areas(inner);
+
+        let names_text: Vec<Line> = self
+            .maps
+            .iter()
+            .enumerate()
+            .map(|(i, map)| {
+                let line = Line::from(format!(" {} ", map.name));
+                if i == self.selected {
+                    line.reversed()
+                } else {
+                    line
+                }
+            })
+            .collect();
+        Paragraph::new(names_text).render(names, buf);
+
+        let board: Vec<Line> = self.maps[self.selected]
+            .board
+            .iter()
+            .map(|row| Line::from(row_text(row).fg(Color::Gray)))
+            .collect();
+        Paragraph::new(board).render(preview, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_parse() {
+        let map: Map = "# a test\nname: pillars\nboard:\n#..X\n#XX.\n"
+            .parse()
+            .unwrap();
+        assert_eq!(map.name, "pillars");
+        assert_eq!(
+            map.board,
+            [
+                vec![Some(Fill::Solid), None, None, Some(Fill::Garbage)],
+                vec![
+                    Some(Fill::Solid),
+                    Some(Fill::Garbage),
+                    Some(Fill::Garbage),
+                    None
+                ],
+            ]
+        );
+        assert_eq!(map.to_string().parse::<Map>().unwrap(), map);
+
+        assert!("name: empty\nboard:\n".parse::<Map>().is_err());
+        assert!("board:\n..o.\n".parse::<Map>().is_err());
+        assert!("size: 10\nboard:\n....\n".parse::<Map>().is_err());
+    }
+
+    #[test]
+    fn test_map_load_all() {
+        let dir = std::env::temp_dir().join(format!("tetris-maps-{}", std::process::id()));
+        assert!(Map::load_all(&dir).unwrap().is_empty());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("b.txt"), "board:\n#..#\n").unwrap();
+        std::fs::write(dir.join("a.txt"), "name: first\nboard:\nXX..\n").unwrap();
+        std::fs::write(dir.join("notes.md"), "not a map").unwrap();
+        let maps = Map::load_all(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<_> = maps.iter().map(|map| map.name.as_str()).collect();
+        assert_eq!(names, ["first", "b"]);
+    }
+}
@@ -0,0 +1,570 @@
+use crate::clock::Timer;
+use crate::stats::{self, GameOver, SECTION_LINES};
+use crate::tetris::{Mode, B_TYPE_LINES, DIG_ROWS, ROYALE_BOTS, SPRINT_LINES, ULTRA_TIME};
+use ratatui::{style::Stylize, text::Line};
+use std::fmt;
+use std::time::Duration;
+
+/// Time before the first row of garbage rises in survival.
+pub const SURVIVAL_START: Duration = Duration::from_secs(10);
+/// How much sooner each row of garbage in survival rises than the one before.
+const SURVIVAL_SPEEDUP: Duration = Duration::from_millis(250);
+/// Shortest time between rows of garbage in survival.
+const SURVIVAL_MIN: Duration = Duration::from_secs(2);
+
+/// The rules of a single player mode: how it starts, what it does as the
+/// game goes on, how it ends and what it adds to the screen. Each [`Mode`]
+/// has one, found by [`game_mode`].
+pub trait GameMode: fmt::Debug {
+    fn mode(&self) -> Mode;
+
+    /// Sets the mode up for a new game, saying how the board starts.
+    fn init(&mut self) -> Setup {
+        Setup::default()
+    }
+
+    /// Reacts to `event`, asking the game for whatever it should lead to.
+    fn on_event(&mut self, _event: Event, _game: &View) -> Vec<Effect> {
+        Vec::new()
+    }
+
+    /// Why the game is over by the rules of the mode, if it is, and the
+    /// time into it that it finished.
+    fn outcome(&self, _game: &View) -> Option<(GameOver, Duration)> {
+        None
+    }
+
+    /// The clock shown while playing, for modes played against it.
+    fn timer(&self) -> Option<Timer> {
+        None
+    }
+
+    /// Score of the game in the statistics.
+    fn score(&self, game: &View) -> u64 {
+        game.score.max(0) as u64
+    }
+
+    /// What the mode adds to the name its statistics are kept under, to
+    /// tell games with other settings apart.
+    fn stats_name(&self, _game: &View) -> Option<String> {
+        None
+    }
+
+    /// Whether the mode can be played headless, where time doesn't pass.
+    fn playable_headless(&self) -> bool {
+        true
+    }
+
+    /// Time the goal was reached in, for modes racing to one.
+    fn goal_time(&self, _game: &View) -> Option<Duration> {
+        None
+    }
+
+    /// Progress shown below the board while playing.
+    fn progress(&self, _game: &View) -> Option<Line<'static>> {
+        None
+    }
+
+    /// Title shown over the board once the mode's goal is reached.
+    fn banner(&self, _game: &View) -> Option<Line<'static>> {
+        None
+    }
+}
+
+/// The rules of `mode`.
+pub fn game_mode(mode: Mode) -> Box<dyn GameMode> {
+    match mode {
+        Mode::Marathon => Box::new(Marathon),
+        Mode::Sprint => Box::new(Sprint),
+        Mode::Ultra => Box::new(Ultra),
+        Mode::Dig => Box::new(Dig),
+        Mode::Survival => Box::new(Survival::default()),
+        Mode::BType => Box::new(BType),
+        Mode::Practice => Box::new(Practice),
+        Mode::Royale => Box::new(Royale),
+    }
+}
+
+/// How a game starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Setup {
+    /// Rows of cheese garbage the board starts on.
+    pub cheese: usize,
+    /// Whether the practice tools are on.
+    pub practice: bool,
+    /// Bots played against.
+    pub bots: usize,
+}
+
+/// Things happening in a game that a mode can react to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A piece locked, clearing `lines` lines.
+    Lock { lines: usize },
+    /// `lines` lines were cleared at once and counted.
+    Clear { lines: usize },
+}
+
+/// What a mode has the game do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Pushes up this many rows of garbage, each with its own hole.
+    Garbage(usize),
+    /// Takes the time of the last clear as a split.
+    Split,
+}
+
+/// What a mode sees of the game it's played in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct View {
+    pub score: i32,
+    pub lines: usize,
+    /// Lines to clear in a sprint.
+    pub line_goal: usize,
+    /// Rows of garbage a B-type starts on.
+    pub garbage_height: usize,
+    /// Splits taken so far.
+    pub splits: usize,
+    /// Time the game has taken, stopping when it ends.
+    pub time: Duration,
+    /// When the last line was cleared.
+    pub cleared_at: Duration,
+    /// When the goal was reached.
+    pub finished: Option<Duration>,
+    /// Rows with garbage left in them.
+    pub garbage_rows: usize,
+    /// Players still in a royale, and how many started it.
+    pub players: Option<(usize, usize)>,
+    /// Best time of the mode in milliseconds, if it was ever finished.
+    pub best_time: Option<u64>,
+    /// Best score of the mode, if it was ever played.
+    pub best_score: Option<u64>,
+}
+
+/// `progress`, followed by how far ahead of or behind the personal best it
+/// is, in green when ahead.
+fn progress_line(progress: String, delta: Option<(String, bool)>) -> Line<'static> {
+    let mut line = Line::from(progress);
+    if let Some((delta, ahead)) = delta {
+        line.push_span(if ahead {
+            delta.green().bold()
+        } else {
+            delta.red().bold()
+        });
+    }
+    line
+}
+
+/// `text` with `value` after it, highlighted.
+fn banner(text: &'static str, value: String) -> Line<'static> {
+    Line::from(vec![text.into(), value.green().bold(), " ".into()])
+}
+
+/// Play until topping out, taking a split every [`SECTION_LINES`] lines.
+#[derive(Debug)]
+struct Marathon;
+
+impl GameMode for Marathon {
+    fn mode(&self) -> Mode {
+        Mode::Marathon
+    }
+
+    fn on_event(&mut self, event: Event, game: &View) -> Vec<Effect> {
+        match event {
+            Event::Clear { .. } if game.lines >= (game.splits + 1) * SECTION_LINES => {
+                vec![Effect::Split]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Clear the line goal as fast as possible.
+#[derive(Debug)]
+struct Sprint;
+
+impl GameMode for Sprint {
+    fn mode(&self) -> Mode {
+        Mode::Sprint
+    }
+
+    fn stats_name(&self, game: &View) -> Option<String> {
+        (game.line_goal != SPRINT_LINES).then(|| game.line_goal.to_string())
+    }
+
+    fn outcome(&self, game: &View) -> Option<(GameOver, Duration)> {
+        (game.lines >= game.line_goal).then_some((GameOver::Goal, game.cleared_at))
+    }
+
+    fn timer(&self) -> Option<Timer> {
+        Some(Timer::Up)
+    }
+
+    fn goal_time(&self, game: &View) -> Option<Duration> {
+        game.finished
+    }
+
+    fn progress(&self, game: &View) -> Option<Line<'static>> {
+        let goal = game.line_goal;
+        let lines = game.lines.min(goal);
+        // compare against the best run's average pace at the last clear
+        let delta = game.best_time.filter(|_| game.lines > 0).map(|ms| {
+            let pace = ms as f64 * lines as f64 / goal as f64 / 1000.0;
+            let delta = game.cleared_at.as_secs_f64() - pace;
+            (format!("{delta:+.2}s "), delta <= 0.0)
+        });
+        Some(progress_line(format!(" {lines}/{goal} "), delta))
+    }
+
+    fn banner(&self, game: &View) -> Option<Line<'static>> {
+        let time = game.finished?;
+        Some(banner(" finished in ", stats::format_lap(time)))
+    }
+}
+
+/// Score as much as possible before the time runs out.
+#[derive(Debug)]
+struct Ultra;
+
+impl GameMode for Ultra {
+    fn mode(&self) -> Mode {
+        Mode::Ultra
+    }
+
+    fn outcome(&self, game: &View) -> Option<(GameOver, Duration)> {
+        (game.time >= ULTRA_TIME).then_some((GameOver::TimeUp, ULTRA_TIME))
+    }
+
+    fn playable_headless(&self) -> bool {
+        false
+    }
+
+    fn timer(&self) -> Option<Timer> {
+        Some(Timer::Down(ULTRA_TIME))
+    }
+
+    fn progress(&self, game: &View) -> Option<Line<'static>> {
+        // the time left is shown beside the board
+        let delta = game.best_score.map(|best| {
+            let pace = best as f64 * game.time.as_secs_f64() / ULTRA_TIME.as_secs_f64();
+            let delta = (game.score as f64 - pace).round() as i64;
+            (format!("{delta:+} "), delta >= 0)
+        });
+        Some(progress_line(format!(" {} points ", game.score), delta))
+    }
+
+    fn banner(&self, game: &View) -> Option<Line<'static>> {
+        Some(banner(" time up! score: ", game.score.to_string()))
+    }
+}
+
+/// Dig through rows of cheese as fast as possible.
+#[derive(Debug)]
+struct Dig;
+
+impl GameMode for Dig {
+    fn mode(&self) -> Mode {
+        Mode::Dig
+    }
+
+    fn init(&mut self) -> Setup {
+        Setup {
+            cheese: DIG_ROWS,
+            ..Setup::default()
+        }
+    }
+
+    fn outcome(&self, game: &View) -> Option<(GameOver, Duration)> {
+        (game.lines > 0 && game.garbage_rows == 0).then_some((GameOver::Goal, game.cleared_at))
+    }
+
+    fn timer(&self) -> Option<Timer> {
+        Some(Timer::Up)
+    }
+
+    fn goal_time(&self, game: &View) -> Option<Duration> {
+        game.finished
+    }
+
+    fn progress(&self, game: &View) -> Option<Line<'static>> {
+        Some(Line::from(format!(" {} rows left ", game.garbage_rows)))
+    }
+
+    fn banner(&self, game: &View) -> Option<Line<'static>> {
+        Sprint.banner(game)
+    }
+}
+
+/// Survive garbage rising on a timer, ever faster.
+#[derive(Debug)]
+struct Survival {
+    /// Rows risen so far.
+    rows: usize,
+    /// Time into the game at which the next row rises.
+    next_rise: Duration,
+}
+
+impl Default for Survival {
+    fn default() -> Self {
+        Self {
+            rows: 0,
+            next_rise: SURVIVAL_START,
+        }
+    }
+}
+
+impl Survival {
+    /// Counts the rows due by `now`, scheduling the ones after.
+    fn rise(&mut self, now: Duration) -> usize {
+        let mut rows = 0;
+        while self.next_rise <= now {
+            rows += 1;
+            self.rows += 1;
+            let interval = SURVIVAL_START
+                .saturating_sub(SURVIVAL_SPEEDUP * self.rows as u32)
+                .max(SURVIVAL_MIN);
+            self.next_rise += interval;
+        }
+        rows
+    }
+}
+
+impl GameMode for Survival {
+    fn mode(&self) -> Mode {
+        Mode::Survival
+    }
+
+    fn init(&mut self) -> Setup {
+        *self = Self::default();
+        Setup::default()
+    }
+
+    fn on_event(&mut self, event: Event, game: &View) -> Vec<Effect> {
+        match event {
+            // garbage rises between pieces, never under the falling one
+            Event::Lock { .. } => match self.rise(game.time) {
+                0 => Vec::new(),
+                rows => vec![Effect::Garbage(rows)],
+            },
+            Event::Clear { .. } => Vec::new(),
+        }
+    }
+
+    fn playable_headless(&self) -> bool {
+        false
+    }
+
+    fn timer(&self) -> Option<Timer> {
+        Some(Timer::Up)
+    }
+
+    // the score of a survival is the time survived
+    fn score(&self, game: &View) -> u64 {
+        game.time.as_secs()
+    }
+
+    fn progress(&self, game: &View) -> Option<Line<'static>> {
+        let next = self.next_rise.saturating_sub(game.time);
+        let delta = game.best_score.map(|best| {
+            let delta = game.time.as_secs() as i64 - best as i64;
+            (format!("{delta:+}s "), delta >= 0)
+        });
+        Some(progress_line(
+            format!(" next row in {}s ", next.as_secs() + 1),
+            delta,
+        ))
+    }
+}
+
+/// Clear a set number of lines from a field of garbage.
+#[derive(Debug)]
+struct BType;
+
+impl GameMode for BType {
+    fn mode(&self) -> Mode {
+        Mode::BType
+    }
+
+    fn stats_name(&self, game: &View) -> Option<String> {
+        Some(format!("height {}", game.garbage_height))
+    }
+
+    fn outcome(&self, game: &View) -> Option<(GameOver, Duration)> {
+        (game.lines >= B_TYPE_LINES).then_some((GameOver::Goal, game.cleared_at))
+    }
+
+    fn progress(&self, game: &View) -> Option<Line<'static>> {
+        let left = B_TYPE_LINES.saturating_sub(game.lines);
+        Some(Line::from(format!(" {left} lines left ")))
+    }
+
+    fn banner(&self, game: &View) -> Option<Line<'static>> {
+        Some(banner(" cleared! score: ", game.score.to_string()))
+    }
+}
+
+/// Sandbox with pieces of the player's choice. Its tools are part of the
+/// game rather than the mode, as replays and puzzles share them.
+#[derive(Debug)]
+struct Practice;
+
+impl GameMode for Practice {
+    fn mode(&self) -> Mode {
+        Mode::Practice
+    }
+
+    fn init(&mut self) -> Setup {
+        Setup {
+            practice: true,
+            ..Setup::default()
+        }
+    }
+}
+
+/// Outlast bots attacking each other and the player. The bots are run by
+/// the game, which counts those still playing.
+#[derive(Debug)]
+struct Royale;
+
+impl GameMode for Royale {
+    fn mode(&self) -> Mode {
+        Mode::Royale
+    }
+
+    fn init(&mut self) -> Setup {
+        Setup {
+            bots: ROYALE_BOTS,
+            ..Setup::default()
+        }
+    }
+
+    fn playable_headless(&self) -> bool {
+        false
+    }
+
+    fn progress(&self, game: &View) -> Option<Line<'static>> {
+        let (left, total) = game.players?;
+        Some(Line::from(format!(" {left}/{total} left ")))
+    }
+
+    fn banner(&self, _game: &View) -> Option<Line<'static>> {
+        Some(Line::from(" you win! ".green().bold()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn test_registry() {
+        for &mode in Mode::value_variants() {
+            let mut rules = game_mode(mode);
+            assert_eq!(rules.mode(), mode);
+
+            let setup = rules.init();
+            assert_eq!(setup.cheese > 0, mode == Mode::Dig);
+            assert_eq!(setup.practice, mode == Mode::Practice);
+            assert_eq!(setup.bots > 0, mode == Mode::Royale);
+            // modes going by the clock need time to pass
+            assert_eq!(
+                rules.playable_headless(),
+                !matches!(mode, Mode::Ultra | Mode::Survival | Mode::Royale)
+            );
+        }
+    }
+
+    #[test]
+    fn test_stats_name() {
+        let game = View {
+            line_goal: SPRINT_LINES,
+            garbage_height: 3,
+            ..View::default()
+        };
+        assert_eq!(Sprint.stats_name(&game), None);
+        assert_eq!(BType.stats_name(&game).as_deref(), Some("height 3"));
+        assert_eq!(Marathon.stats_name(&game), None);
+        let game = View {
+            line_goal: 20,
+            ..game
+        };
+        assert_eq!(Sprint.stats_name(&game).as_deref(), Some("20"));
+    }
+
+    #[test]
+    fn test_splits() {
+        let clear = Event::Clear { lines: 4 };
+        let game = View {
+            lines: SECTION_LINES - 1,
+            ..View::default()
+        };
+        assert_eq!(Marathon.on_event(clear, &game), []);
+        let game = View {
+            lines: SECTION_LINES + 2,
+            ..game
+        };
+        assert_eq!(Marathon.on_event(clear, &game), [Effect::Split]);
+        assert_eq!(Marathon.on_event(Event::Lock { lines: 4 }, &game), []);
+        let game = View { splits: 1, ..game };
+        assert_eq!(Marathon.on_event(clear, &game), []);
+        // only marathons take splits
+        assert_eq!(Sprint.on_event(clear, &game), []);
+    }
+
+    #[test]
+    fn test_survival() {
+        let mut survival = Survival::default();
+        assert_eq!(survival.rise(Duration::from_secs(9)), 0);
+        assert_eq!(survival.rise(SURVIVAL_START), 1);
+        assert_eq!(survival.next_rise, SURVIVAL_START * 2 - SURVIVAL_SPEEDUP);
+        // rows come faster and faster, but never faster than the minimum
+        assert_eq!(survival.rise(Duration::from_secs(30)), 2);
+        let before = survival.next_rise;
+        survival.rise(Duration::from_secs(600));
+        assert!(survival.next_rise > Duration::from_secs(600));
+        assert!(survival.next_rise - before > SURVIVAL_MIN * 10);
+
+        let game = View {
+            time: Duration::from_secs(9),
+            ..View::default()
+        };
+        let mut survival = Survival::default();
+        assert_eq!(survival.on_event(Event::Lock { lines: 0 }, &game), []);
+        let game = View {
+            time: SURVIVAL_START,
+            ..game
+        };
+        let effects = survival.on_event(Event::Lock { lines: 0 }, &game);
+        assert_eq!(effects, [Effect::Garbage(1)]);
+    }
+
+    #[test]
+    fn test_outcome() {
+        let game = View {
+            lines: 39,
+            line_goal: 40,
+            cleared_at: Duration::from_secs(50),
+            garbage_rows: 2,
+            ..View::default()
+        };
+        assert_eq!(Sprint.outcome(&game), None);
+        assert_eq!(Dig.outcome(&game), None);
+        let game = View {
+            lines: 40,
+            garbage_rows: 0,
+            ..game
+        };
+        let goal = Some((GameOver::Goal, Duration::from_secs(50)));
+        assert_eq!(Sprint.outcome(&game), goal);
+        assert_eq!(Dig.outcome(&game), goal);
+        assert_eq!(BType.outcome(&game), goal);
+        assert_eq!(Marathon.outcome(&game), None);
+        assert_eq!(Ultra.outcome(&game), None);
+        let game = View {
+            time: ULTRA_TIME,
+            ..game
+        };
+        assert_eq!(Ultra.outcome(&game), Some((GameOver::TimeUp, ULTRA_TIME)));
+    }
+}
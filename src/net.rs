@@ -0,0 +1,146 @@
+use crate::protocol::{self, Handicap, Handshake, Message};
+use crate::tetrinet::{self, Session};
+use anyhow::Result;
+use std::io::BufReader;
+use std::net::{IpAddr, Ipv4Addr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+/// Connection from a game client to `tetris-server`.
+///
+/// Incoming messages are read on a background thread so the game loop never
+/// blocks on the network.
+#[derive(Debug)]
+pub struct Client {
+    name: String,
+    stream: TcpStream,
+    incoming: Receiver<Message>,
+    /// Number of the player on a TetriNet server, 0 until known, or `None`
+    /// when connected to `tetris-server`.
+    tetrinet: Option<Arc<AtomicU8>>,
+}
+
+impl Client {
+    /// Connects to the server at `addr` and joins `room` as `name`.
+    pub fn connect(
+        addr: impl ToSocketAddrs,
+        room: &str,
+        name: &str,
+        handshake: Handshake,
+        handicap: Handicap,
+    ) -> Result<Self> {
+        let join = Message::Join {
+            room: room.to_owned(),
+            name: name.to_owned(),
+            handshake,
+            handicap,
+        };
+        Self::open(addr, name, &join)
+    }
+
+    /// Connects to the server at `addr` to watch the match in `room`
+    /// without playing.
+    pub fn watch(addr: impl ToSocketAddrs, room: &str) -> Result<Self> {
+        let watch = Message::Watch {
+            room: room.to_owned(),
+            version: protocol::PROTOCOL_VERSION,
+        };
+        Self::open(addr, "spectator", &watch)
+    }
+
+    /// Connects to `tetris-server` at `addr` and sends `first`, reading
+    /// whatever comes back on a background thread.
+    fn open(addr: impl ToSocketAddrs, name: &str, first: &Message) -> Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        protocol::send(&mut stream, first)?;
+
+        let (tx, incoming) = mpsc::channel();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        thread::spawn(move || {
+            while let Ok(Some(msg)) = protocol::recv(&mut reader) {
+                if tx.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            name: name.to_owned(),
+            stream,
+            incoming,
+            tetrinet: None,
+        })
+    }
+
+    /// Connects to the TetriNet server at `addr` and logs in as `name`,
+    /// translating between its protocol and [`Message`]s.
+    pub fn connect_tetrinet(addr: impl ToSocketAddrs, name: &str) -> Result<Self> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let server = match stream.peer_addr()?.ip() {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().unwrap_or(Ipv4Addr::UNSPECIFIED),
+        };
+        tetrinet::send(&mut stream, &tetrinet::login(name, server, rand::random()))?;
+
+        let me = Arc::new(AtomicU8::new(0));
+        let (tx, incoming) = mpsc::channel();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream.try_clone()?;
+        let player = me.clone();
+        thread::spawn(move || {
+            let mut session = Session::default();
+            while let Ok(Some(line)) = tetrinet::recv(&mut reader) {
+                let (messages, replies) = session.handle(&line);
+                player.store(session.me().unwrap_or(0), Ordering::Relaxed);
+                for reply in replies {
+                    let _ = tetrinet::send(&mut writer, &reply);
+                }
+                if messages.into_iter().any(|msg| tx.send(msg).is_err()) {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            name: name.to_owned(),
+            stream,
+            incoming,
+            tetrinet: Some(me),
+        })
+    }
+
+    /// Name the player joined as.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn send(&mut self, msg: &Message) -> Result<()> {
+        let Some(me) = &self.tetrinet else {
+            return protocol::send(&mut self.stream, msg);
+        };
+
+        let me = me.load(Ordering::Relaxed);
+        if me == 0 {
+            return Ok(());
+        }
+        for line in tetrinet::encode(msg, me) {
+            tetrinet::send(&mut self.stream, &line)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the next pending message, if any.
+    ///
+    /// `Err` means the server has disconnected.
+    pub fn try_recv(&self) -> Result<Option<Message>, TryRecvError> {
+        match self.incoming.try_recv() {
+            Ok(msg) => Ok(Some(msg)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,304 @@
+use crate::{
+    block::{Block, PieceKind},
+    puzzle,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+
+/// A standard way to stack the first pieces of a game, placement by
+/// placement.
+///
+/// Opener files look like this, with the board aligned to the bottom and
+/// each cell numbered by the placement that fills it, in queue order, from 1
+/// to 9 and then on from `a`:
+///
+/// ```text
+/// # comment
+/// name: stairs
+/// queue: IOT
+/// board:
+/// .......3..
+/// 22....333.
+/// 221111....
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opener {
+    pub name: String,
+    pub queue: Vec<PieceKind>,
+    /// Cells of every placement as column and row of the board, counting
+    /// rows from the top.
+    pub steps: Vec<Vec<(usize, usize)>>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Opener {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        text.parse()
+            .with_context(|| format!("invalid opener {}", path.display()))
+    }
+
+    /// Cells of placement `step` on a board `height` rows high.
+    pub fn target(&self, step: usize, height: usize) -> Option<Vec<(i32, i32)>> {
+        let offset = height.checked_sub(self.height)?;
+        let cells = self.steps.get(step)?;
+        Some(
+            cells
+                .iter()
+                .map(|&(x, y)| (x as i32, (y + offset) as i32))
+                .collect(),
+        )
+    }
+}
+
+impl std::str::FromStr for Opener {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut name = None;
+        let mut queue = None;
+        let mut lines = s.lines();
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("expected `key: value`, got {line:?}"))?;
+            match key.trim() {
+                "name" => name = Some(value.trim().to_owned()),
+                "queue" => queue = Some(puzzle::parse_queue(value)?),
+                "board" => break,
+                key => bail!("unknown key {key:?}"),
+            }
+        }
+        let queue = queue.ok_or_else(|| anyhow!("missing queue"))?;
+
+        let rows: Vec<&str> = lines.map(str::trim).filter(|l| !l.is_empty()).collect();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        let mut steps = vec![Vec::new(); queue.len()];
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                bail!("rows have to be {width} cells wide");
+            }
+            for (x, c) in row.chars().enumerate() {
+                if c == '.' {
+                    continue;
+                }
+                let step = c
+                    .to_digit(36)
+                    .filter(|&n| n >= 1 && n as usize <= queue.len())
+                    .ok_or_else(|| anyhow!("cell {c:?} isn't a placement of the queue"))?;
+                steps[step as usize - 1].push((x, y));
+            }
+        }
+        for (i, (cells, &kind)) in steps.iter().zip(&queue).enumerate() {
+            if !is_shape(cells, kind) {
+                bail!("placement {} isn't an {} piece", i + 1, kind.name());
+            }
+        }
+
+        Ok(Self {
+            name: name.ok_or_else(|| anyhow!("missing name"))?,
+            queue,
+            steps,
+            width,
+            height: rows.len(),
+        })
+    }
+}
+
+/// Whether `cells` are a `kind` piece in some rotation.
+fn is_shape(cells: &[(usize, usize)], kind: PieceKind) -> bool {
+    if cells.is_empty() {
+        return false;
+    }
+    let cells: Vec<_> = cells.iter().map(|&(x, y)| (x as i32, y as i32)).collect();
+    let target = sorted(&Block::new(&cells).normalized());
+    let mut block = kind.block();
+    (0..4).any(|_| {
+        block = block.rotate();
+        sorted(&block.normalized()) == target
+    })
+}
+
+/// Cells of `block`, sorted.
+fn sorted(block: &Block) -> Vec<(i32, i32)> {
+    let mut coords = block.coords().to_vec();
+    coords.sort_unstable();
+    coords
+}
+
+/// Progress through an opener, scoring every placement against its target.
+#[derive(Debug)]
+pub struct Trainer {
+    opener: Opener,
+    step: usize,
+    correct: usize,
+}
+
+impl Trainer {
+    pub fn new(opener: Opener) -> Self {
+        Self {
+            opener,
+            step: 0,
+            correct: 0,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.opener.name
+    }
+
+    /// Placements done so far, and how many of them hit their target.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.step, self.correct)
+    }
+
+    /// Percentage of the placements done so far that hit their target.
+    pub fn accuracy(&self) -> f64 {
+        match self.step {
+            0 => 100.0,
+            step => 100.0 * self.correct as f64 / step as f64,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.opener.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.opener.queue.is_empty()
+    }
+
+    pub fn done(&self) -> bool {
+        self.step >= self.len()
+    }
+
+    /// The piece of the current placement, unless the opener is done.
+    pub fn next_shape(&self) -> Option<PieceKind> {
+        self.opener.queue.get(self.step).copied()
+    }
+
+    /// Pieces of the placements after the current one.
+    pub fn queue(&self) -> impl Iterator<Item = PieceKind> + '_ {
+        self.opener.queue.iter().skip(self.step + 1).copied()
+    }
+
+    /// Where the falling piece should go on a board `height` rows high.
+    pub fn target(&self, height: usize) -> Option<Vec<(i32, i32)>> {
+        self.opener.target(self.step, height)
+    }
+
+    /// Scores a piece locked on `cells` of a board `height` rows high, moving
+    /// on to the next placement. Returns whether it hit its target.
+    pub fn on_lock(&mut self, cells: &[(i32, i32)], height: usize) -> bool {
+        let hit = self.target(height).is_some_and(|mut target| {
+            let mut cells = cells.to_vec();
+            target.sort_unstable();
+            cells.sort_unstable();
+            target == cells
+        });
+        self.step += 1;
+        if hit {
+            self.correct += 1;
+        }
+        hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STAIRS: &str = "
+        # the example from the docs
+        name: stairs
+        queue: IOT
+        board:
+        .......3..
+        22....333.
+        221111....
+    ";
+
+    #[test]
+    fn test_parse_opener() {
+        let opener: Opener = STAIRS.parse().unwrap();
+        assert_eq!(opener.name, "stairs");
+        assert_eq!(opener.queue, [PieceKind::I, PieceKind::O, PieceKind::T]);
+        assert_eq!(opener.steps[0], [(2, 2), (3, 2), (4, 2), (5, 2)]);
+        assert_eq!((opener.width, opener.height), (10, 3));
+        assert_eq!(
+            opener.target(2, 20).unwrap(),
+            [(7, 17), (6, 18), (7, 18), (8, 18)]
+        );
+
+        assert!("name: x\nboard:\n1111\n".parse::<Opener>().is_err());
+        assert!("name: x\nqueue: I\nboard:\n2222\n"
+            .parse::<Opener>()
+            .is_err());
+        assert!("name: x\nqueue: I\nboard:\n11..\n.11.\n"
+            .parse::<Opener>()
+            .is_err());
+        assert!("queue: O\nboard:\n11\n11\n".parse::<Opener>().is_err());
+        // placements past the ninth go on in letters
+        let board: String = "123456789a"
+            .chars()
+            .map(|c| format!("{c}{c}{c}{c}\n"))
+            .collect();
+        let opener: Opener = format!("name: x\nqueue: IIIIIIIIII\nboard:\n{board}")
+            .parse()
+            .unwrap();
+        assert_eq!(opener.steps[9], [(0, 9), (1, 9), (2, 9), (3, 9)]);
+    }
+
+    #[test]
+    fn test_trainer() {
+        let mut trainer = Trainer::new(STAIRS.parse().unwrap());
+        assert_eq!(trainer.next_shape(), Some(PieceKind::I));
+        assert_eq!(trainer.queue().count(), 2);
+        assert!(trainer.on_lock(&[(5, 19), (4, 19), (3, 19), (2, 19)], 20));
+        assert!(!trainer.on_lock(&[(2, 18), (3, 18), (2, 19), (3, 19)], 20));
+        assert_eq!(trainer.progress(), (2, 1));
+        assert_eq!(trainer.accuracy(), 50.0);
+        assert_eq!(trainer.next_shape(), Some(PieceKind::T));
+        assert_eq!(trainer.queue().count(), 0);
+        assert!(!trainer.done());
+        trainer.on_lock(&[], 20);
+        assert!(trainer.done());
+        assert_eq!(trainer.target(20), None);
+    }
+
+    #[test]
+    fn test_shipped_openers() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("openers");
+        let mut count = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let opener = Opener::load(entry.unwrap().path()).unwrap();
+            assert_eq!(opener.width, 10, "{}", opener.name);
+
+            // every placement can be dropped straight down onto the ones
+            // before it
+            let mut filled = vec![vec![false; opener.width]; opener.height];
+            for (step, cells) in opener.steps.iter().enumerate() {
+                let resting = cells
+                    .iter()
+                    .any(|&(x, y)| y + 1 == opener.height || filled[y + 1][x]);
+                let open = cells
+                    .iter()
+                    .all(|&(x, y)| (0..y).all(|above| !filled[above][x]));
+                assert!(resting && open, "{} step {}", opener.name, step + 1);
+                for &(x, y) in cells {
+                    filled[y][x] = true;
+                }
+            }
+            count += 1;
+        }
+        assert!(count >= 3);
+    }
+}
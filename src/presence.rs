@@ -0,0 +1,79 @@
+use anyhow::Result;
+use discord_rich_presence::{activity, DiscordIpc, DiscordIpcClient};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Discord drops updates sent more often than this.
+const UPDATE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// What's being played, shown on the player's Discord profile until it's
+/// dropped.
+pub struct Presence {
+    client: DiscordIpcClient,
+    /// Details, state and start of the activity last sent.
+    shown: Option<(String, String, i64)>,
+    updated: Option<Instant>,
+}
+
+impl Presence {
+    /// Connects to the Discord app running on this machine, as the
+    /// application `app_id`.
+    pub fn connect(app_id: &str) -> Result<Self> {
+        let mut client = DiscordIpcClient::new(app_id);
+        client.connect()?;
+        Ok(Self {
+            client,
+            shown: None,
+            updated: None,
+        })
+    }
+
+    /// Shows `details` and `state` of a game that has been going on for
+    /// `elapsed`, unless that's what was shown last or the last update was
+    /// too recent. Failures are only logged, the game goes on without.
+    pub fn update(&mut self, details: &str, state: &str, elapsed: Duration) {
+        if self
+            .updated
+            .is_some_and(|at| at.elapsed() < UPDATE_INTERVAL)
+        {
+            return;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        // Discord counts the time up from the start itself
+        let start = now.saturating_sub(elapsed).as_millis() as i64;
+        let shown = (details.to_owned(), state.to_owned(), start);
+        if self.shown.as_ref().is_some_and(|last| {
+            // a second either way is just rounding
+            (last.0.as_str(), last.1.as_str()) == (details, state) && last.2.abs_diff(start) <= 1000
+        }) {
+            return;
+        }
+
+        let activity = activity::Activity::new()
+            .details(details)
+            .state(state)
+            .timestamps(activity::Timestamps::new().start(start));
+        match self.client.set_activity(activity) {
+            Ok(()) => self.shown = Some(shown),
+            Err(err) => warn!("failed to update Discord presence: {err}"),
+        }
+        self.updated = Some(Instant::now());
+    }
+}
+
+impl std::fmt::Debug for Presence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Presence")
+            .field("shown", &self.shown)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Presence {
+    fn drop(&mut self) {
+        let _ = self.client.clear_activity();
+        let _ = self.client.close();
+    }
+}
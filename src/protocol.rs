@@ -0,0 +1,433 @@
+use crate::attack::AttackTable;
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+/// Default address `tetris-server` listens on.
+pub const DEFAULT_ADDR: &str = "0.0.0.0:7878";
+
+/// Version of the message format below. Bump on incompatible changes.
+pub const PROTOCOL_VERSION: u32 = 8;
+
+/// Most players a room can hold.
+pub const MAX_PLAYERS: usize = 4;
+
+/// Settings both players of a match have to agree on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handshake {
+    pub version: u32,
+    pub ruleset: String,
+    pub width: usize,
+    pub height: usize,
+    pub attack: AttackTable,
+    /// Whether clearing lines wins items to use on either player.
+    pub items: bool,
+    /// Players a match starts with, 2 for versus and up to [`MAX_PLAYERS`]
+    /// for a free-for-all.
+    #[serde(default = "two_players")]
+    pub players: usize,
+}
+
+fn two_players() -> usize {
+    2
+}
+
+impl Handshake {
+    /// Checks that a client sending `other` can play against this one.
+    pub fn check(&self, other: &Handshake) -> Result<()> {
+        if self.version != other.version {
+            bail!(
+                "protocol version mismatch: expected v{}, got v{}",
+                self.version,
+                other.version
+            );
+        }
+        if self.ruleset != other.ruleset {
+            bail!(
+                "ruleset mismatch: room plays {}, client plays {}",
+                self.ruleset,
+                other.ruleset
+            );
+        }
+        if (self.width, self.height) != (other.width, other.height) {
+            bail!(
+                "board size mismatch: room uses {}x{}, client uses {}x{}",
+                self.width,
+                self.height,
+                other.width,
+                other.height
+            );
+        }
+        if self.attack != other.attack {
+            bail!("attack table mismatch: room uses {:?}", self.attack);
+        }
+        if self.items != other.items {
+            let room = if self.items { "with" } else { "without" };
+            bail!("item battle mismatch: room plays {room} items");
+        }
+        if self.players != other.players {
+            bail!(
+                "player count mismatch: room is for {}, client for {}",
+                self.players,
+                other.players
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Rows of garbage a player asks for both players to start a match with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Handicap {
+    /// Rows under the player's own pieces.
+    pub own: usize,
+    /// Rows under the opponent's.
+    pub opponent: usize,
+}
+
+/// Messages exchanged between game clients and `tetris-server`.
+///
+/// Messages are sent as newline-delimited JSON. Messages relayed from
+/// another player say `from` which of the receiver's opponents sent them, an
+/// index into the opponents of [`Message::Start`], or into the players of
+/// [`Message::Watching`] for a spectator; the server fills it in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// Client -> server: join `room`, creating it with the settings in
+    /// `handshake` if needed.
+    Join {
+        room: String,
+        name: String,
+        handshake: Handshake,
+        #[serde(default)]
+        handicap: Handicap,
+    },
+    /// Client -> server: watch the match in `room` without playing.
+    Watch { room: String, version: u32 },
+    /// Server -> spectator: the match in the watched room is on between
+    /// `players`, on boards `width` by `height`.
+    Watching {
+        players: Vec<String>,
+        width: usize,
+        height: usize,
+    },
+    /// Server -> client: every player is present and the match begins
+    /// against `opponents`, in the order of the room, with pieces drawn
+    /// from an RNG seeded with `seed`, on a board with `handicap` rows of
+    /// garbage.
+    Start {
+        opponents: Vec<String>,
+        seed: u64,
+        #[serde(default)]
+        handicap: usize,
+    },
+    /// Client -> server -> opponents and spectators: snapshot of the
+    /// sender's board, one string per row with `.` for empty cells.
+    Board {
+        rows: Vec<String>,
+        #[serde(default)]
+        from: usize,
+    },
+    /// Client -> server -> opponent: lines of garbage to add, sent on to one
+    /// opponent still playing.
+    Garbage {
+        lines: u32,
+        #[serde(default)]
+        from: usize,
+    },
+    /// Client -> server -> opponent: an item used on an opponent still
+    /// playing.
+    Item {
+        item: Item,
+        #[serde(default)]
+        from: usize,
+    },
+    /// Client -> server: from now on, send the sender's garbage and items
+    /// to whoever `targeting` picks, `opponent` being the index of the one
+    /// picked by hand.
+    Target {
+        targeting: Targeting,
+        #[serde(default)]
+        opponent: usize,
+    },
+    /// Client -> server: the sender topped out.
+    Lost,
+    /// Server -> client: an opponent, or a player watched, topped out or
+    /// left, and plays on no more.
+    Eliminated { from: usize },
+    /// Server -> client: the match is over for the receiver, who won by
+    /// being the last one standing or lost by topping out.
+    GameOver { won: bool },
+    /// Server -> client: the request could not be fulfilled.
+    Error { reason: String },
+}
+
+/// Items of an item battle, won by clearing lines. They take effect when
+/// the player they're used on next locks a piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Item {
+    /// Adds a line of garbage.
+    AddLine,
+    /// Removes the bottom row.
+    ClearLine,
+    /// Shifts every row sideways by a random amount.
+    Scramble,
+    /// Swaps the next piece for a random one.
+    Swap,
+}
+
+impl Item {
+    pub const ALL: [Item; 4] = [Item::AddLine, Item::ClearLine, Item::Scramble, Item::Swap];
+
+    /// Letter the item is shown as.
+    pub fn symbol(self) -> char {
+        match self {
+            Item::AddLine => 'A',
+            Item::ClearLine => 'C',
+            Item::Scramble => 'S',
+            Item::Swap => 'N',
+        }
+    }
+}
+
+/// Whom a player's garbage and items go to in a free-for-all.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Targeting {
+    /// A random opponent, every attack
+    #[default]
+    Random,
+    /// The opponent who last attacked the player
+    Attacker,
+    /// The opponent with the most rows of their board filled
+    MostLines,
+    /// The opponent with the fewest rows of their board filled
+    LeastLines,
+    /// The opponent picked by hand
+    Manual,
+}
+
+impl Targeting {
+    pub fn name(self) -> &'static str {
+        match self {
+            Targeting::Random => "random",
+            Targeting::Attacker => "attacker",
+            Targeting::MostLines => "most lines",
+            Targeting::LeastLines => "least lines",
+            Targeting::Manual => "manual",
+        }
+    }
+
+    /// Index of the opponent to attack, given the rows each opponent has
+    /// filled or `None` once they're out, the index of the last one to
+    /// attack and of the one picked by hand. Ties and targets that are out
+    /// are settled by `roll`, any random number.
+    pub fn pick(
+        self,
+        rows: &[Option<usize>],
+        attacker: Option<usize>,
+        manual: usize,
+        roll: usize,
+    ) -> Option<usize> {
+        let playing = |i: &usize| rows.get(*i).is_some_and(Option::is_some);
+        let random = |choices: Vec<usize>| match choices.len() {
+            0 => None,
+            n => Some(choices[roll % n]),
+        };
+        let tied = |best: Option<usize>| {
+            let tied = (0..rows.len()).filter(|&i| rows[i].is_some() && rows[i] == best);
+            random(tied.collect())
+        };
+        let picked = match self {
+            Targeting::Random => None,
+            Targeting::Attacker => attacker.filter(playing),
+            Targeting::MostLines => tied(rows.iter().flatten().max().copied()),
+            Targeting::LeastLines => tied(rows.iter().flatten().min().copied()),
+            Targeting::Manual => Some(manual).filter(playing),
+        };
+        picked.or_else(|| random((0..rows.len()).filter(playing).collect()))
+    }
+}
+
+/// Rows of a board snapshot with anything in them.
+pub fn filled_rows(rows: &[String]) -> usize {
+    rows.iter()
+        .filter(|row| row.chars().any(|c| c != '.'))
+        .count()
+}
+
+/// Writes `msg` as a single line.
+pub fn send(writer: &mut impl Write, msg: &Message) -> Result<()> {
+    let mut line = serde_json::to_string(msg)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the next message, returning `None` once the peer has disconnected.
+pub fn recv(reader: &mut impl BufRead) -> Result<Option<Message>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_roundtrip() {
+        let messages = [
+            Message::Join {
+                room: "lobby".into(),
+                name: "alice".into(),
+                handshake: Handshake {
+                    version: PROTOCOL_VERSION,
+                    ruleset: "classic".into(),
+                    width: 10,
+                    height: 20,
+                    attack: AttackTable::default(),
+                    items: false,
+                    players: 2,
+                },
+                handicap: Handicap {
+                    own: 0,
+                    opponent: 3,
+                },
+            },
+            Message::Watch {
+                room: "lobby".into(),
+                version: PROTOCOL_VERSION,
+            },
+            Message::Watching {
+                players: vec!["alice".into(), "bob".into()],
+                width: 10,
+                height: 20,
+            },
+            Message::Start {
+                opponents: vec!["bob".into(), "carol".into()],
+                seed: 42,
+                handicap: 3,
+            },
+            Message::Board {
+                rows: vec!["..#".into(), "###".into()],
+                from: 1,
+            },
+            Message::Garbage { lines: 4, from: 0 },
+            Message::Item {
+                item: Item::Scramble,
+                from: 1,
+            },
+            Message::Target {
+                targeting: Targeting::MostLines,
+                opponent: 0,
+            },
+            Message::Lost,
+            Message::Eliminated { from: 1 },
+            Message::GameOver { won: true },
+        ];
+
+        let mut buf = Vec::new();
+        for msg in &messages {
+            send(&mut buf, msg).unwrap();
+        }
+
+        let mut reader = Cursor::new(buf);
+        for msg in &messages {
+            assert_eq!(recv(&mut reader).unwrap().as_ref(), Some(msg));
+        }
+        assert_eq!(recv(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_handshake_check() {
+        let handshake = Handshake {
+            version: PROTOCOL_VERSION,
+            ruleset: "classic".into(),
+            width: 10,
+            height: 20,
+            attack: AttackTable::default(),
+            items: false,
+            players: 2,
+        };
+        assert!(handshake.check(&handshake).is_ok());
+
+        let newer = Handshake {
+            version: PROTOCOL_VERSION + 1,
+            ..handshake.clone()
+        };
+        assert!(handshake.check(&newer).is_err());
+
+        let ruleset = Handshake {
+            ruleset: "modern".into(),
+            ..handshake.clone()
+        };
+        assert!(handshake.check(&ruleset).is_err());
+
+        let wide = Handshake {
+            width: 12,
+            ..handshake.clone()
+        };
+        assert!(handshake.check(&wide).is_err());
+
+        let attack = Handshake {
+            attack: AttackTable {
+                single: 1,
+                ..Default::default()
+            },
+            ..handshake.clone()
+        };
+        assert!(handshake.check(&attack).is_err());
+
+        let items = Handshake {
+            items: true,
+            ..handshake.clone()
+        };
+        assert!(handshake.check(&items).is_err());
+
+        let ffa = Handshake {
+            players: 4,
+            ..handshake.clone()
+        };
+        assert!(handshake.check(&ffa).is_err());
+        // clients from before free-for-alls play versus
+        let json = serde_json::to_value(&handshake).unwrap();
+        let mut old = json.as_object().unwrap().clone();
+        old.remove("players");
+        let old: Handshake = serde_json::from_value(old.into()).unwrap();
+        assert_eq!(old.players, 2);
+    }
+
+    #[test]
+    fn test_targeting() {
+        // the second opponent is out
+        let rows = [Some(3), None, Some(9), Some(3)];
+        assert_eq!(Targeting::MostLines.pick(&rows, None, 0, 7), Some(2));
+        // ties are settled by the roll
+        assert_eq!(Targeting::LeastLines.pick(&rows, None, 0, 0), Some(0));
+        assert_eq!(Targeting::LeastLines.pick(&rows, None, 0, 1), Some(3));
+        assert_eq!(Targeting::Attacker.pick(&rows, Some(3), 0, 0), Some(3));
+        assert_eq!(Targeting::Manual.pick(&rows, None, 2, 0), Some(2));
+        // falling back to a random opponent still playing
+        assert_eq!(Targeting::Attacker.pick(&rows, None, 0, 1), Some(2));
+        assert_eq!(Targeting::Manual.pick(&rows, None, 1, 1), Some(2));
+        assert_eq!(Targeting::Random.pick(&rows, None, 0, 2), Some(3));
+        assert_eq!(Targeting::Random.pick(&[None, None], None, 0, 2), None);
+
+        let board = ["....".to_owned(), ".#..".to_owned(), "###.".to_owned()];
+        assert_eq!(filled_rows(&board), 2);
+    }
+
+    #[test]
+    fn test_recv_invalid() {
+        let mut reader = Cursor::new(b"not json\n".to_vec());
+        assert!(recv(&mut reader).is_err());
+    }
+}
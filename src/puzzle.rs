@@ -0,0 +1,322 @@
+use crate::block::PieceKind;
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+
+/// Lines cleared when a block locks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Clear {
+    pub lines: usize,
+    /// The block was a T rotated into a spot with three corners filled.
+    pub tspin: bool,
+    /// The block was another piece rotated into a spot it can't move out of,
+    /// which only counts under all-spin rules.
+    pub spin: bool,
+    /// The board is empty afterwards.
+    pub perfect: bool,
+}
+
+/// What has to be done to solve a puzzle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Goal {
+    /// Clear this many lines in total.
+    Lines(usize),
+    /// Clear this many lines at once with a T-spin.
+    TSpin(usize),
+    /// Leave the board empty.
+    PerfectClear,
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Goal::Lines(1) => write!(f, "clear 1 line"),
+            Goal::Lines(n) => write!(f, "clear {n} lines"),
+            Goal::TSpin(n) => {
+                let name = ["zero", "single", "double", "triple"]
+                    .get(*n)
+                    .copied()
+                    .unwrap_or("spin");
+                write!(f, "T-spin {name}")
+            }
+            Goal::PerfectClear => write!(f, "perfect clear"),
+        }
+    }
+}
+
+/// A preset board, the pieces to place on it and the goal to reach.
+///
+/// Scenario files look like this, with the board aligned to the bottom:
+///
+/// ```text
+/// # comment
+/// goal: tspin 2
+/// queue: T
+/// board:
+/// ..X.......
+/// XX...XXXXX
+/// XXX.XXXXXX
+/// ```
+///
+/// Goals are `lines N`, `tspin N` and `perfect-clear`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+    pub goal: Goal,
+    pub queue: Vec<PieceKind>,
+    /// Rows from top to bottom, `true` for filled cells.
+    pub board: Vec<Vec<bool>>,
+}
+
+impl Scenario {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        text.parse()
+            .with_context(|| format!("invalid scenario {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_string())
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+impl Default for Scenario {
+    /// Clearing a line with one of each piece on an empty board.
+    fn default() -> Self {
+        Self {
+            goal: Goal::Lines(1),
+            queue: PieceKind::ALL.to_vec(),
+            board: Vec::new(),
+        }
+    }
+}
+
+/// Writes the scenario file format read by [`Scenario::from_str`].
+///
+/// [`Scenario::from_str`]: std::str::FromStr::from_str
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.goal {
+            Goal::Lines(n) => writeln!(f, "goal: lines {n}")?,
+            Goal::TSpin(n) => writeln!(f, "goal: tspin {n}")?,
+            Goal::PerfectClear => writeln!(f, "goal: perfect-clear")?,
+        }
+        let queue: String = self.queue.iter().map(|kind| kind.name()).collect();
+        writeln!(f, "queue: {queue}")?;
+        writeln!(f, "board:")?;
+        for row in &self.board {
+            let row: String = row.iter().map(|&c| if c { 'X' } else { '.' }).collect();
+            writeln!(f, "{row}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Scenario {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut goal = None;
+        let mut queue = None;
+        let mut lines = s.lines();
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("expected `key: value`, got {line:?}"))?;
+            match key.trim() {
+                "goal" => goal = Some(parse_goal(value.trim())?),
+                "queue" => queue = Some(parse_queue(value)?),
+                "board" => break,
+                key => bail!("unknown key {key:?}"),
+            }
+        }
+
+        let board = lines
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.chars().map(|c| c != '.').collect())
+            .collect();
+
+        Ok(Self {
+            goal: goal.ok_or_else(|| anyhow!("missing goal"))?,
+            queue: queue.ok_or_else(|| anyhow!("missing queue"))?,
+            board,
+        })
+    }
+}
+
+fn parse_goal(s: &str) -> Result<Goal> {
+    let parse_count = |n: &str| {
+        n.trim()
+            .parse()
+            .with_context(|| format!("invalid line count {n:?}"))
+    };
+
+    Ok(match s.split_once(' ') {
+        Some(("lines", n)) => Goal::Lines(parse_count(n)?),
+        Some(("tspin", n)) => Goal::TSpin(parse_count(n)?),
+        None if s == "perfect-clear" => Goal::PerfectClear,
+        _ => bail!("unknown goal {s:?}"),
+    })
+}
+
+pub(crate) fn parse_queue(s: &str) -> Result<Vec<PieceKind>> {
+    s.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| PieceKind::from_name(c).ok_or_else(|| anyhow!("unknown piece {c:?}")))
+        .collect()
+}
+
+/// Progress through a scenario.
+#[derive(Debug)]
+pub struct Puzzle {
+    goal: Goal,
+    queue: VecDeque<PieceKind>,
+    lines: usize,
+    solved: Option<bool>,
+}
+
+impl Puzzle {
+    pub fn new(scenario: &Scenario) -> Self {
+        Self {
+            goal: scenario.goal,
+            queue: scenario.queue.iter().copied().collect(),
+            lines: 0,
+            solved: None,
+        }
+    }
+
+    pub fn goal(&self) -> Goal {
+        self.goal
+    }
+
+    /// `Some(true)` once solved, `Some(false)` once the pieces ran out.
+    pub fn solved(&self) -> Option<bool> {
+        self.solved
+    }
+
+    pub fn queue(&self) -> impl Iterator<Item = PieceKind> + '_ {
+        self.queue.iter().copied()
+    }
+
+    /// Takes the next piece to spawn, if the puzzle isn't over.
+    pub fn next_shape(&mut self) -> Option<PieceKind> {
+        if self.solved.is_some() {
+            return None;
+        }
+        self.queue.pop_front()
+    }
+
+    /// Evaluates the goal after a block locked with `clear`.
+    pub fn on_lock(&mut self, clear: &Clear) {
+        if self.solved.is_some() {
+            return;
+        }
+
+        self.lines += clear.lines;
+        let met = match self.goal {
+            Goal::Lines(n) => self.lines >= n,
+            Goal::TSpin(n) => clear.tspin && clear.lines == n,
+            Goal::PerfectClear => clear.perfect,
+        };
+        if met {
+            self.solved = Some(true);
+        } else if self.queue.is_empty() {
+            self.solved = Some(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TSD: &str = "
+        # T-spin double
+        goal: tspin 2
+        queue: T
+
+        board:
+        ..X.......
+        XX...XXXXX
+        XXX.XXXXXX
+    ";
+
+    #[test]
+    fn test_parse_scenario() {
+        let scenario: Scenario = TSD.parse().unwrap();
+        assert_eq!(scenario.goal, Goal::TSpin(2));
+        assert_eq!(scenario.queue, vec![PieceKind::T]);
+        assert_eq!(scenario.board.len(), 3);
+        assert_eq!(
+            scenario.board[1],
+            vec![true, true, false, false, false, true, true, true, true, true]
+        );
+
+        let scenario: Scenario = "goal: perfect-clear\nqueue: I O I\nboard:\n"
+            .parse()
+            .unwrap();
+        assert_eq!(scenario.goal, Goal::PerfectClear);
+        assert_eq!(
+            scenario.queue,
+            vec![PieceKind::I, PieceKind::O, PieceKind::I]
+        );
+        assert!(scenario.board.is_empty());
+    }
+
+    #[test]
+    fn test_scenario_display() {
+        let scenario: Scenario = TSD.parse().unwrap();
+        assert_eq!(scenario.to_string().parse::<Scenario>().unwrap(), scenario);
+        assert_eq!(
+            Scenario::default().to_string(),
+            "goal: lines 1\nqueue: IOTJLSZ\nboard:\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!("queue: T\nboard:\n".parse::<Scenario>().is_err());
+        assert!("goal: lines 2\nboard:\n".parse::<Scenario>().is_err());
+        assert!("goal: lines x\nqueue: T\n".parse::<Scenario>().is_err());
+        assert!("goal: win\nqueue: T\n".parse::<Scenario>().is_err());
+        assert!("goal: lines 1\nqueue: Q\n".parse::<Scenario>().is_err());
+    }
+
+    #[test]
+    fn test_puzzle_goal() {
+        let mut puzzle = Puzzle::new(&"goal: lines 3\nqueue: IIO\n".parse().unwrap());
+        assert_eq!(puzzle.next_shape(), Some(PieceKind::I));
+        puzzle.on_lock(&Clear {
+            lines: 2,
+            ..Default::default()
+        });
+        assert_eq!(puzzle.solved(), None);
+        assert_eq!(puzzle.next_shape(), Some(PieceKind::I));
+        puzzle.on_lock(&Clear {
+            lines: 1,
+            ..Default::default()
+        });
+        assert_eq!(puzzle.solved(), Some(true));
+        assert_eq!(puzzle.next_shape(), None);
+
+        let mut puzzle = Puzzle::new(&TSD.parse().unwrap());
+        assert_eq!(puzzle.next_shape(), Some(PieceKind::T));
+        puzzle.on_lock(&Clear {
+            lines: 2,
+            tspin: false,
+            spin: true,
+            perfect: false,
+        });
+        assert_eq!(puzzle.solved(), Some(false));
+    }
+}
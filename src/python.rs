@@ -0,0 +1,99 @@
+use crate::headless;
+use crate::tetris::{Action, Mode, Tetris};
+use clap::ValueEnum;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList, PyString};
+use serde::Serialize;
+use serde_json::Value;
+
+/// A game played without a terminal, as in `--headless`: time doesn't
+/// pass, so gravity only moves the piece on a `tick`.
+#[pyclass(unsendable)]
+pub struct Game {
+    tetris: Tetris,
+}
+
+#[pymethods]
+impl Game {
+    /// A new game of `mode` on the default settings, with pieces from
+    /// `seed` if given.
+    #[new]
+    #[pyo3(signature = (mode = "marathon", seed = None))]
+    fn new(mode: &str, seed: Option<u64>) -> PyResult<Self> {
+        let mode = Mode::from_str(mode, true).map_err(PyValueError::new_err)?;
+        if !headless::playable(mode) {
+            return Err(PyValueError::new_err(format!(
+                "{} games can't be played headless",
+                mode.name()
+            )));
+        }
+        let mut tetris = Tetris::default();
+        if let Some(seed) = seed {
+            tetris = tetris.with_seed(seed);
+        }
+        let mut tetris = tetris.with_mode(mode);
+        headless::start(&mut tetris);
+        Ok(Self { tetris })
+    }
+
+    /// Applies the action named `action`, like `"left"` or `"drop"`,
+    /// returning the events it led to as dicts.
+    fn apply<'py>(&mut self, py: Python<'py>, action: &str) -> PyResult<Bound<'py, PyAny>> {
+        let action: Action = serde_json::from_value(Value::String(action.to_owned()))
+            .map_err(|_| PyValueError::new_err(format!("unknown action {action:?}")))?;
+        if self.tetris.game_over().is_some() {
+            return Err(PyRuntimeError::new_err("the game is over"));
+        }
+        to_python(py, &headless::step(&mut self.tetris, action))
+    }
+
+    /// The board, pieces and score as a dict.
+    fn state<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &self.tetris.headless_state())
+    }
+
+    /// Why the game ended, or `None` while it goes on.
+    #[getter]
+    fn game_over(&self) -> Option<String> {
+        self.tetris.game_over().map(|reason| reason.to_string())
+    }
+}
+
+/// `value` as the Python object its JSON would load as.
+fn to_python<'py>(py: Python<'py>, value: &impl Serialize) -> PyResult<Bound<'py, PyAny>> {
+    let value =
+        serde_json::to_value(value).map_err(|err| PyValueError::new_err(err.to_string()))?;
+    json_to_python(py, &value)
+}
+
+fn json_to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+        Value::Number(n) => match n.as_i64() {
+            Some(n) => n.into_pyobject(py)?.into_any(),
+            None => n.as_f64().unwrap_or_default().into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => PyString::new(py, s).into_any(),
+        Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| json_to_python(py, item))
+                .collect::<PyResult<Vec<_>>>()?;
+            PyList::new(py, items)?.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, field) in fields {
+                dict.set_item(key, json_to_python(py, field)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+#[pymodule]
+fn tetris_rust(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Game>()
+}
@@ -0,0 +1,52 @@
+/// Rating of a player yet to finish a match.
+pub const INITIAL_RATING: i32 = 1500;
+/// Most a rating moves after a single match.
+const K: f64 = 32.0;
+/// Rating points a row of handicap garbage makes up for.
+const POINTS_PER_ROW: i32 = 100;
+/// Most rows of handicap garbage suggested.
+pub const MAX_HANDICAP: usize = 8;
+
+/// Chance that a player rated `rating` beats one rated `opponent`, by the
+/// Elo formula.
+pub fn expected(rating: i32, opponent: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf(f64::from(opponent - rating) / 400.0))
+}
+
+/// New ratings of a player and their opponent after a match the player
+/// `won` or lost.
+pub fn update(rating: i32, opponent: i32, won: bool) -> (i32, i32) {
+    let score = if won { 1.0 } else { 0.0 };
+    let change = (K * (score - expected(rating, opponent))).round() as i32;
+    (rating + change, opponent - change)
+}
+
+/// Rows of garbage the player rated `stronger` could start with to make a
+/// match against one rated `weaker` even.
+pub fn handicap(stronger: i32, weaker: i32) -> usize {
+    ((stronger - weaker).max(0) / POINTS_PER_ROW).min(MAX_HANDICAP as i32) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rating() {
+        assert_eq!(expected(1500, 1500), 0.5);
+        assert!(expected(1700, 1500) > 0.75);
+
+        assert_eq!(update(1500, 1500, true), (1516, 1484));
+        assert_eq!(update(1500, 1500, false), (1484, 1516));
+        // beating a much stronger player moves ratings more
+        let (upset, _) = update(1300, 1700, true);
+        assert!(upset - 1300 > 16);
+        let (expected_win, _) = update(1700, 1300, true);
+        assert!(expected_win - 1700 < 16);
+
+        assert_eq!(handicap(1500, 1500), 0);
+        assert_eq!(handicap(1750, 1500), 2);
+        assert_eq!(handicap(1500, 1750), 0);
+        assert_eq!(handicap(3000, 1000), MAX_HANDICAP);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    config::{Config, HoldMode, LockResets, Randomizer, Rotation, Scoring},
+    tetris::{Action, Mode, SPRINT_LINES},
+};
+use anyhow::{bail, Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+/// Newest replay format this build reads and the one it writes.
+pub const REPLAY_VERSION: u32 = 5;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Everything needed to set up the game a replay was recorded from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    pub version: u32,
+    pub ruleset: String,
+    pub seed: u64,
+    pub width: usize,
+    pub height: usize,
+    /// Milliseconds of lock delay of the recorded game, since v5.
+    #[serde(default)]
+    pub lock_delay: u64,
+    /// Lock delay resets of the recorded game, since v2.
+    #[serde(default)]
+    pub lock_resets: LockResets,
+    #[serde(default)]
+    pub hold: HoldMode,
+    #[serde(default)]
+    pub line_clear_delay: u64,
+    #[serde(default)]
+    pub zone: bool,
+    #[serde(default = "first_level")]
+    pub start_level: usize,
+    #[serde(default)]
+    pub scoring: Scoring,
+    #[serde(default)]
+    pub randomizer: Randomizer,
+    #[serde(default)]
+    pub rotation: Rotation,
+    #[serde(default)]
+    pub mode: Mode,
+    /// Lines to clear in a sprint.
+    #[serde(default = "sprint_lines")]
+    pub line_goal: usize,
+    /// How messy the cheese of a dig is.
+    #[serde(default = "default_messiness")]
+    pub messiness: u32,
+}
+
+fn first_level() -> usize {
+    1
+}
+
+fn sprint_lines() -> usize {
+    SPRINT_LINES
+}
+
+fn default_messiness() -> u32 {
+    Config::default().messiness
+}
+
+/// An action applied `time` milliseconds after the game started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Input {
+    pub time: u64,
+    pub action: Action,
+}
+
+/// A recorded game: its header followed by every input in order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    pub header: Header,
+    pub inputs: Vec<Input>,
+}
+
+impl Replay {
+    /// Loads a replay, transparently decompressing gzipped files.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut data = Vec::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut data))
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::from_bytes(&data)
+    }
+
+    /// Saves the replay, gzipping it if the file name ends in `.gz`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let compress = path.extension().is_some_and(|ext| ext == "gz");
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        self.write(BufWriter::new(file), compress)
+    }
+
+    pub fn write(&self, writer: impl Write, compress: bool) -> Result<()> {
+        if compress {
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            serde_json::to_writer(&mut encoder, self)?;
+            encoder.finish()?.flush()?;
+        } else {
+            let mut writer = writer;
+            serde_json::to_writer(&mut writer, self)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.starts_with(&GZIP_MAGIC) {
+            let mut json = Vec::new();
+            GzDecoder::new(data).read_to_end(&mut json)?;
+            return Self::from_json(&json);
+        }
+        Self::from_json(data)
+    }
+
+    fn from_json(json: &[u8]) -> Result<Self> {
+        // check the version before anything else, a newer body may not parse
+        #[derive(Deserialize)]
+        struct Probe {
+            header: Version,
+        }
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+
+        let probe: Probe = serde_json::from_slice(json).context("not a replay file")?;
+        if probe.header.version > REPLAY_VERSION {
+            bail!(
+                "replay format v{} is newer than this build supports (v{})",
+                probe.header.version,
+                REPLAY_VERSION
+            );
+        }
+
+        Ok(serde_json::from_slice(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> Replay {
+        Replay {
+            header: Header {
+                version: REPLAY_VERSION,
+                ruleset: "classic".into(),
+                seed: 42,
+                width: 10,
+                height: 20,
+                lock_delay: 500,
+                lock_resets: LockResets::Infinite,
+                hold: HoldMode::Unlimited,
+                line_clear_delay: 300,
+                zone: true,
+                start_level: 3,
+                scoring: Scoring::Classic,
+                randomizer: Randomizer::Bag,
+                rotation: Rotation::Srs,
+                mode: Mode::Sprint,
+                line_goal: 20,
+                messiness: 50,
+            },
+            inputs: vec![
+                Input {
+                    time: 800,
+                    action: Action::Tick,
+                },
+                Input {
+                    time: 950,
+                    action: Action::Left,
+                },
+                Input {
+                    time: 1010,
+                    action: Action::Drop,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_replay_roundtrip() {
+        for compress in [false, true] {
+            let mut buf = Vec::new();
+            replay().write(&mut buf, compress).unwrap();
+            assert_eq!(buf.starts_with(&GZIP_MAGIC), compress);
+            assert_eq!(Replay::from_bytes(&buf).unwrap(), replay());
+        }
+    }
+
+    #[test]
+    fn test_replay_newer_version() {
+        let mut newer = replay();
+        newer.header.version = REPLAY_VERSION + 1;
+        let mut buf = Vec::new();
+        newer.write(&mut buf, false).unwrap();
+
+        let err = Replay::from_bytes(&buf).unwrap_err();
+        assert!(err.to_string().contains("newer"));
+
+        // fields unknown to this build must not hide the version error
+        let json = r#"{"header":{"version":99,"frames":1},"events":[]}"#;
+        assert!(Replay::from_bytes(json.as_bytes())
+            .unwrap_err()
+            .to_string()
+            .contains("newer"));
+    }
+
+    #[test]
+    fn test_replay_invalid() {
+        assert!(Replay::from_bytes(b"garbage").is_err());
+    }
+}
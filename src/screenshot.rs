@@ -0,0 +1,287 @@
+use anyhow::{bail, Context, Result};
+use flate2::{write::ZlibEncoder, Compression, Crc};
+use std::collections::{hash_map::Entry, HashMap};
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::Path;
+use std::time::Duration;
+
+/// Colors of the cells of a board, from the top row down, `None` for empty
+/// cells.
+pub type Cells = Vec<Vec<Option<[u8; 3]>>>;
+
+/// Pixels per cell, grid line included.
+pub const CELL_SIZE: usize = 16;
+const BACKGROUND: [u8; 3] = [16, 16, 16];
+const GRID: [u8; 3] = [40, 40, 40];
+
+/// An RGB image, rows from the top down.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// Three bytes per pixel.
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Draws `cells` as a grid of [`CELL_SIZE`] pixel squares.
+    pub fn new(cells: &Cells) -> Self {
+        let columns = cells.first().map_or(0, Vec::len);
+        let (width, height) = (columns * CELL_SIZE + 1, cells.len() * CELL_SIZE + 1);
+        let mut image = Self {
+            width,
+            height,
+            pixels: BACKGROUND.repeat(width * height),
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let (col, row) = (x / CELL_SIZE, y / CELL_SIZE);
+                let color = if x % CELL_SIZE == 0 || y % CELL_SIZE == 0 {
+                    Some(GRID)
+                } else {
+                    cells[row][col]
+                };
+                if let Some(color) = color {
+                    image.set(x, y, color);
+                }
+            }
+        }
+        image
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        let i = 3 * (y * self.width + x);
+        self.pixels[i..i + 3].copy_from_slice(&color);
+    }
+
+    /// Encodes the image as a PNG file.
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filter and no
+        // interlacing
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in self.pixels.chunks(3 * self.width) {
+            // every row starts with its filter, none here
+            encoder.write_all(&[0])?;
+            encoder.write_all(row)?;
+        }
+        let idat = encoder.finish()?;
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut png, b"IHDR", &ihdr);
+        chunk(&mut png, b"IDAT", &idat);
+        chunk(&mut png, b"IEND", &[]);
+        Ok(png)
+    }
+}
+
+/// Appends a chunk of type `kind` holding `data` to `png`.
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+/// Draws `cells` as an SVG grid of [`CELL_SIZE`] unit squares.
+pub fn to_svg(cells: &Cells) -> String {
+    let columns = cells.first().map_or(0, Vec::len);
+    let (width, height) = (columns * CELL_SIZE, cells.len() * CELL_SIZE);
+    let hex = |[r, g, b]: [u8; 3]| format!("#{r:02x}{g:02x}{b:02x}");
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">\n"
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"{width}\" height=\"{height}\" fill=\"{}\" stroke=\"{}\"/>",
+        hex(BACKGROUND),
+        hex(GRID)
+    );
+    for (y, row) in cells.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if let Some(color) = cell {
+                let _ = writeln!(
+                    svg,
+                    "<rect x=\"{}\" y=\"{}\" width=\"{CELL_SIZE}\" height=\"{CELL_SIZE}\" \
+                     fill=\"{}\" stroke=\"{}\"/>",
+                    x * CELL_SIZE,
+                    y * CELL_SIZE,
+                    hex(*color),
+                    hex(GRID)
+                );
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Encodes `frames` of a board, each shown for as long as given, as an
+/// animated GIF looping forever.
+pub fn to_gif(frames: &[(Cells, Duration)]) -> Result<Vec<u8>> {
+    let images: Vec<_> = frames.iter().map(|(cells, _)| Image::new(cells)).collect();
+    let Some(first) = images.first() else {
+        bail!("no frames");
+    };
+
+    // boards have few enough colors to share one palette
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut indices = HashMap::new();
+    for image in &images {
+        for pixel in image.pixels.chunks(3) {
+            let color = [pixel[0], pixel[1], pixel[2]];
+            if let Entry::Vacant(entry) = indices.entry(color) {
+                if palette.len() == 256 {
+                    bail!("more than 256 colors");
+                }
+                entry.insert(palette.len() as u8);
+                palette.push(color);
+            }
+        }
+    }
+
+    let mut gif = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(
+            &mut gif,
+            first.width as u16,
+            first.height as u16,
+            palette.as_flattened(),
+        )?;
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for (image, (_, shown)) in images.iter().zip(frames) {
+            let buffer: Vec<u8> = image
+                .pixels
+                .chunks(3)
+                .map(|pixel| indices[&[pixel[0], pixel[1], pixel[2]]])
+                .collect();
+            let mut frame = gif::Frame::from_indexed_pixels(
+                image.width as u16,
+                image.height as u16,
+                buffer,
+                None,
+            );
+            // in hundredths of a second
+            frame.delay = (shown.as_millis() / 10).min(u16::MAX.into()) as u16;
+            encoder.write_frame(&frame)?;
+        }
+    }
+    Ok(gif)
+}
+
+/// Saves `frames` to `path` as an animated GIF.
+pub fn save_gif(frames: &[(Cells, Duration)], path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let data = to_gif(frames)?;
+    std::fs::write(path, data).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Saves `cells` to `path`, as an SVG if it ends in `.svg` and as a PNG
+/// otherwise.
+pub fn save(cells: &Cells, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let data = if path.extension().is_some_and(|ext| ext == "svg") {
+        to_svg(cells).into_bytes()
+    } else {
+        Image::new(cells).to_png()?
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+    }
+    std::fs::write(path, data).with_context(|| format!("failed to write {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    const RED: [u8; 3] = [255, 0, 0];
+
+    fn cells() -> Cells {
+        vec![vec![None, None, None], vec![Some(RED), None, Some(RED)]]
+    }
+
+    #[test]
+    fn test_image() {
+        let image = Image::new(&cells());
+        assert_eq!((image.width, image.height), (49, 33));
+        let pixel = |x: usize, y: usize| {
+            let i = 3 * (y * image.width + x);
+            [image.pixels[i], image.pixels[i + 1], image.pixels[i + 2]]
+        };
+        assert_eq!(pixel(0, 0), GRID);
+        assert_eq!(pixel(5, 5), BACKGROUND);
+        assert_eq!(pixel(5, 20), RED);
+        assert_eq!(pixel(16, 20), GRID);
+        assert_eq!(pixel(20, 20), BACKGROUND);
+        assert_eq!(pixel(40, 20), RED);
+    }
+
+    #[test]
+    fn test_png() {
+        let image = Image::new(&cells());
+        let png = image.to_png().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[16..20], &49u32.to_be_bytes());
+        assert_eq!(&png[20..24], &33u32.to_be_bytes());
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+
+        // the pixels come back out of the IDAT chunk, each row after its
+        // filter byte
+        let start = 8 + 12 + 13;
+        let len = u32::from_be_bytes(png[start..start + 4].try_into().unwrap()) as usize;
+        assert_eq!(&png[start + 4..start + 8], b"IDAT");
+        let mut raw = Vec::new();
+        ZlibDecoder::new(&png[start + 8..start + 8 + len])
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert_eq!(raw.len(), image.height * (1 + 3 * image.width));
+        let row = 20 * (1 + 3 * image.width);
+        assert_eq!(raw[row], 0);
+        assert_eq!(&raw[row + 1 + 3 * 5..row + 1 + 3 * 6], &RED);
+    }
+
+    #[test]
+    fn test_gif() {
+        let mut moved = cells();
+        moved[0][1] = Some([0, 0, 255]);
+        let frames = [
+            (cells(), Duration::from_millis(100)),
+            (moved, Duration::from_secs(2)),
+        ];
+        let gif = to_gif(&frames).unwrap();
+
+        let mut decoder = gif::DecodeOptions::new();
+        decoder.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = decoder.read_info(&gif[..]).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (49, 33));
+        assert_eq!(decoder.read_next_frame().unwrap().unwrap().delay, 10);
+        assert_eq!(decoder.read_next_frame().unwrap().unwrap().delay, 200);
+        assert!(decoder.read_next_frame().unwrap().is_none());
+
+        assert!(to_gif(&[]).is_err());
+    }
+
+    #[test]
+    fn test_svg() {
+        let svg = to_svg(&cells());
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("width=\"48\" height=\"32\""));
+        assert_eq!(svg.matches("fill=\"#ff0000\"").count(), 2);
+        assert!(svg.contains("<rect x=\"32\" y=\"16\""));
+    }
+}
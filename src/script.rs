@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Context, Result};
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Map, Scope, AST, INT};
+use std::path::Path;
+
+/// Most operations a hook may take, so a script stuck in a loop can't hang
+/// the game.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// A script in [Rhai](https://rhai.rs) hooking into the events of a game.
+///
+/// It can define `on_spawn(piece)`, called with the letter of each piece
+/// before it spawns, `on_clear(lines)`, called as lines are cleared, and
+/// `on_tick()`, called on every gravity tick. Each sees the game as `this`:
+/// `this.score`, `this.gravity` in milliseconds between ticks and
+/// `this.garbage`, rows of garbage to push up after the next lock, can be
+/// changed, while `this.lines` and `this.level` are only read.
+#[derive(Debug)]
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+/// What a script sees of a game, and changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Hooked {
+    pub score: i32,
+    pub lines: usize,
+    pub level: usize,
+    /// Milliseconds between gravity ticks.
+    pub gravity: u64,
+    /// Rows of garbage to rise after the next lock.
+    pub garbage: usize,
+}
+
+impl Script {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::compile(&source).with_context(|| format!("failed to compile {}", path.display()))
+    }
+
+    pub fn compile(source: &str) -> Result<Self> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = engine.compile(source)?;
+        Ok(Self { engine, ast })
+    }
+
+    pub fn on_spawn(&self, game: &mut Hooked, piece: char) -> Result<()> {
+        self.call(game, "on_spawn", (piece,))
+    }
+
+    pub fn on_clear(&self, game: &mut Hooked, lines: usize) -> Result<()> {
+        self.call(game, "on_clear", (lines as INT,))
+    }
+
+    pub fn on_tick(&self, game: &mut Hooked) -> Result<()> {
+        self.call(game, "on_tick", ())
+    }
+
+    /// Calls the hook `name` with `args`, if the script defines it, with
+    /// `game` as `this`, keeping what it changed.
+    fn call(&self, game: &mut Hooked, name: &str, args: impl FuncArgs) -> Result<()> {
+        let mut params = Vec::new();
+        args.parse(&mut params);
+        if !self
+            .ast
+            .iter_functions()
+            .any(|f| f.name == name && f.params.len() == params.len())
+        {
+            return Ok(());
+        }
+
+        let mut this = Map::new();
+        this.insert("score".into(), INT::from(game.score).into());
+        this.insert("lines".into(), (game.lines as INT).into());
+        this.insert("level".into(), (game.level as INT).into());
+        this.insert("gravity".into(), (game.gravity as INT).into());
+        this.insert("garbage".into(), (game.garbage as INT).into());
+        let mut this = Dynamic::from_map(this);
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut this);
+        // whatever the hook returns is left alone
+        let _: Dynamic = self
+            .engine
+            .call_fn_with_options(options, &mut Scope::new(), &self.ast, name, params)
+            .map_err(|err| anyhow!("{name}: {err}"))?;
+
+        let this = this
+            .try_cast::<Map>()
+            .ok_or_else(|| anyhow!("{name}: `this` is no longer the game"))?;
+        let get = |field: &str| -> Result<INT> {
+            this.get(field)
+                .and_then(|value| value.as_int().ok())
+                .ok_or_else(|| anyhow!("{name}: this.{field} has to be a number"))
+        };
+        game.score = i32::try_from(get("score")?).context("score out of range")?;
+        game.gravity = get("gravity")?.max(0) as u64;
+        game.garbage = get("garbage")?.max(0) as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let script = Script::compile(
+            r#"
+            fn on_clear(lines) {
+                this.score += 1000 * lines;
+                this.garbage = lines;
+            }
+            fn on_spawn(piece) {
+                if piece == 'I' {
+                    this.gravity /= 2;
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let mut game = Hooked {
+            score: 100,
+            gravity: 1000,
+            ..Hooked::default()
+        };
+        script.on_clear(&mut game, 2).unwrap();
+        assert_eq!((game.score, game.garbage), (2100, 2));
+        script.on_spawn(&mut game, 'T').unwrap();
+        assert_eq!(game.gravity, 1000);
+        script.on_spawn(&mut game, 'I').unwrap();
+        assert_eq!(game.gravity, 500);
+        // hooks left out do nothing
+        script.on_tick(&mut game).unwrap();
+
+        let script = Script::compile("fn on_tick() { loop {} }").unwrap();
+        assert!(script.on_tick(&mut game).is_err());
+        let script = Script::compile("fn on_tick() { this.score = \"lots\"; }").unwrap();
+        assert!(script.on_tick(&mut game).is_err());
+        assert!(Script::compile("fn on_tick( {").is_err());
+    }
+}
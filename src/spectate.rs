@@ -0,0 +1,230 @@
+use crate::{net::Client, protocol::Message};
+use anyhow::{anyhow, bail, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Widget},
+    DefaultTerminal,
+};
+use std::time::Duration;
+
+/// How long to wait for a key before looking for news from the server.
+const POLL: Duration = Duration::from_millis(50);
+
+/// A match on `tetris-server` watched without playing, every player's board
+/// side by side.
+#[derive(Debug)]
+pub struct Spectator {
+    client: Client,
+    room: String,
+    watched: Watched,
+}
+
+/// What a spectator has seen of the match so far.
+#[derive(Debug, Default)]
+struct Watched {
+    players: Vec<Player>,
+    width: usize,
+    height: usize,
+}
+
+#[derive(Debug)]
+struct Player {
+    name: String,
+    rows: Vec<String>,
+    /// Whether they topped out or left.
+    out: bool,
+}
+
+impl Spectator {
+    pub fn new(client: Client, room: &str) -> Self {
+        Self {
+            client,
+            room: room.to_owned(),
+            watched: Watched::default(),
+        }
+    }
+
+    /// Shows the match as it goes until <Q> or <Esc> is pressed.
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| frame.render_widget(&*self, frame.area()))?;
+            loop {
+                match self.client.try_recv() {
+                    Ok(Some(msg)) => self.watched.handle(msg)?,
+                    Ok(None) => break,
+                    Err(_) if self.watched.winner().is_some() => break,
+                    Err(_) => bail!("lost connection to server"),
+                }
+            }
+
+            if !event::poll(POLL)? {
+                continue;
+            }
+            if let Event::Key(key_event) = event::read()? {
+                if key_event.kind == KeyEventKind::Press
+                    && matches!(key_event.code, KeyCode::Char('q') | KeyCode::Esc)
+                {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+impl Watched {
+    fn handle(&mut self, msg: Message) -> Result<()> {
+        match msg {
+            Message::Watching {
+                players,
+                width,
+                height,
+            } => {
+                self.players = players
+                    .into_iter()
+                    .map(|name| Player {
+                        name,
+                        rows: Vec::new(),
+                        out: false,
+                    })
+                    .collect();
+                self.width = width;
+                self.height = height;
+            }
+            Message::Board { rows, from } => {
+                if let Some(player) = self.players.get_mut(from) {
+                    player.rows = rows;
+                }
+            }
+            Message::Eliminated { from } => {
+                if let Some(player) = self.players.get_mut(from) {
+                    player.out = true;
+                }
+            }
+            Message::Error { reason } => return Err(anyhow!(reason)),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Name of the last one standing, once everyone else is out.
+    fn winner(&self) -> Option<&str> {
+        let mut playing = self.players.iter().filter(|p| !p.out);
+        match (playing.next(), playing.next()) {
+            (Some(winner), None) if self.players.len() > 1 => Some(&winner.name),
+            _ => None,
+        }
+    }
+}
+
+impl Widget for &Spectator {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let bottom = match self.watched.winner() {
+            Some(name) => Line::from(format!(" {name} wins ").bold()),
+            None => Line::from(vec![
+                " press ".into(),
+                "<Q>".blue().bold(),
+                " to quit ".into(),
+            ]),
+        };
+        let block = Block::bordered()
+            .title(Line::from(format!(" watching {} ", self.room).bold()).centered())
+            .title_bottom(bottom.centered())
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let watched = &self.watched;
+        if watched.players.is_empty() {
+            Paragraph::new("waiting for the match to start")
+                .centered()
+                .render(inner, buf);
+            return;
+        }
+
+        // two characters a cell, to look square
+        let width = watched.width as u16 * 2 + 2;
+        let height = watched.height as u16 + 2;
+        let [boards] = Layout::vertical([Constraint::Length(height)])
+            .flex(Flex::Center)
+            .areas(inner);
+        let areas = Layout::horizontal(watched.players.iter().map(|_| Constraint::Length(width)))
+            .flex(Flex::Center)
+            .split(boards);
+        for (player, &area) in watched.players.iter().zip(areas.iter()) {
+            let color = if player.out {
+                Color::DarkGray
+            } else {
+                Color::Gray
+            };
+            let rows: Vec<Line> = player
+                .rows
+                .iter()
+                .map(|row| {
+                    let cells: String = row
+                        .chars()
+                        .map(|c| if c == '.' { "  " } else { "██" })
+                        .collect();
+                    Line::from(cells)
+                })
+                .collect();
+            Paragraph::new(rows)
+                .block(
+                    Block::bordered()
+                        .title(Line::from(format!(" {} ", player.name).bold()).centered())
+                        .border_set(border::THICK),
+                )
+                .fg(color)
+                .render(area, buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watched() {
+        let mut watched = Watched::default();
+        watched
+            .handle(Message::Watching {
+                players: vec!["alice".into(), "bob".into(), "carol".into()],
+                width: 4,
+                height: 2,
+            })
+            .unwrap();
+        assert_eq!((watched.width, watched.height), (4, 2));
+        assert_eq!(watched.winner(), None);
+
+        let rows = vec!["....".to_owned(), "##.#".to_owned()];
+        watched
+            .handle(Message::Board {
+                rows: rows.clone(),
+                from: 1,
+            })
+            .unwrap();
+        assert_eq!(watched.players[1].rows, rows);
+        // players the spectator never heard of are left alone
+        watched
+            .handle(Message::Board {
+                rows: rows.clone(),
+                from: 7,
+            })
+            .unwrap();
+
+        watched.handle(Message::Eliminated { from: 0 }).unwrap();
+        assert_eq!(watched.winner(), None);
+        watched.handle(Message::Eliminated { from: 2 }).unwrap();
+        assert_eq!(watched.winner(), Some("bob"));
+
+        let error = Message::Error {
+            reason: "no such room".into(),
+        };
+        assert!(watched.handle(error).is_err());
+    }
+}
@@ -0,0 +1,719 @@
+use crate::{analysis::Analysis, clipboard, config, rating};
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table, Widget},
+    DefaultTerminal,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Summary of a finished game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameResult {
+    pub mode: String,
+    pub score: u64,
+    pub lines: u64,
+    pub tetrises: u64,
+    pub time: Duration,
+    /// Time taken to reach the goal of a mode raced against the clock.
+    pub goal_time: Option<Duration>,
+    /// Time at which each section of [`SECTION_LINES`] lines was completed.
+    pub splits: Vec<Duration>,
+    /// Where the pieces locked.
+    pub heatmap: Heatmap,
+    pub analysis: Analysis,
+    pub end: GameOver,
+    /// Seed of the pieces, to play the same ones again with `--seed`.
+    pub seed: u64,
+}
+
+/// How many cells of locked pieces went into each column and row of the
+/// board, to spot a stack leaning to one side.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Heatmap {
+    pub columns: Vec<u64>,
+    /// From top to bottom.
+    pub rows: Vec<u64>,
+}
+
+impl Heatmap {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            columns: vec![0; width],
+            rows: vec![0; height],
+        }
+    }
+
+    /// Counts the `cells` of a locked piece.
+    pub fn add(&mut self, cells: impl IntoIterator<Item = (usize, usize)>) {
+        for (x, y) in cells {
+            self.columns[x] += 1;
+            self.rows[y] += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.iter().all(|&n| n == 0)
+    }
+}
+
+/// Shades `counts` relative to the largest one, from `·` for none to `█`,
+/// `width` characters each.
+fn shades(counts: impl Iterator<Item = u64> + Clone, width: usize) -> String {
+    let max = counts.clone().max().unwrap_or(0).max(1);
+    counts
+        .map(|n| {
+            let shade = match (4 * n).div_ceil(max) {
+                0 => '·',
+                1 => '░',
+                2 => '▒',
+                3 => '▓',
+                _ => '█',
+            };
+            shade.to_string().repeat(width)
+        })
+        .collect()
+}
+
+/// Why a game ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOver {
+    /// The next piece had no room to spawn.
+    BlockOut,
+    /// Garbage pushed the stack out of the top of the board.
+    GarbageOut,
+    /// The opponent won the match.
+    Defeated,
+    /// The opponent topped out or left.
+    Won,
+    /// The goal of the mode or puzzle was reached.
+    Goal,
+    /// An ultra run's time ran out.
+    TimeUp,
+    /// A puzzle ran out of pieces before its goal was reached.
+    OutOfPieces,
+    /// The player quit.
+    Quit,
+}
+
+impl fmt::Display for GameOver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GameOver::BlockOut => "block out",
+            GameOver::GarbageOut => "garbage out",
+            GameOver::Defeated => "opponent won",
+            GameOver::Won => "won the match",
+            GameOver::Goal => "goal reached",
+            GameOver::TimeUp => "time up",
+            GameOver::OutOfPieces => "out of pieces",
+            GameOver::Quit => "quit",
+        })
+    }
+}
+
+/// Lines in a section of a marathon, i.e. 10 levels.
+pub const SECTION_LINES: usize = 100;
+
+/// Totals for a single mode.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModeStats {
+    pub games: u64,
+    pub best_score: u64,
+    /// Fastest time to the goal in milliseconds, for modes raced against
+    /// the clock.
+    pub best_time: Option<u64>,
+}
+
+impl ModeStats {
+    /// Whether `result` beats the best game so far: the best time for modes
+    /// raced against the clock, the best score otherwise.
+    pub fn beaten_by(&self, result: &GameResult) -> bool {
+        if self.games == 0 {
+            return false;
+        }
+        match result.goal_time {
+            Some(time) => self
+                .best_time
+                .is_none_or(|best| (time.as_millis() as u64) < best),
+            None => result.score > self.best_score,
+        }
+    }
+}
+
+/// A versus match played under a profile name.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Match {
+    pub player: String,
+    pub opponent: String,
+    pub won: bool,
+    /// Seconds since the Unix epoch when it ended.
+    pub at: u64,
+}
+
+/// Statistics accumulated over every game played.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub games: u64,
+    pub lines: u64,
+    pub tetrises: u64,
+    /// Seconds spent playing.
+    pub time: u64,
+    pub modes: BTreeMap<String, ModeStats>,
+    /// Every versus match finished, oldest first.
+    pub matches: Vec<Match>,
+    /// Elo ratings of the players of those matches, by name.
+    pub ratings: BTreeMap<String, i32>,
+}
+
+impl Stats {
+    /// Where statistics are kept: `stats.json` in the [`config::data_dir`].
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(config::data_dir()?.join("stats.json"))
+    }
+
+    /// Loads statistics, starting afresh if there are none yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let data =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_slice(&data).with_context(|| format!("invalid stats {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    pub fn record(&mut self, result: &GameResult) {
+        self.games += 1;
+        self.lines += result.lines;
+        self.tetrises += result.tetrises;
+        self.time += result.time.as_secs();
+
+        let mode = self.modes.entry(result.mode.clone()).or_default();
+        mode.games += 1;
+        mode.best_score = mode.best_score.max(result.score);
+        if let Some(time) = result.goal_time {
+            let time = time.as_millis() as u64;
+            mode.best_time = Some(mode.best_time.map_or(time, |best| best.min(time)));
+        }
+    }
+
+    /// Records a versus match, updating the ratings of both players. Both
+    /// going by the same name, as they do unless `--name` is given, the
+    /// match is left out rather than rating the player against themselves.
+    pub fn record_match(&mut self, player: &str, opponent: &str, won: bool) {
+        if player == opponent {
+            return;
+        }
+        let rating = self.rating(player);
+        let (rating, opponent_rating) = rating::update(rating, self.rating(opponent), won);
+        self.ratings.insert(player.to_owned(), rating);
+        self.ratings.insert(opponent.to_owned(), opponent_rating);
+        self.matches.push(Match {
+            player: player.to_owned(),
+            opponent: opponent.to_owned(),
+            won,
+            at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+        });
+    }
+
+    /// Rating of the player named `name`, the initial one if they haven't
+    /// finished a match yet.
+    pub fn rating(&self, name: &str) -> i32 {
+        self.ratings
+            .get(name)
+            .copied()
+            .unwrap_or(rating::INITIAL_RATING)
+    }
+
+    /// Matches won and lost by the player named `name`.
+    pub fn record_of(&self, name: &str) -> (usize, usize) {
+        let mut record = (0, 0);
+        for m in &self.matches {
+            let won = if m.player == name {
+                m.won
+            } else if m.opponent == name {
+                !m.won
+            } else {
+                continue;
+            };
+            if won {
+                record.0 += 1;
+            } else {
+                record.1 += 1;
+            }
+        }
+        record
+    }
+
+    /// Shows the statistics screen until a key is pressed.
+    pub fn run(&self, terminal: &mut DefaultTerminal) -> Result<()> {
+        show(terminal, self, |_| Ok(()))
+    }
+}
+
+impl GameResult {
+    /// Shows the results screen until a key is pressed, copying the seed on
+    /// <Y> and the summary on <C>.
+    pub fn run(&self, terminal: &mut DefaultTerminal) -> Result<()> {
+        show(terminal, self, |code| match code {
+            KeyCode::Char('y') => clipboard::copy(&self.seed.to_string()),
+            KeyCode::Char('c') => clipboard::copy(&self.summary()),
+            _ => Ok(()),
+        })
+    }
+
+    /// The game in a line, to paste into a chat.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![self.mode.clone()];
+        match self.goal_time {
+            Some(time) => parts.push(format_lap(time)),
+            None => {
+                parts.push(format!("{} points", self.score));
+                parts.push(format_lap(self.time));
+            }
+        }
+        parts.push(format!("{} lines", self.lines));
+        if self.analysis.pieces > 0 {
+            parts.push(format!("{} pieces", self.analysis.pieces));
+        }
+        parts.push(format!("seed {}", self.seed));
+        parts.join(" — ")
+    }
+}
+
+/// Renders `widget` until <Q> or <Esc> is pressed, passing other keys to
+/// `on_key`.
+fn show(
+    terminal: &mut DefaultTerminal,
+    widget: impl Widget + Copy,
+    mut on_key: impl FnMut(KeyCode) -> Result<()>,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| frame.render_widget(widget, frame.area()))?;
+        if let Event::Key(key_event) = event::read()? {
+            if key_event.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key_event.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                code => on_key(code)?,
+            }
+        }
+    }
+}
+
+fn quit_hint() -> Line<'static> {
+    Line::from(vec![
+        " press ".into(),
+        "<Q>".blue().bold(),
+        " to quit ".into(),
+    ])
+    .centered()
+}
+
+fn total(name: &'static str, value: String) -> Line<'static> {
+    Line::from(vec![format!(" {name:<15}").into(), value.blue().bold()])
+}
+
+/// Formats `secs` as `h:mm:ss`.
+pub fn format_time(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Formats `time` as `m:ss.cc`, precise enough to compare runs.
+pub fn format_lap(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "{}:{:02}.{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+impl Widget for &Stats {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(Line::from(" statistics ".bold()).centered())
+            .title_bottom(quit_hint())
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        // a row per player and two for the header and gap
+        let ratings_height = match self.ratings.len() {
+            0 => 0,
+            players => players as u16 + 2,
+        };
+        let [totals, modes, ratings] = Layout::vertical([
+            Constraint::Length(5),
+            Constraint::Fill(1),
+            Constraint::Length(ratings_height),
+        ])
+        .areas(inner);
+
+        Paragraph::new(vec![
+            total("games played", self.games.to_string()),
+            total("lines cleared", self.lines.to_string()),
+            total("tetrises", self.tetrises.to_string()),
+            total("time played", format_time(self.time)),
+        ])
+        .render(totals, buf);
+
+        let rows = self.modes.iter().map(|(name, mode)| {
+            Row::new(vec![
+                format!(" {name}"),
+                mode.games.to_string(),
+                mode.best_score.to_string(),
+                mode.best_time
+                    .map_or("-".into(), |ms| format_lap(Duration::from_millis(ms))),
+            ])
+        });
+        Table::new(
+            rows,
+            [
+                Constraint::Length(16),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(10),
+            ],
+        )
+        .header(Row::new(vec![" mode", "games", "best", "best time"]).bold())
+        .render(modes, buf);
+
+        // handicaps are rows of garbage the best player would start with
+        // against each of the others
+        let best = self.ratings.values().copied().max().unwrap_or_default();
+        let mut players: Vec<_> = self.ratings.iter().collect();
+        players.sort_by_key(|&(_, &rating)| std::cmp::Reverse(rating));
+        let rows = players.into_iter().map(|(name, &rating)| {
+            let (won, lost) = self.record_of(name);
+            Row::new(vec![
+                format!(" {name}"),
+                rating.to_string(),
+                format!("{won}-{lost}"),
+                rating::handicap(best, rating).to_string(),
+            ])
+        });
+        Table::new(
+            rows,
+            [
+                Constraint::Length(16),
+                Constraint::Length(8),
+                Constraint::Length(8),
+                Constraint::Length(10),
+            ],
+        )
+        .header(
+            Row::new(vec![" player", "rating", "won-lost", "handicap"])
+                .bold()
+                .top_margin(1),
+        )
+        .render(ratings, buf);
+    }
+}
+
+impl Widget for &GameResult {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(Line::from(format!(" {} results ", self.mode).bold()).centered())
+            .title_bottom(quit_hint())
+            .title_bottom(
+                Line::from(vec![" <C>".blue().bold(), " to copy a summary ".into()])
+                    .right_aligned(),
+            )
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let mut lines = vec![
+            total("score", self.score.to_string()),
+            total("lines cleared", self.lines.to_string()),
+            total("tetrises", self.tetrises.to_string()),
+            total("time", format_lap(self.time)),
+            total("ended by", self.end.to_string()),
+        ];
+        let mut seed = total("seed", self.seed.to_string());
+        seed.push_span(", ");
+        seed.push_span("<Y>".blue().bold());
+        seed.push_span(" to copy");
+        lines.push(seed);
+        if !self.heatmap.is_empty() {
+            let columns = shades(self.heatmap.columns.iter().copied(), 2);
+            let rows = shades(self.heatmap.rows.iter().rev().copied(), 1);
+            lines.push(Line::default());
+            lines.push(total("columns", columns));
+            lines.push(total("rows, bottom up", rows));
+        }
+        let analysis = &self.analysis;
+        if analysis.pieces > 0 {
+            let clears = format!(
+                "{} / {} / {} / {}",
+                analysis.singles, analysis.doubles, analysis.triples, analysis.tetrises
+            );
+            lines.push(Line::default());
+            lines.push(total("clears 1/2/3/4", clears));
+            lines.push(total("T-spins", analysis.tspins.to_string()));
+            if analysis.perfect_clears > 0 {
+                lines.push(total("perfect clears", analysis.perfect_clears.to_string()));
+            }
+            lines.push(total("longest combo", analysis.longest_combo.to_string()));
+            lines.push(total(
+                "stack height",
+                format!("{:.1}", analysis.average_height()),
+            ));
+            lines.push(total("holes created", analysis.holes_created.to_string()));
+            let keys = format!("{} ({:.2} per piece)", analysis.keys, analysis.kpp());
+            lines.push(total("keys pressed", keys));
+            if analysis.finesse_pieces > 0 {
+                let finesse = format!(
+                    "{} ({:.1}%)",
+                    analysis.finesse_faults,
+                    analysis.finesse_rate()
+                );
+                lines.push(total("finesse faults", finesse));
+            }
+        }
+        let [totals, splits] = Layout::vertical([
+            Constraint::Length(lines.len() as u16 + 1),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
+        Paragraph::new(lines).render(totals, buf);
+
+        if self.splits.is_empty() {
+            return;
+        }
+        let rows = self.splits.iter().enumerate().map(|(i, &time)| {
+            let start = i.checked_sub(1).map_or(Duration::ZERO, |j| self.splits[j]);
+            Row::new(vec![
+                format!(" {}-{}", i * SECTION_LINES, (i + 1) * SECTION_LINES),
+                format_lap(time - start),
+                format_lap(time),
+            ])
+        });
+        Table::new(
+            rows,
+            [
+                Constraint::Length(16),
+                Constraint::Length(10),
+                Constraint::Length(10),
+            ],
+        )
+        .header(Row::new(vec![" section", "split", "total"]).bold())
+        .render(splits, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_record() {
+        let mut stats = Stats::default();
+        let result = GameResult {
+            mode: "marathon".to_owned(),
+            score: 12,
+            lines: 12,
+            tetrises: 2,
+            time: Duration::from_secs(90),
+            goal_time: None,
+            splits: Vec::new(),
+            heatmap: Heatmap::default(),
+            analysis: Analysis::default(),
+            end: GameOver::BlockOut,
+            seed: 0,
+        };
+        stats.record(&result);
+        stats.record(&GameResult {
+            score: 5,
+            lines: 5,
+            tetrises: 0,
+            ..result.clone()
+        });
+        stats.record(&GameResult {
+            mode: "puzzle".to_owned(),
+            ..result.clone()
+        });
+        for secs in [75, 60, 80] {
+            stats.record(&GameResult {
+                mode: "sprint".to_owned(),
+                goal_time: Some(Duration::from_secs(secs)),
+                ..result.clone()
+            });
+        }
+        stats.record(&GameResult {
+            mode: "sprint".to_owned(),
+            ..result
+        });
+
+        assert_eq!(stats.games, 7);
+        assert_eq!(stats.lines, 77);
+        assert_eq!(stats.tetrises, 12);
+        assert_eq!(stats.time, 630);
+        assert_eq!(
+            stats.modes["marathon"],
+            ModeStats {
+                games: 2,
+                best_score: 12,
+                best_time: None,
+            }
+        );
+        assert_eq!(stats.modes["puzzle"].games, 1);
+        assert_eq!(stats.modes["sprint"].best_time, Some(60_000));
+    }
+
+    #[test]
+    fn test_beaten_by() {
+        let result = GameResult {
+            mode: "sprint".to_owned(),
+            score: 40,
+            lines: 40,
+            tetrises: 10,
+            time: Duration::from_secs(70),
+            goal_time: Some(Duration::from_secs(60)),
+            splits: Vec::new(),
+            heatmap: Heatmap::default(),
+            analysis: Analysis::default(),
+            end: GameOver::Goal,
+            seed: 0,
+        };
+        let best = ModeStats {
+            games: 3,
+            best_score: 100,
+            best_time: Some(65_000),
+        };
+        assert!(best.beaten_by(&result));
+        assert!(!ModeStats::default().beaten_by(&result));
+        assert!(!ModeStats {
+            best_time: Some(60_000),
+            ..best.clone()
+        }
+        .beaten_by(&result));
+
+        // without a goal, the score counts
+        let marathon = GameResult {
+            goal_time: None,
+            ..result
+        };
+        assert!(!best.beaten_by(&marathon));
+        assert!(best.beaten_by(&GameResult {
+            score: 101,
+            ..marathon
+        }));
+    }
+
+    #[test]
+    fn test_record_match() {
+        let mut stats = Stats::default();
+        stats.record_match("alice", "bob", true);
+        assert_eq!(stats.rating("alice"), 1516);
+        assert_eq!(stats.rating("bob"), 1484);
+        assert_eq!(stats.rating("carol"), rating::INITIAL_RATING);
+
+        stats.record_match("bob", "alice", true);
+        stats.record_match("alice", "carol", false);
+        assert_eq!(stats.record_of("alice"), (1, 2));
+        assert_eq!(stats.record_of("bob"), (1, 1));
+        assert_eq!(stats.matches.len(), 3);
+        assert!(stats.matches[0].won);
+
+        stats.record_match("bob", "bob", true);
+        assert_eq!(stats.matches.len(), 3);
+        assert_eq!(stats.record_of("bob"), (1, 1));
+    }
+
+    #[test]
+    fn test_summary() {
+        let result = GameResult {
+            mode: "marathon".to_owned(),
+            score: 12,
+            lines: 12,
+            tetrises: 2,
+            time: Duration::from_secs(90),
+            goal_time: None,
+            splits: Vec::new(),
+            heatmap: Heatmap::default(),
+            analysis: Analysis::default(),
+            end: GameOver::BlockOut,
+            seed: 0,
+        };
+        assert_eq!(
+            result.summary(),
+            "marathon — 12 points — 1:30.00 — 12 lines — seed 0"
+        );
+        let sprint = GameResult {
+            mode: "sprint".to_owned(),
+            goal_time: Some(Duration::from_millis(83_450)),
+            analysis: Analysis {
+                pieces: 62,
+                ..Analysis::default()
+            },
+            seed: 42,
+            ..result
+        };
+        assert_eq!(
+            sprint.summary(),
+            "sprint — 1:23.45 — 12 lines — 62 pieces — seed 42"
+        );
+    }
+
+    #[test]
+    fn test_stats_save_load() {
+        let path = std::env::temp_dir().join(format!("tetris-stats-{}.json", std::process::id()));
+        assert_eq!(Stats::load(&path).unwrap(), Stats::default());
+
+        let mut stats = Stats {
+            games: 3,
+            ..Default::default()
+        };
+        stats.modes.insert("marathon".into(), ModeStats::default());
+        stats.save(&path).unwrap();
+        assert_eq!(Stats::load(&path).unwrap(), stats);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let mut heatmap = Heatmap::new(4, 3);
+        assert!(heatmap.is_empty());
+        // an I lying on the bottom, then an O on its left half
+        heatmap.add((0..4).map(|x| (x, 2)));
+        heatmap.add([(0, 0), (1, 0), (0, 1), (1, 1)]);
+        assert_eq!(heatmap.columns, [3, 3, 1, 1]);
+        assert_eq!(heatmap.rows, [2, 2, 4]);
+        assert_eq!(shades(heatmap.columns.iter().copied(), 2), "████▒▒▒▒");
+        assert_eq!(shades([0, 1, 8].into_iter(), 1), "·░█");
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "0:00:00");
+        assert_eq!(format_time(3723), "1:02:03");
+        assert_eq!(format_lap(Duration::from_millis(83_456)), "1:23.45");
+    }
+}
@@ -0,0 +1,415 @@
+use crate::block::{self, Block, PieceKind};
+use crate::board::{Board, Cell};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// Width of the boards of the Tetris Bot Protocol.
+pub const WIDTH: usize = 10;
+/// Rows of the boards of the Tetris Bot Protocol, counted from the bottom.
+pub const HEIGHT: usize = 40;
+
+/// Messages sent to a bot by the Tetris Bot Protocol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    /// The standard rules, answered with `Ready` if the bot plays by them.
+    Rules,
+    /// A position to think about, the board from the bottom row up.
+    Start {
+        hold: Option<char>,
+        queue: Vec<char>,
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<Vec<Option<char>>>,
+    },
+    /// Asks for the moves the bot thinks best so far.
+    Suggest,
+    /// Stops thinking about the position.
+    Stop,
+    Quit,
+}
+
+/// Messages sent by a bot by the Tetris Bot Protocol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+        #[serde(default)]
+        features: Vec<String>,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    /// Moves for the first piece of the queue, or for the held piece, from
+    /// the best.
+    Suggestion {
+        moves: Vec<Move>,
+    },
+    /// Messages of later versions of the protocol.
+    #[serde(other)]
+    Other,
+}
+
+/// A spot a bot suggests dropping a piece on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    pub location: Location,
+}
+
+/// Where a piece is, by its center, counting rows from the bottom.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    #[serde(rename = "type")]
+    pub kind: char,
+    pub orientation: Orientation,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Which way a piece points, turning clockwise from how it spawns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Orientation {
+    /// The state as the Super Rotation System counts them, which the Tetris
+    /// Bot Protocol follows.
+    pub fn state(self) -> block::Orientation {
+        match self {
+            Orientation::North => block::Orientation::Spawn,
+            Orientation::East => block::Orientation::Right,
+            Orientation::South => block::Orientation::Reverse,
+            Orientation::West => block::Orientation::Left,
+        }
+    }
+}
+
+impl Location {
+    /// Cells of the piece, counting rows from the bottom.
+    pub fn cells(&self) -> Result<[(i32, i32); 4]> {
+        let kind = PieceKind::from_name(self.kind)
+            .ok_or_else(|| anyhow!("unknown piece {:?}", self.kind))?;
+        let cells = match kind {
+            PieceKind::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            PieceKind::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            PieceKind::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            PieceKind::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            PieceKind::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            PieceKind::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            PieceKind::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        };
+        Ok(cells.map(|(x, y)| {
+            let (x, y) = match self.orientation {
+                Orientation::North => (x, y),
+                Orientation::East => (y, -x),
+                Orientation::South => (-x, -y),
+                Orientation::West => (-y, x),
+            };
+            (self.x + x, self.y + y)
+        }))
+    }
+}
+
+/// `board` as the rows of a [`FrontendMessage::Start`], with the piece each
+/// cell is of by `kind`, or garbage. The falling block of `board`, if any,
+/// is left out.
+pub fn encode_board<T: Cell>(
+    board: &Board<T>,
+    kind: impl Fn(&T) -> Option<PieceKind>,
+) -> Vec<Vec<Option<char>>> {
+    let mut stack = board.clone();
+    stack.remove_block();
+    (0..HEIGHT)
+        .map(|row| {
+            (0..WIDTH)
+                .map(|x| {
+                    let y = stack.height().checked_sub(row + 1)?;
+                    let cell = stack.get(x, y).as_ref()?;
+                    Some(kind(cell).map_or('G', PieceKind::name))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// A bot playing by the Tetris Bot Protocol, such as Cold Clear, run as a
+/// process talking over its standard input and output.
+///
+/// Every suggestion is for a position of its own, so the bot doesn't have
+/// to follow the game between them. Answers are waited for, so a slow bot
+/// holds up the game.
+#[derive(Debug)]
+pub struct Engine {
+    name: String,
+    child: Child,
+    /// Closed on drop, for bots that stop at the end of their input.
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Engine {
+    /// Launches the bot `command` runs, its words split on whitespace.
+    pub fn launch(command: &str) -> Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words.next().context("no bot to launch")?;
+        let mut command = Command::new(program);
+        command.args(words);
+        Self::spawn(command).with_context(|| format!("failed to launch {program}"))
+    }
+
+    /// Runs `command` and agrees on the rules with the bot it starts.
+    pub fn spawn(mut command: Command) -> Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().context("no stdin")?;
+        let stdout = BufReader::new(child.stdout.take().context("no stdout")?);
+        let mut engine = Self {
+            name: String::new(),
+            child,
+            stdin: Some(stdin),
+            stdout,
+        };
+        match engine.recv()? {
+            BotMessage::Info { name, version, .. } => engine.name = format!("{name} {version}"),
+            msg => bail!("expected info, got {msg:?}"),
+        }
+        engine.send(&FrontendMessage::Rules)?;
+        match engine.recv()? {
+            BotMessage::Ready => Ok(engine),
+            BotMessage::Error { reason } => bail!("{} refused the rules: {reason}", engine.name),
+            msg => bail!("expected ready, got {msg:?}"),
+        }
+    }
+
+    /// Name and version of the bot.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Where the bot would drop the first of `pieces` on `board`, the pieces
+    /// after it previewed. Cells are of the piece `kind` says, or garbage.
+    pub fn suggest<T: Cell>(
+        &mut self,
+        board: &Board<T>,
+        pieces: &[PieceKind],
+        kind: impl Fn(&T) -> Option<PieceKind>,
+    ) -> Result<Block> {
+        if board.width() != WIDTH {
+            bail!("bots only play on boards {WIDTH} cells wide");
+        }
+        let &first = pieces.first().context("no piece to place")?;
+        self.send(&FrontendMessage::Start {
+            hold: None,
+            queue: pieces.iter().map(|piece| piece.name()).collect(),
+            combo: 0,
+            back_to_back: false,
+            board: encode_board(board, kind),
+        })?;
+        self.send(&FrontendMessage::Suggest)?;
+        let moves = loop {
+            match self.recv()? {
+                BotMessage::Suggestion { moves } => break moves,
+                BotMessage::Other => continue,
+                msg => bail!("expected a suggestion, got {msg:?}"),
+            }
+        };
+        self.send(&FrontendMessage::Stop)?;
+
+        // moves of other pieces would hold the first one
+        let location = moves
+            .iter()
+            .map(|m| &m.location)
+            .find(|location| location.kind == first.name())
+            .with_context(|| format!("{} has no move for {}", self.name, first.name()))?;
+        let height = board.height() as i32;
+        let cells = location.cells()?.map(|(x, row)| (x, height - 1 - row));
+        // filled cells other than the falling block's
+        let stack = |(x, y): (i32, i32)| {
+            let falling = board
+                .current_block()
+                .is_some_and(|block| block.coords().contains(&(x, y)));
+            board.get(x as usize, y as usize).is_some() && !falling
+        };
+        for &(x, y) in &cells {
+            if !(0..WIDTH as i32).contains(&x) || !(0..height).contains(&y) {
+                bail!("{} suggested a move off the board", self.name);
+            }
+            if stack((x, y)) {
+                bail!("{} suggested a move onto the stack", self.name);
+            }
+        }
+        if !cells
+            .iter()
+            .any(|&(x, y)| y + 1 == height || stack((x, y + 1)))
+        {
+            bail!("{} suggested a move in midair", self.name);
+        }
+
+        // the piece turned as suggested, moved onto the cells
+        let block = first.srs_block(location.orientation.state());
+        let ((left, top), _) = block.bounding_box();
+        let x = cells.iter().map(|&(x, _)| x).min().unwrap();
+        let y = cells.iter().map(|&(_, y)| y).min().unwrap();
+        Ok(block.translate(x - left, y - top))
+    }
+
+    fn send(&mut self, msg: &FrontendMessage) -> Result<()> {
+        let mut line = serde_json::to_string(msg)?;
+        line.push('\n');
+        let stdin = self.stdin.as_mut().context("the bot has quit")?;
+        stdin.write_all(line.as_bytes())?;
+        stdin.flush()?;
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<BotMessage> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            bail!("the bot exited");
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+        self.stdin = None;
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location() {
+        let location = |kind, orientation, x, y| Location {
+            kind,
+            orientation,
+            x,
+            y,
+        };
+        let mut cells = location('T', Orientation::North, 4, 0).cells().unwrap();
+        cells.sort();
+        assert_eq!(cells, [(3, 0), (4, 0), (4, 1), (5, 0)]);
+        let mut cells = location('I', Orientation::East, 0, 2).cells().unwrap();
+        cells.sort();
+        assert_eq!(cells, [(0, 0), (0, 1), (0, 2), (0, 3)]);
+        assert!(location('X', Orientation::North, 4, 0).cells().is_err());
+
+        let msg: BotMessage = serde_json::from_str(
+            r#"{"type":"suggestion","moves":[{"location":{"type":"L","orientation":"west","x":1,"y":1},"spin":"none"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            msg,
+            BotMessage::Suggestion {
+                moves: vec![Move {
+                    location: location('L', Orientation::West, 1, 1)
+                }]
+            }
+        );
+        let msg: BotMessage = serde_json::from_str(r#"{"type":"new_thing"}"#).unwrap();
+        assert_eq!(msg, BotMessage::Other);
+
+        // every location has the cells of the piece turned to its state,
+        // rows flipped
+        let sorted = |mut cells: Vec<(i32, i32)>| {
+            cells.sort();
+            cells
+        };
+        for kind in PieceKind::ALL {
+            for orientation in [
+                Orientation::North,
+                Orientation::East,
+                Orientation::South,
+                Orientation::West,
+            ] {
+                let cells = location(kind.name(), orientation, 0, 0).cells().unwrap();
+                let flipped = Block::new(&cells.map(|(x, y)| (x, -y))).normalized();
+                let block = kind.srs_block(orientation.state()).normalized();
+                assert_eq!(
+                    sorted(flipped.coords().to_vec()),
+                    sorted(block.coords().to_vec()),
+                    "{kind:?} {orientation:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_board() {
+        let mut board: Board<()> = Board::new(10, 20);
+        board.set(0, 19, ());
+        board.set(9, 18, ());
+        board.spawn(PieceKind::T.block(), ()).unwrap();
+        let rows = encode_board(&board, |_| None);
+        assert_eq!(rows.len(), HEIGHT);
+        assert_eq!(rows[0][0], Some('G'));
+        assert_eq!(rows[1][9], Some('G'));
+        // the falling piece isn't part of the board
+        assert_eq!(rows.iter().flatten().flatten().count(), 2);
+
+        assert_eq!(
+            serde_json::to_string(&FrontendMessage::Suggest).unwrap(),
+            r#"{"type":"suggest"}"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_engine() {
+        // a bot that always drops the T flat in the bottom left corner, and
+        // leaves the O hanging in midair
+        let script = r#"
+            echo '{"type":"info","name":"flat","version":"1","author":"test","features":[]}'
+            read rules
+            echo '{"type":"ready"}'
+            while read start && read suggest; do
+                echo '{"type":"suggestion","moves":[{"location":{"type":"T","orientation":"north","x":1,"y":0},"spin":"none"},{"location":{"type":"O","orientation":"north","x":4,"y":5},"spin":"none"}]}'
+                read stop
+            done
+        "#;
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        let mut engine = Engine::spawn(command).unwrap();
+        assert_eq!(engine.name(), "flat 1");
+
+        let mut board: Board<()> = Board::new(10, 20);
+        board.spawn(PieceKind::T.block(), ()).unwrap();
+        let block = engine
+            .suggest(&board, &[PieceKind::T, PieceKind::I], |_| None)
+            .unwrap();
+        let mut coords = block.coords().to_vec();
+        coords.sort();
+        assert_eq!(coords, [(0, 19), (1, 18), (1, 19), (2, 19)]);
+        // turned the way it was suggested, so it kicks as it should
+        assert_eq!(
+            block.orientation(),
+            PieceKind::T
+                .srs_block(block::Orientation::Spawn)
+                .orientation()
+        );
+        assert!(engine.suggest(&board, &[PieceKind::O], |_| None).is_err());
+        // it has no move for an I
+        assert!(engine.suggest(&board, &[PieceKind::I], |_| None).is_err());
+        assert!(engine
+            .suggest(&Board::<()>::new(12, 20), &[PieceKind::T], |_| None)
+            .is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use crate::protocol::Message;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Write};
+use std::net::Ipv4Addr;
+
+/// Port TetriNet servers listen on.
+pub const PORT: u16 = 31457;
+
+/// Size of every TetriNet field.
+pub const WIDTH: usize = 12;
+pub const HEIGHT: usize = 22;
+
+/// Client version sent when logging in.
+const VERSION: &str = "1.13";
+
+/// Byte every message ends with.
+const TERMINATOR: u8 = 0xff;
+
+/// Encodes the login line for `nick`, scrambled with the IPv4 address of the
+/// `server` as TetriNet servers expect. `salt` is the first byte of the
+/// result, normally random.
+pub fn login(nick: &str, server: Ipv4Addr, salt: u8) -> String {
+    let line = format!("tetrisstart {nick} {VERSION}");
+    let hash = server_hash(server);
+
+    let mut dec = u32::from(salt);
+    let mut encoded = format!("{dec:02X}");
+    for (i, c) in line.bytes().enumerate() {
+        dec = ((dec + u32::from(c)) % 255) ^ u32::from(hash[i % hash.len()]);
+        encoded.push_str(&format!("{dec:02X}"));
+    }
+    encoded
+}
+
+fn server_hash(server: Ipv4Addr) -> Vec<u8> {
+    let [a, b, c, d] = server.octets().map(u32::from);
+    (a * 54 + b * 41 + c * 29 + d * 17).to_string().into_bytes()
+}
+
+/// Writes `line` as a single message.
+pub fn send(writer: &mut impl Write, line: &str) -> Result<()> {
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(&[TERMINATOR])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the next message, returning `None` once the server has disconnected.
+pub fn recv(reader: &mut impl BufRead) -> Result<Option<String>> {
+    let mut buf = Vec::new();
+    if reader.read_until(TERMINATOR, &mut buf)? == 0 {
+        return Ok(None);
+    }
+    if buf.last() == Some(&TERMINATOR) {
+        buf.pop();
+    }
+    Ok(Some(String::from_utf8_lossy(&buf).into_owned()))
+}
+
+/// Applies field `data` from an `f` message to `rows`, one string per row
+/// with `.` for empty cells as in [`Message::Board`].
+///
+/// Fields are sent whole, one digit per cell with `0` for empty ones, or as
+/// changes: a character from `!` to `/` for the contents, followed by the
+/// cells to fill with it as pairs of coordinates offset by `3`.
+pub fn apply_field(rows: &mut Vec<String>, data: &str) {
+    let mut cells: Vec<Vec<u8>> = match rows.len() {
+        HEIGHT => rows.iter().map(|row| row.bytes().collect()).collect(),
+        _ => vec![vec![b'.'; WIDTH]; HEIGHT],
+    };
+
+    let data = data.as_bytes();
+    if data.len() == WIDTH * HEIGHT && !(b'!'..=b'/').contains(&data[0]) {
+        for (i, &c) in data.iter().enumerate() {
+            cells[i / WIDTH][i % WIDTH] = if c == b'0' { b'.' } else { b'#' };
+        }
+    } else {
+        let mut cell = b'.';
+        let mut data = data.iter().copied();
+        while let Some(c) = data.next() {
+            if (b'!'..=b'/').contains(&c) {
+                cell = if c == b'!' { b'.' } else { b'#' };
+                continue;
+            }
+            let Some(y) = data.next() else {
+                break;
+            };
+            let (x, y) = (c.wrapping_sub(b'3') as usize, y.wrapping_sub(b'3') as usize);
+            if x < WIDTH && y < HEIGHT {
+                cells[y][x] = cell;
+            }
+        }
+    }
+
+    *rows = cells
+        .into_iter()
+        .map(|row| String::from_utf8(row).unwrap())
+        .collect();
+}
+
+/// Encodes `rows` as a whole field for an `f` message.
+pub fn field(rows: &[String]) -> String {
+    rows.iter()
+        .flat_map(|row| row.chars())
+        .map(|c| if c == '.' { '0' } else { '1' })
+        .collect()
+}
+
+/// Lines to send to the server for `msg` from the game, as player `me`.
+pub fn encode(msg: &Message, me: u8) -> Vec<String> {
+    match msg {
+        Message::Board { rows, .. } => vec![format!("f {me} {}", field(rows))],
+        Message::Garbage { lines, .. } => {
+            // classic attacks only come in 1, 2 and 4 lines
+            let mut lines = *lines;
+            let mut sent = Vec::new();
+            for n in [4, 2, 1] {
+                while lines >= n {
+                    sent.push(format!("sb 0 cs{n} {me}"));
+                    lines -= n;
+                }
+            }
+            sent
+        }
+        Message::Lost => vec![format!("playerlost {me}")],
+        _ => Vec::new(),
+    }
+}
+
+/// State of a TetriNet game as seen by one player, turning what the server
+/// says into [`Message`]s for the game.
+///
+/// Every other player in the game when it starts is an opponent, in the
+/// order of their numbers.
+#[derive(Debug, Default)]
+pub struct Session {
+    me: Option<u8>,
+    players: BTreeMap<u8, String>,
+    /// Numbers of the opponents in the game, in order.
+    opponents: Vec<u8>,
+    fields: BTreeMap<u8, Vec<String>>,
+    playing: BTreeSet<u8>,
+    started: bool,
+    over: bool,
+}
+
+impl Session {
+    /// Number the server gave the player, once it has.
+    pub fn me(&self) -> Option<u8> {
+        self.me
+    }
+
+    /// Handles `line` from the server, returning the messages it means for
+    /// the game and the lines to answer with.
+    pub fn handle(&mut self, line: &str) -> (Vec<Message>, Vec<String>) {
+        let mut words = line.split(' ');
+        let command = words.next().unwrap_or_default();
+        let mut number = || words.next().and_then(|w| w.parse::<u8>().ok());
+        let (mut messages, mut replies) = (Vec::new(), Vec::new());
+
+        match command {
+            "playernum" => {
+                if let Some(me) = number() {
+                    self.me = Some(me);
+                    replies.push(format!("team {me} "));
+                }
+            }
+            "playerjoin" => {
+                let Some(player) = number() else {
+                    return (messages, replies);
+                };
+                let nick = words.next().unwrap_or_default();
+                self.players.insert(player, nick.to_owned());
+                // the lowest numbered player is the one who can start games
+                let moderator = self.players.keys().all(|&p| Some(p) > self.me);
+                if moderator && !self.started {
+                    if let Some(me) = self.me {
+                        replies.push(format!("startgame 1 {me}"));
+                    }
+                }
+            }
+            "playerleave" | "playerlost" => {
+                if let Some(player) = number() {
+                    if self.playing.remove(&player) && !self.over {
+                        messages.extend(self.from(player).map(|from| Message::Eliminated { from }));
+                    }
+                    if command == "playerleave" {
+                        self.players.remove(&player);
+                    }
+                }
+                if self.started && self.playing.is_empty() {
+                    messages.extend(self.finish(true));
+                }
+            }
+            "playerwon" => {
+                let won = number().is_some_and(|p| Some(p) == self.me);
+                messages.extend(self.finish(won));
+            }
+            "newgame" => {
+                self.started = true;
+                self.over = false;
+                self.playing = self.players.keys().copied().collect();
+                self.fields.clear();
+                self.opponents = self.players.keys().copied().collect();
+                messages.push(Message::Start {
+                    opponents: self.players.values().cloned().collect(),
+                    seed: rand::random(),
+                    handicap: 0,
+                });
+            }
+            "endgame" => messages.extend(self.finish(false)),
+            "f" => {
+                let (Some(player), Some(data)) = (number(), words.next()) else {
+                    return (messages, replies);
+                };
+                let rows = self.fields.entry(player).or_default();
+                apply_field(rows, data);
+                let rows = rows.clone();
+                messages.extend(self.from(player).map(|from| Message::Board { rows, from }));
+            }
+            "sb" => {
+                let (Some(target), Some(kind), Some(sender)) = (
+                    number(),
+                    words.next(),
+                    words.next().and_then(|w| w.parse::<u8>().ok()),
+                ) else {
+                    return (messages, replies);
+                };
+                if Some(sender) == self.me || (target != 0 && Some(target) != self.me) {
+                    return (messages, replies);
+                }
+                let lines = match kind {
+                    "cs1" | "a" => 1,
+                    "cs2" => 2,
+                    "cs4" => 4,
+                    // other specials have no equivalent in the engine
+                    _ => 0,
+                };
+                if lines > 0 {
+                    let from = self.from(sender).unwrap_or_default();
+                    messages.push(Message::Garbage { lines, from });
+                }
+            }
+            _ => {}
+        }
+
+        (messages, replies)
+    }
+
+    /// Index of `player` among the opponents of the game.
+    fn from(&self, player: u8) -> Option<usize> {
+        self.opponents.iter().position(|&p| p == player)
+    }
+
+    fn finish(&mut self, won: bool) -> Option<Message> {
+        if !self.started || std::mem::replace(&mut self.over, true) {
+            return None;
+        }
+        self.started = false;
+        Some(Message::GameOver { won })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Undoes [`login`], as servers do.
+    fn decode_login(encoded: &str, server: Ipv4Addr) -> String {
+        let hash = server_hash(server);
+        let dec: Vec<u32> = (0..encoded.len())
+            .step_by(2)
+            .map(|i| u32::from_str_radix(&encoded[i..i + 2], 16).unwrap())
+            .collect();
+        (1..dec.len())
+            .map(|i| {
+                let c = (dec[i] ^ u32::from(hash[(i - 1) % hash.len()])) + 255 - dec[i - 1];
+                (c % 255) as u8 as char
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_login() {
+        let server = Ipv4Addr::new(192, 168, 1, 20);
+        let encoded = login("alice", server, 0x42);
+        assert!(encoded.starts_with("42"));
+        assert_eq!(decode_login(&encoded, server), "tetrisstart alice 1.13");
+    }
+
+    #[test]
+    fn test_framing() {
+        let mut buf = Vec::new();
+        send(&mut buf, "playerlost 2").unwrap();
+        send(&mut buf, "endgame").unwrap();
+
+        let mut reader = Cursor::new(buf);
+        assert_eq!(recv(&mut reader).unwrap().as_deref(), Some("playerlost 2"));
+        assert_eq!(recv(&mut reader).unwrap().as_deref(), Some("endgame"));
+        assert_eq!(recv(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_apply_field() {
+        let mut data = "0".repeat(WIDTH * HEIGHT);
+        data.replace_range(WIDTH * (HEIGHT - 1).., "111110111111");
+        let mut rows = Vec::new();
+        apply_field(&mut rows, &data);
+        assert_eq!(rows.len(), HEIGHT);
+        assert_eq!(rows[HEIGHT - 1], "#####.######");
+        assert_eq!(field(&rows), data);
+
+        // fill (5, 21) with a block, empty (0, 21)
+        apply_field(&mut rows, "\"8H!3H");
+        assert_eq!(rows[HEIGHT - 1], ".###########");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encode(&Message::Garbage { lines: 7, from: 0 }, 3),
+            ["sb 0 cs4 3", "sb 0 cs2 3", "sb 0 cs1 3"]
+        );
+        assert_eq!(encode(&Message::Lost, 3), ["playerlost 3"]);
+        assert!(encode(
+            &Message::Item {
+                item: crate::protocol::Item::Swap,
+                from: 0,
+            },
+            3
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::default();
+        let (_, replies) = session.handle("playernum 1");
+        assert_eq!(replies, ["team 1 "]);
+        let (_, replies) = session.handle("playerjoin 2 bob");
+        assert_eq!(replies, ["startgame 1 1"]);
+        session.handle("playerjoin 4 carol");
+
+        let (messages, _) = session.handle("newgame 0 1 2 1 1 1 18 1111111 1111 1 1");
+        assert!(
+            matches!(&messages[..], [Message::Start { opponents, .. }] if opponents == &["bob", "carol"])
+        );
+
+        let (messages, _) = session.handle("sb 0 cs2 4");
+        assert_eq!(messages, [Message::Garbage { lines: 2, from: 1 }]);
+        let (messages, _) = session.handle("sb 0 cs2 1");
+        assert!(messages.is_empty());
+        let (messages, _) = session.handle("sb 3 a 2");
+        assert!(messages.is_empty());
+
+        let (messages, _) = session.handle(&format!("f 2 {}", "0".repeat(WIDTH * HEIGHT)));
+        assert!(
+            matches!(&messages[..], [Message::Board { rows, from: 0 }] if rows.len() == HEIGHT)
+        );
+
+        let (messages, _) = session.handle("playerleave 4");
+        assert_eq!(messages, [Message::Eliminated { from: 1 }]);
+        let (messages, _) = session.handle("playerlost 2");
+        assert_eq!(
+            messages,
+            [
+                Message::Eliminated { from: 0 },
+                Message::GameOver { won: true }
+            ]
+        );
+        let (messages, _) = session.handle("endgame");
+        assert!(messages.is_empty());
+    }
+}
@@ -1,31 +1,699 @@
 use std::cmp;
-use std::time::{Duration, Instant};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::{block::Block as TBlock, board::Board};
-use anyhow::Result;
+#[cfg(feature = "discord")]
+use crate::presence::Presence;
+use crate::{
+    analysis::{Analysis, LockEvent},
+    attack::{self, AttackTable},
+    block::{self, PieceKind},
+    board::{self, Board, Numbered},
+    bot::{self, Bot, Difficulty, Move, Weights},
+    clipboard,
+    clock::{Clock, Timer, FULL_SPEED},
+    config::{
+        self, Config, Ghost, Grid, HoldMode, LockResets, Randomizer, Rotation, Scoring, Skin,
+        Sparkline, Theme,
+    },
+    finesse,
+    fumen::{self, Cell},
+    grade::Grading,
+    headless,
+    map::{Fill, Map},
+    modes::{self, Effect, Event as ModeEvent, GameMode, View as ModeView},
+    net::Client,
+    opener::{Opener, Trainer},
+    protocol::{Handshake, Item, Message, Targeting, PROTOCOL_VERSION},
+    puzzle::{Clear, Puzzle, Scenario},
+    replay::{Header, Input, Replay, REPLAY_VERSION},
+    scoring, screenshot,
+    script::{Hooked, Script},
+    stats::{self, GameOver, GameResult, Heatmap, ModeStats},
+    tbp::Engine,
+};
+use anyhow::{anyhow, bail, Result};
+use clap::ValueEnum;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, trace, warn};
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use ratatui::{
     buffer::Buffer,
-    layout::Rect,
-    style::{Color, Stylize},
-    symbols::{border, Marker},
+    layout::{Constraint, Layout, Position, Rect},
+    style::{Color, Style, Stylize},
+    symbols::Marker,
     text::Line,
     widgets::{
         canvas::{self, Canvas, Context},
-        Block, Widget,
+        Block, Paragraph, Sparkline as SparklineWidget, Widget,
     },
     DefaultTerminal, Frame,
 };
 
+/// Rules the game is played by, shared with opponents and replays.
+const RULESET: &str = "classic";
+
+/// Cells across and down the boxes pieces are previewed in, enough for every
+/// piece lying flat.
+const PREVIEW_WIDTH: usize = 4;
+const PREVIEW_HEIGHT: usize = 2;
+/// Previews are drawn small to fit many of them beside the board.
+const PREVIEW_SCALE: u16 = 1;
+/// Rows taken by the level and score multiplier beside the board, without
+/// the zone meter.
+const STATS_HEIGHT: u16 = 4;
+
+/// Time for each row of the stack to turn gray after topping out.
+const GAME_OVER_ROW_TIME: Duration = Duration::from_millis(50);
+/// Time the gray stack stays on screen before the game ends.
+const GAME_OVER_PAUSE: Duration = Duration::from_millis(800);
+
+/// Countdown before play resumes after a pause.
+const RESUME_COUNTDOWN: Duration = Duration::from_secs(3);
+/// Shade of the guides drawn on empty cells, barely off black.
+const GRID_COLOR: Color = Color::Indexed(236);
+/// Color of cells placed by a map that are never cleared.
+const SOLID_COLOR: Color = Color::Indexed(240);
+/// Rows at the top of the board tinted as a reminder of how close topping
+/// out is.
+const DANGER_ROWS: usize = 4;
+/// Background of the empty cells of the danger zone.
+const DANGER_COLOR: Color = Color::Indexed(52);
+/// Pieces the plan shown on the board looks ahead to, the falling one
+/// included.
+const PLAN_PIECES: usize = 2;
+
+/// Lines to clear to fill the zone meter, which can be spent once it's a
+/// quarter full.
+const ZONE_METER: usize = 32;
+/// Time gravity stops in the zone for each line in the meter, 20 seconds
+/// for a full one.
+const ZONE_TIME_PER_LINE: Duration = Duration::from_millis(625);
+
+/// Most items that can be kept in an item battle.
+const ITEM_SLOTS: usize = 6;
+
+/// Time the label of a scoring clear floats over the board.
+const POPUP_TIME: Duration = Duration::from_millis(1200);
+/// Terminal rows the label drifts up by before it's gone.
+const POPUP_RISE: u16 = 3;
+/// Time the banner announcing a new level stays up, flashing.
+const BANNER_TIME: Duration = Duration::from_millis(1500);
+const BANNER_FLASH: Duration = Duration::from_millis(250);
+
+/// Seconds of pace the sparkline covers.
+const PACE_SECONDS: u64 = 60;
+/// Rows taken by the sparkline beside the board.
+const SPARKLINE_HEIGHT: u16 = 4;
+/// Rows taken by the pieces left in the bag beside the board.
+const BAG_HEIGHT: u16 = 3;
+/// Rows taken by the timer of modes played against the clock.
+const TIMER_HEIGHT: u16 = 3;
+/// Times the TGM randomizer rolls for a piece that isn't in its history.
+const TGM_ROLLS: usize = 4;
+/// History the TGM randomizer starts from, so that the first pieces are
+/// unlikely to be S or Z.
+const TGM_HISTORY: [PieceKind; 4] = [PieceKind::Z, PieceKind::S, PieceKind::Z, PieceKind::S];
+/// Pieces without an I after which the drought counter turns red.
+const DROUGHT_PIECES: usize = 12;
+/// Scoring clears listed under the board when the score log is on.
+const SCORE_LOG_LENGTH: usize = 4;
+/// A timer counting down turns red when this little time is left.
+const TIMER_WARNING: Duration = Duration::from_secs(10);
+/// How long the final board of a replay rendered to a GIF stays up.
+const LAST_FRAME_TIME: Duration = Duration::from_secs(2);
+/// How far back practice mode can rewind.
+const REWIND_TIME: Duration = Duration::from_secs(10);
+/// Time between the states practice mode can rewind to.
+const REWIND_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Lines to clear in a sprint.
+pub const SPRINT_LINES: usize = 40;
+/// Highest level a marathon can be started at.
+pub const MAX_START_LEVEL: usize = 20;
+/// Rows of cheese to dig through.
+pub const DIG_ROWS: usize = 10;
+/// Lines to clear in a B-type game.
+pub const B_TYPE_LINES: usize = 25;
+/// Rows of garbage a B-type game starts on at each height, as on the NES.
+pub const B_TYPE_HEIGHTS: [usize; 6] = [0, 3, 5, 8, 10, 12];
+/// Length of an ultra run.
+pub const ULTRA_TIME: Duration = Duration::from_secs(120);
+/// Bots a royale is played against, unless told otherwise.
+pub const ROYALE_BOTS: usize = 15;
+/// Most bots a royale can be played against.
+pub const MAX_BOTS: usize = 48;
+
+/// Single player modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    /// Play until topping out
+    #[default]
+    Marathon,
+    /// Clear 40 lines as fast as possible
+    Sprint,
+    /// Score as much as possible in two minutes
+    Ultra,
+    /// Dig through rows of cheese garbage as fast as possible
+    Dig,
+    /// Survive garbage rising ever faster for as long as possible
+    Survival,
+    /// Clear 25 lines from a field of garbage, scoring as much as possible
+    BType,
+    /// Sandbox with pieces of your choice and optional gravity
+    Practice,
+    /// Outlast bots attacking each other and you
+    Royale,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Sprint => "sprint",
+            Mode::Ultra => "ultra",
+            Mode::Dig => "dig",
+            Mode::Survival => "survival",
+            Mode::BType => "b-type",
+            Mode::Practice => "practice",
+            Mode::Royale => "royale",
+        }
+    }
+}
+
+/// How a filled cell of the board came to be and behaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TileState {
+    /// Part of a piece, falling or locked.
+    Locked,
+    Garbage,
+    /// Rows with indestructible cells are never cleared.
+    Indestructible,
+    /// In a row being cleared, shown until the line clear delay is over.
+    Fading,
+}
+
+/// A filled cell of the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Tile {
+    /// Piece the cell is part of, `None` for garbage and solid cells.
+    kind: Option<PieceKind>,
+    color: Color,
+    state: TileState,
+}
+
+impl Tile {
+    /// A cell of a `kind` piece.
+    fn piece(kind: PieceKind) -> Self {
+        Self {
+            kind: Some(kind),
+            color: kind.color(),
+            state: TileState::Locked,
+        }
+    }
+
+    fn garbage() -> Self {
+        Self {
+            kind: None,
+            color: Color::Gray,
+            state: TileState::Garbage,
+        }
+    }
+
+    /// An indestructible cell of a map.
+    fn solid() -> Self {
+        Self {
+            kind: None,
+            color: SOLID_COLOR,
+            state: TileState::Indestructible,
+        }
+    }
+}
+
+impl board::Cell for Tile {
+    fn clearable(&self) -> bool {
+        self.state != TileState::Indestructible
+    }
+}
+
+/// 1 to 7 for the pieces `IOTJLSZ`, 8 for garbage and 9 for indestructible
+/// cells, as in [`Tetris::board_text`].
+impl board::Numbered for Tile {
+    fn number(&self) -> u32 {
+        if self.state == TileState::Indestructible {
+            return 9;
+        }
+        self.kind
+            .and_then(|kind| PieceKind::ALL.iter().position(|&k| k == kind))
+            .map_or(8, |i| i as u32 + 1)
+    }
+
+    fn from_number(number: u32) -> Result<Self> {
+        match number {
+            1..=7 => Ok(Tile::piece(PieceKind::ALL[number as usize - 1])),
+            8 => Ok(Tile::garbage()),
+            9 => Ok(Tile::solid()),
+            n => bail!("unknown cell {n}"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Tetris {
-    board: Board<Color>,
+    board: Board<Tile>,
     scale: u16,
     score: i32,
+    /// The part of the score from soft and hard drops, which gravity leaves
+    /// out.
+    dropped: i32,
+    lines: usize,
+    tetrises: usize,
     exit: bool,
-    rng: ThreadRng,
+    /// Ctrl+Z was pressed, the terminal is handed back at the next update.
+    suspend: bool,
+    seed: u64,
+    rng: ChaCha8Rng,
+    versus: Option<Versus>,
+    #[cfg(feature = "discord")]
+    presence: Option<Presence>,
+    clock: Clock,
+    recording: Option<Vec<Input>>,
+    playback: Option<Playback>,
+    /// Shape of the falling block.
+    piece: PieceKind,
+    /// Random pieces to come, at least as many as are previewed.
+    queue: VecDeque<PieceKind>,
+    /// Colors of the pieces in `queue`, drawn at random for replays before
+    /// v3.
+    colors: VecDeque<Color>,
+    /// Pieces left in the bag, drawn from the back.
+    bag: Vec<PieceKind>,
+    /// Last pieces dealt by the TGM or NES randomizer, oldest first.
+    history: VecDeque<PieceKind>,
+    /// Pieces dealt since the last I piece.
+    drought: usize,
+    /// Whether the last move of the falling block was a rotation.
+    rotated: bool,
+    puzzle: Option<Puzzle>,
+    trainer: Option<Trainer>,
+    /// Rules of the mode played.
+    game_mode: Box<dyn GameMode>,
+    /// Lines to clear in a sprint.
+    line_goal: usize,
+    /// Level the game started at.
+    start_level: usize,
+    /// Height of the garbage a B-type game started on.
+    garbage_height: usize,
+    /// When the goal of a sprint or ultra run was reached.
+    finished: Option<Duration>,
+    /// When the last line was cleared.
+    cleared_at: Duration,
+    /// When the stack topped out.
+    topped_out: Option<Duration>,
+    game_over: Option<GameOver>,
+    /// Clears in a row before the last one, if the last lock cleared lines.
+    combo: Option<usize>,
+    /// Personal best in this mode to compare against.
+    best: Option<ModeStats>,
+    grading: Option<Grading>,
+    /// When each section of a marathon was completed.
+    splits: Vec<Duration>,
+    practice: Option<Practice>,
+    royale: Option<Royale>,
+    hint: Option<Hint>,
+    /// Engine suggesting the plan shown on the board instead of the
+    /// built-in bot.
+    engine: Option<Engine>,
+    script: Option<Script>,
+    /// Config file to take changes to the look of the game from.
+    watcher: Option<config::Watcher>,
+    /// Time between gravity ticks a script set, over the usual.
+    script_gravity: Option<Duration>,
+    /// Rows of garbage a script wants risen after the next lock.
+    script_garbage: usize,
+    stepping: Option<Stepping>,
+    /// Score at the last [`Tetris::check_invariants`].
+    checked_score: i32,
+    config: Config,
+    lock_delay: LockDelay,
+    hold: Hold,
+    clearing: Option<Clearing>,
+    pause: Option<Pause>,
+    zone: Zone,
+    /// Items won in an item battle, oldest first.
+    items: Option<VecDeque<Item>>,
+    /// Labels of recent scoring clears, oldest first.
+    popups: Vec<Popup>,
+    /// Names and points of the last scoring clears, oldest first.
+    score_log: VecDeque<(String, i32)>,
+    /// The last level up: what it's announced as and when it happened.
+    banner: Option<(String, Duration)>,
+    /// What the sparkline plots over the last minute.
+    pace: Pace,
+    /// Where every piece locked.
+    heatmap: Heatmap,
+    /// What every lock did, for the analysis after the game.
+    locks: Vec<LockEvent>,
+    /// Fewest inputs for every placement, to count finesse faults.
+    finesse: finesse::Table,
+    /// Moves and rotations of the falling block so far.
+    inputs: u32,
+    /// Keys pressed since the last lock.
+    keys: u32,
+}
+
+/// Pieces placed or lines sent in each second of the last
+/// [`PACE_SECONDS`], for the sparkline.
+#[derive(Debug, Default)]
+struct Pace {
+    /// Seconds into the game with anything to count, oldest first.
+    seconds: VecDeque<(u64, u64)>,
+}
+
+impl Pace {
+    /// Counts `amount` at time `at` into the game.
+    fn add(&mut self, at: Duration, amount: u64) {
+        let second = at.as_secs();
+        match self.seconds.back_mut() {
+            Some((last, total)) if *last == second => *total += amount,
+            _ => self.seconds.push_back((second, amount)),
+        }
+        while self
+            .seconds
+            .front()
+            .is_some_and(|&(s, _)| s + PACE_SECONDS < second)
+        {
+            self.seconds.pop_front();
+        }
+    }
+
+    /// Totals of the [`PACE_SECONDS`] whole seconds before `now`, oldest
+    /// first.
+    fn samples(&self, now: Duration) -> Vec<u64> {
+        let end = now.as_secs();
+        let start = end.saturating_sub(PACE_SECONDS);
+        let mut samples = vec![0; (end - start) as usize];
+        for &(second, total) in &self.seconds {
+            if (start..end).contains(&second) {
+                samples[(second - start) as usize] = total;
+            }
+        }
+        samples
+    }
+}
+
+/// A label like `+4 TETRIS` floating up from the rows a clear scored for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Popup {
+    text: String,
+    /// Board row it starts from.
+    row: usize,
+    /// Time into the game at which the rows were cleared.
+    at: Duration,
+}
+
+/// Meter charged by clearing lines, spent to stop gravity for a while. Rows
+/// cleared in the meantime gather at the bottom and score all at once when
+/// the zone ends.
+#[derive(Debug, Default)]
+struct Zone {
+    meter: usize,
+    /// Time into the game at which the zone ends, while in it.
+    until: Option<Duration>,
+}
+
+/// A pause, followed by a countdown with the board frozen once the player
+/// resumes.
+#[derive(Debug)]
+struct Pause {
+    /// When play resumes, once counting down.
+    resume_at: Option<Instant>,
+}
+
+/// Rows cleared by the last lock, shown until the line clear delay is over.
+#[derive(Debug)]
+struct Clearing {
+    /// The board before the rows collapsed.
+    board: Board<Tile>,
+    /// Time into the game at which the next piece spawns.
+    until: Duration,
+}
+
+/// What a cell of the board shows, for the widget to style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layer {
+    Empty,
+    /// A cell of the locked stack, garbage included.
+    Stack(Tile),
+    /// A cell of the falling piece.
+    Active(Tile),
+    /// An empty cell the falling piece would land on, in its color.
+    Ghost(Color),
+}
+
+/// The board as it's drawn, composed from the stack, the falling piece and
+/// its ghost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BoardView {
+    /// Layers of the cells, row by row from the top.
+    cells: Vec<Vec<Layer>>,
+    /// Rows of the stack turned gray after topping out, from the bottom.
+    gray_rows: usize,
+    /// Rows of garbage waiting to rise in a match.
+    pending: usize,
+}
+
+impl BoardView {
+    fn get(&self, x: usize, y: usize) -> Layer {
+        self.cells[y][x]
+    }
+
+    /// Color cell (`x`, `y`) is filled with, [`Color::Reset`] if none.
+    fn color(&self, x: usize, y: usize) -> Color {
+        let gray = y + self.gray_rows >= self.cells.len();
+        match self.get(x, y) {
+            Layer::Empty | Layer::Ghost(_) => Color::Reset,
+            Layer::Stack(_) | Layer::Active(_) if gray => Color::DarkGray,
+            Layer::Stack(tile) if tile.state == TileState::Fading => Color::White,
+            Layer::Stack(tile) | Layer::Active(tile) => tile.color,
+        }
+    }
+}
+
+/// The held piece.
+#[derive(Debug, Clone, Default)]
+struct Hold {
+    piece: Option<PieceKind>,
+    /// Color the held piece had on the board.
+    color: Option<Color>,
+    /// Whether the falling block came out of or went into hold.
+    used: bool,
+}
+
+/// Lock delay of the falling block: resting on the stack, it locks once the
+/// delay runs out, which moves restart while it has resets left.
+#[derive(Debug, Default)]
+struct LockDelay {
+    resets: u32,
+    /// Milliseconds into the game the block came to rest or was last moved
+    /// on the stack, `None` while it can fall.
+    since: Option<u64>,
+    /// Whether a move since the last tick restarted the delay, for replays
+    /// before v5 that waited a tick instead.
+    reset: bool,
+}
+
+/// The plan of a bot for the board, kept until the stack or the pieces to
+/// come change.
+#[derive(Debug)]
+struct Hint {
+    stack: String,
+    pieces: Vec<PieceKind>,
+    line: Vec<Move>,
+}
+
+/// Frame-step debugging: gravity only ticks on request, and inputs wait for
+/// the next step.
+#[derive(Debug, Default)]
+struct Stepping {
+    buffered: Vec<Action>,
+    frames: u64,
+}
+
+/// Bots of a royale, attacking each other and the player.
+#[derive(Debug)]
+struct Royale {
+    difficulty: Difficulty,
+    bots: Vec<Bot>,
+    /// Time into the game at which each bot drops its next piece.
+    moves_at: Vec<Duration>,
+    /// Garbage the bots sent the player, rising after the player's next
+    /// lock unless canceled.
+    pending_garbage: u32,
+    /// Engine playing for the first bot, if any.
+    engine: Option<Engine>,
+    /// Picks the bots' pieces, who gets attacked and where garbage holes
+    /// go, so a royale plays out the same from the same seed.
+    rng: ChaCha8Rng,
+}
+
+impl Royale {
+    /// `bots` bots of `difficulty` on boards `width` by `height` cells, all
+    /// the randomness of the royale coming from `seed`.
+    fn new(bots: usize, difficulty: Difficulty, width: usize, height: usize, seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        // apart from the player's pieces, which come from the same seed
+        rng.set_stream(1);
+        let mut bots: Vec<_> = (0..bots)
+            .map(|_| Bot::new(width, height, rng.random(), difficulty.skill()))
+            .collect();
+        Self {
+            difficulty,
+            moves_at: bots.iter_mut().map(Bot::piece_time).collect(),
+            bots,
+            pending_garbage: 0,
+            engine: None,
+            rng,
+        }
+    }
+
+    /// Bots still standing.
+    fn left(&self) -> usize {
+        self.bots.iter().filter(|bot| !bot.is_out()).count()
+    }
+
+    /// Sends `lines` of garbage from bot `attacker`, or from the player if
+    /// `None`, to someone else still standing.
+    fn attack(&mut self, attacker: Option<usize>, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        let mut targets: Vec<Option<usize>> = (0..self.bots.len())
+            .filter(|&i| Some(i) != attacker && !self.bots[i].is_out())
+            .map(Some)
+            .collect();
+        if attacker.is_some() {
+            targets.push(None);
+        }
+        match targets.choose(&mut self.rng) {
+            Some(Some(bot)) => self.bots[*bot].receive(lines),
+            Some(None) => self.pending_garbage += lines,
+            None => {}
+        }
+    }
+
+    /// Has every bot due to move by `now` drop a piece, attacking with what
+    /// it clears by `attack`.
+    fn update(&mut self, now: Duration, attack: &AttackTable) {
+        for i in 0..self.bots.len() {
+            if self.bots[i].is_out() || self.moves_at[i] > now {
+                continue;
+            }
+            let engine = if i == 0 { self.engine.as_mut() } else { None };
+            let lines = self.bots[i].step_with(attack, engine);
+            self.attack(Some(i), lines);
+            self.moves_at[i] = now + self.bots[i].piece_time();
+            if self.bots[i].is_out() {
+                debug!(bot = i, left = self.left(), "bot out");
+            }
+        }
+    }
+}
+
+/// Sandbox controls of practice mode.
+#[derive(Debug, Default)]
+struct Practice {
+    /// Piece to spawn next instead of a random one.
+    next: Option<PieceKind>,
+    gravity_off: bool,
+    /// State as every piece spawned, latest last.
+    history: Vec<Snapshot>,
+    /// State every [`REWIND_INTERVAL`] over the last [`REWIND_TIME`] of
+    /// play, latest last.
+    rewind: VecDeque<Snapshot>,
+}
+
+/// State of the game as a piece spawned or while it fell, to undo its
+/// placement or rewind to.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    /// When it was taken.
+    at: Duration,
+    board: Board<Tile>,
+    piece: PieceKind,
+    score: i32,
+    dropped: i32,
+    lines: usize,
+    tetrises: usize,
+    hold: Hold,
+}
+
+/// Everything that changes the state of the game.
+///
+/// Gravity is an action too, so replaying the recorded actions reproduces a
+/// game exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Left,
+    Right,
+    Rotate,
+    Drop,
+    /// Moves the falling piece a row down without locking it.
+    SoftDrop,
+    Tick,
+    Hold,
+    Zone,
+}
+
+/// Inputs of a replay being played back, in order.
+#[derive(Debug)]
+struct Playback {
+    inputs: Vec<Input>,
+    next: usize,
+    /// Milliseconds into the game of the input being replayed.
+    time: u64,
+    /// Format of the replay, which picks the rules of older builds.
+    version: u32,
+    lock_delay: u64,
+    lock_resets: LockResets,
+    hold: HoldMode,
+    line_clear_delay: u64,
+    zone: bool,
+    scoring: Scoring,
+    randomizer: Randomizer,
+    rotation: Rotation,
+}
+
+/// State of a versus match played through `tetris-server`.
+#[derive(Debug)]
+struct Versus {
+    client: Client,
+    /// Everyone else in the match, in the order of the room, once it
+    /// starts.
+    opponents: Vec<Opponent>,
+    /// Whom the player's attacks go to in a free-for-all.
+    targeting: Targeting,
+    /// Index of the opponent picked by hand.
+    manual: usize,
+    pending_garbage: u32,
+    /// Items used on the player, taking effect on the next lock.
+    pending_items: Vec<Item>,
+    topped_out: bool,
+    won: Option<bool>,
+}
+
+#[derive(Debug)]
+struct Opponent {
+    name: String,
+    rows: Vec<String>,
+    /// Whether they topped out or left.
+    out: bool,
 }
 
 impl Default for Tetris {
@@ -36,163 +704,4654 @@ impl Default for Tetris {
 
 impl Tetris {
     pub fn new(width: usize, height: usize, scale: u16) -> Self {
+        let seed = rand::random();
         Self {
             board: Board::new(width, height),
             scale,
             score: 0,
+            dropped: 0,
+            lines: 0,
+            tetrises: 0,
             exit: false,
-            rng: rand::rng(),
+            suspend: false,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            versus: None,
+            #[cfg(feature = "discord")]
+            presence: None,
+            clock: Clock::default(),
+            recording: None,
+            playback: None,
+            piece: PieceKind::I,
+            queue: VecDeque::new(),
+            colors: VecDeque::new(),
+            bag: Vec::new(),
+            history: VecDeque::new(),
+            drought: 0,
+            rotated: false,
+            puzzle: None,
+            trainer: None,
+            game_mode: modes::game_mode(Mode::Marathon),
+            line_goal: SPRINT_LINES,
+            start_level: 1,
+            garbage_height: 0,
+            finished: None,
+            cleared_at: Duration::ZERO,
+            topped_out: None,
+            game_over: None,
+            combo: None,
+            best: None,
+            grading: None,
+            splits: Vec::new(),
+            practice: None,
+            royale: None,
+            hint: None,
+            engine: None,
+            script: None,
+            watcher: None,
+            script_gravity: None,
+            script_garbage: 0,
+            stepping: None,
+            checked_score: 0,
+            config: Config::default(),
+            lock_delay: LockDelay::default(),
+            hold: Hold::default(),
+            clearing: None,
+            pause: None,
+            zone: Zone::default(),
+            items: None,
+            popups: Vec::new(),
+            score_log: VecDeque::new(),
+            banner: None,
+            pace: Pace::default(),
+            heatmap: Heatmap::new(width, height),
+            locks: Vec::new(),
+            finesse: finesse::Table::new(width),
+            inputs: 0,
+            keys: 0,
         }
     }
 
-    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
-        let mut last_update = Instant::now();
-        while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
+    /// Sets up the game `replay` was recorded from to play it back.
+    pub fn from_replay(replay: Replay, scale: u16) -> Result<Self> {
+        let header = replay.header;
+        if header.ruleset != RULESET {
+            bail!("replay uses unknown ruleset {}", header.ruleset);
+        }
+        if !(1..=MAX_START_LEVEL).contains(&header.start_level) {
+            bail!(
+                "replay starts at level {}, not between 1 and {MAX_START_LEVEL}",
+                header.start_level
+            );
+        }
 
-            if event::poll(Duration::from_millis(20))? {
-                self.handle_events()?;
+        let mut tetris = Self::new(header.width, header.height, scale);
+        tetris.reseed(header.seed);
+        // the cheese of a dig comes from the seed
+        tetris.config.messiness = header.messiness;
+        let mut tetris = tetris
+            .with_mode(header.mode)
+            .with_line_goal(header.line_goal);
+        tetris.start_level = header.start_level;
+        tetris.playback = Some(Playback {
+            inputs: replay.inputs,
+            next: 0,
+            time: 0,
+            version: header.version,
+            lock_delay: header.lock_delay,
+            lock_resets: header.lock_resets,
+            hold: header.hold,
+            line_clear_delay: header.line_clear_delay,
+            zone: header.zone,
+            scoring: header.scoring,
+            randomizer: header.randomizer,
+            rotation: header.rotation,
+        });
+        Ok(tetris)
+    }
+
+    /// Starts from the field of the first page of `fumen`.
+    pub fn with_fumen(mut self, fumen: &str) -> Result<Self> {
+        if self.board.width() != fumen::WIDTH {
+            bail!("fumen fields are {} cells wide", fumen::WIDTH);
+        }
+
+        let field: Vec<Vec<_>> = fumen::decode(fumen)?
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.map(fumen_tile)).collect())
+            .collect();
+        self.fill_bottom(&field)?;
+        Ok(self)
+    }
+
+    /// Plays `scenario` until its goal is reached or its pieces run out.
+    pub fn with_puzzle(self, scenario: &Scenario) -> Result<Self> {
+        let mut tetris = self.with_field(&scenario.board)?;
+        tetris.puzzle = Some(Puzzle::new(scenario));
+        Ok(tetris)
+    }
+
+    /// Walks through `opener` placement by placement, showing where each
+    /// piece goes and scoring how many land there.
+    pub fn with_opener(mut self, opener: &Opener) -> Result<Self> {
+        if opener.width != self.board.width() {
+            bail!(
+                "opener is {} cells wide, the board {}",
+                opener.width,
+                self.board.width()
+            );
+        }
+        if opener.height > self.board.height() {
+            bail!(
+                "opener is {} rows high, the board {}",
+                opener.height,
+                self.board.height()
+            );
+        }
+        self.trainer = Some(Trainer::new(opener.clone()));
+        Ok(self)
+    }
+
+    /// Starts with gray cells where `field` is `true`, given from top to
+    /// bottom and aligned to the bottom of the board.
+    pub fn with_field(mut self, field: &[Vec<bool>]) -> Result<Self> {
+        let field: Vec<Vec<_>> = field
+            .iter()
+            .map(|row| row.iter().map(|&c| c.then(Tile::garbage)).collect())
+            .collect();
+        self.fill_bottom(&field)?;
+        Ok(self)
+    }
+
+    /// Starts from the board of `map`, its indestructible cells never
+    /// cleared.
+    pub fn with_map(mut self, map: &Map) -> Result<Self> {
+        let field: Vec<Vec<_>> = map
+            .board
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| {
+                        cell.map(|fill| match fill {
+                            Fill::Garbage => Tile::garbage(),
+                            Fill::Solid => Tile::solid(),
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        self.fill_bottom(&field)?;
+        Ok(self)
+    }
+
+    /// Starts from a board in the text format of [`Tetris::board_text`],
+    /// aligned to the bottom.
+    pub fn with_board_text(mut self, text: &str) -> Result<Self> {
+        let board: Board<Tile> = text.parse()?;
+        let field: Vec<Vec<_>> = (0..board.height())
+            .map(|y| (0..board.width()).map(|x| *board.get(x, y)).collect())
+            .collect();
+        self.fill_bottom(&field)?;
+        Ok(self)
+    }
+
+    /// The board without the falling piece as text, in the format of the
+    /// `board!` test macro: 1 to 7 for the pieces `IOTJLSZ`, 8 for garbage
+    /// and 9 for indestructible cells.
+    pub fn board_text(&self) -> String {
+        let mut board = self.board.clone();
+        board.remove_block();
+        board.to_string()
+    }
+
+    /// The state of the game for a program driving it, see
+    /// [`headless`](crate::headless).
+    pub fn headless_state(&self) -> headless::State {
+        let mut board = self.board.clone();
+        let block = board.remove_block();
+        headless::State {
+            board: (0..board.height())
+                .map(|y| {
+                    (0..board.width())
+                        .map(|x| {
+                            board
+                                .get(x, y)
+                                .as_ref()
+                                .map_or(0, Numbered::number)
+                                .to_string()
+                        })
+                        .collect()
+                })
+                .collect(),
+            piece: block.as_ref().map(|_| self.piece.name()),
+            cells: block.map_or(Vec::new(), |block| block.coords().to_vec()),
+            queue: self
+                .upcoming()
+                .into_iter()
+                .take(self.config.next(self.mode()))
+                .map(PieceKind::name)
+                .collect(),
+            hold: self.hold.piece.map(PieceKind::name),
+            score: self.score,
+            lines: self.lines,
+            level: self.level(),
+            game_over: self.game_over.map(|reason| reason.to_string()),
+        }
+    }
+
+    /// Every lock so far, oldest first.
+    pub fn locks(&self) -> &[LockEvent] {
+        &self.locks
+    }
+
+    /// Fills the bottom of the board with `field`, given from top to bottom.
+    fn fill_bottom(&mut self, field: &[Vec<Option<Tile>>]) -> Result<()> {
+        let offset = self.board.height() as isize - field.len() as isize;
+        for (row, cells) in field.iter().enumerate() {
+            if cells.len() != self.board.width() {
+                bail!("rows have to be {} cells wide", self.board.width());
             }
-            if last_update.elapsed()
-                >= Duration::from_millis(cmp::max(800 - 20 * self.score, 200) as u64)
-            {
-                let _ = self.board.down();
-                self.update_board();
-                last_update = Instant::now();
+            for (x, cell) in cells.iter().enumerate() {
+                let Some(tile) = cell else {
+                    continue;
+                };
+                let y = usize::try_from(row as isize + offset)
+                    .map_err(|_| anyhow!("field is too tall for the board"))?;
+                self.board.set(x, y, *tile);
             }
         }
 
         Ok(())
     }
 
-    fn update_board(&mut self) {
-        const COLORS: [Color; 6] = [
-            Color::Red,
-            Color::Green,
-            Color::Yellow,
-            Color::Blue,
-            Color::Magenta,
-            Color::Cyan,
-        ];
+    /// Plays `mode` instead of marathon. A dig starts from cheese as messy as
+    /// the config says, so set that first.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.game_mode = modes::game_mode(mode);
+        let setup = self.game_mode.init();
+        self.practice = setup.practice.then(Practice::default);
+        self.royale = (setup.bots > 0).then(|| {
+            let (width, height) = (self.board.width(), self.board.height());
+            Royale::new(setup.bots, Difficulty::default(), width, height, self.seed)
+        });
+        if setup.cheese > 0 {
+            self.add_cheese(setup.cheese);
+        }
+        self
+    }
 
-        if self
-            .board
-            .try_down()
-            .or_else(|_| {
-                self.score += self.board.clear_filled_rows() as i32;
-                self.board.spawn(
-                    TBlock::new(*TBlock::SHAPES.choose(&mut self.rng).unwrap()),
-                    *COLORS.choose(&mut self.rng).unwrap(),
-                )
-            })
-            .is_err()
-        {
-            self.exit()
+    /// Pushes up `rows` rows of cheese garbage, as messy as configured,
+    /// leaving the top row free.
+    fn add_cheese(&mut self, rows: usize) {
+        let rows = rows.min(self.board.height() - 1);
+        let holes = attack::cheese_holes(
+            rows,
+            self.board.width(),
+            self.config.messiness(),
+            &mut self.rng,
+        );
+        for hole in holes {
+            // only ever called before the first piece, so nothing is pushed
+            // out of the board
+            let _ = self.board.add_garbage(1, hole, Tile::garbage());
         }
     }
 
-    fn draw(&self, frame: &mut Frame) {
-        let area = Rect {
-            x: 0,
-            y: 0,
-            width: self.board.width() as u16 * self.scale * 2 + 2,
-            height: self.board.height() as u16 * self.scale + 2,
-        };
-        if area.intersection(frame.area()) != area {
-            frame.render_widget("too small", frame.area());
-        } else {
-            frame.render_widget(self, area);
+    /// Plays a royale against `bots` bots instead of [`ROYALE_BOTS`].
+    pub fn with_bots(mut self, bots: usize) -> Self {
+        if let Some(royale) = &self.royale {
+            let (width, height) = (self.board.width(), self.board.height());
+            self.royale = Some(Royale::new(
+                bots,
+                royale.difficulty,
+                width,
+                height,
+                self.seed,
+            ));
         }
+        self
     }
 
-    fn fill_square(&self, ctx: &mut Context<'_>, x: usize, y: usize) {
-        let color = self.board.get(x, y).unwrap_or(Color::Reset);
-        let cx = x as f64;
-        let cy = (self.board.height() - y - 1) as f64;
-        let line_count = 2 * self.scale;
-        for i in 0..line_count {
-            ctx.draw(&canvas::Line {
-                x1: cx + 1.0 / line_count as f64,
-                y1: cy + i as f64 / line_count as f64,
-                x2: cx + 1.0,
-                y2: cy + i as f64 / line_count as f64,
-                color,
-            });
+    /// Plays a royale against bots of `difficulty` instead of medium ones.
+    pub fn with_difficulty(mut self, difficulty: Difficulty) -> Self {
+        if let Some(royale) = &self.royale {
+            let (width, height) = (self.board.width(), self.board.height());
+            self.royale = Some(Royale::new(
+                royale.bots.len(),
+                difficulty,
+                width,
+                height,
+                self.seed,
+            ));
         }
+        self
     }
 
-    fn handle_events(&mut self) -> Result<()> {
-        match event::read()? {
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-                match key_event.code {
-                    KeyCode::Char('q') => self.exit(),
-                    KeyCode::Left => {
-                        if self.board.left().is_ok() {
-                            self.update_board();
-                        }
-                    }
-                    KeyCode::Right => {
-                        if self.board.right().is_ok() {
-                            self.update_board();
-                        }
-                    }
-                    KeyCode::Up => {
-                        if self.board.rotate().is_ok() {
-                            self.update_board();
-                        }
-                    }
-                    KeyCode::Down => {
-                        self.board.drop();
-                        self.update_board();
-                    }
-                    _ => {}
-                }
-            }
-            _ => {}
+    /// Races to clear `lines` lines in a sprint instead of [`SPRINT_LINES`].
+    pub fn with_line_goal(mut self, lines: usize) -> Self {
+        self.line_goal = lines;
+        self
+    }
+
+    /// Starts at `level`, scoring and falling as fast as if the levels
+    /// before it had been played.
+    pub fn with_start_level(mut self, level: usize) -> Self {
+        self.start_level = level;
+        self
+    }
+
+    /// Starts a B-type game on the rows of garbage of `height`, one of
+    /// [`B_TYPE_HEIGHTS`], as messy as the config says.
+    pub fn with_garbage_height(mut self, height: usize) -> Self {
+        self.garbage_height = height;
+        self.add_cheese(B_TYPE_HEIGHTS[height]);
+        self
+    }
+
+    /// Plays at `speed` percent of full speed, slowing gravity, lock delay
+    /// and animations alike.
+    pub fn with_speed(mut self, speed: u32) -> Self {
+        self.clock.set_speed(speed);
+        self
+    }
+
+    /// Shows how the game compares to `best` while playing.
+    pub fn with_personal_best(mut self, best: Option<&ModeStats>) -> Self {
+        self.best = best.cloned();
+        self
+    }
+
+    /// Awards arcade style grades as the score goes up.
+    pub fn with_grades(mut self) -> Self {
+        self.grading = Some(Grading::default());
+        self
+    }
+
+    /// Has `engine` play the first bot of a royale, or else suggest where
+    /// to drop the falling piece, marked on the board. Bots set up after
+    /// this play without it.
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        info!(engine = engine.name(), "engine");
+        match &mut self.royale {
+            Some(royale) => royale.engine = Some(engine),
+            None => self.engine = Some(engine),
         }
+        self
+    }
 
-        Ok(())
+    /// Takes on changes to how the game looks as `watcher` sees its config
+    /// file change, see [`Config::reload`].
+    pub fn with_watcher(mut self, watcher: config::Watcher) -> Self {
+        self.watcher = Some(watcher);
+        self
     }
 
-    fn exit(&mut self) {
-        self.exit = true;
+    /// Has `script` hook into the game, see [`Script`].
+    pub fn with_script(mut self, script: Script) -> Self {
+        self.script = Some(script);
+        self
     }
-}
 
-impl Widget for &Tetris {
-    fn render(self, area: Rect, buf: &mut Buffer) {
-        let title = Line::from(" tetris ".bold());
-        let title_bottom = if self.score > 0 {
-            Line::from(vec![
-                " score: ".into(),
-                self.score.to_string().blue().bold(),
-                " ".into(),
-            ])
+    /// Pauses gravity and advances one tick at a time, see [`Tetris::step`].
+    pub fn with_stepping(mut self) -> Self {
+        self.stepping = Some(Stepping::default());
+        self
+    }
+
+    /// Records every action so the game can be saved with [`Tetris::replay`].
+    pub fn record(mut self) -> Self {
+        self.recording = Some(Vec::new());
+        self
+    }
+
+    /// The recorded game, if recording.
+    pub fn replay(&self) -> Option<Replay> {
+        Some(Replay {
+            header: Header {
+                version: REPLAY_VERSION,
+                ruleset: RULESET.to_owned(),
+                seed: self.seed,
+                width: self.board.width(),
+                height: self.board.height(),
+                lock_delay: self.lock_delay(),
+                lock_resets: self.lock_resets(),
+                hold: self.hold_mode(),
+                line_clear_delay: self.line_clear_delay(),
+                zone: self.zone_enabled(),
+                start_level: self.start_level,
+                scoring: self.scoring(),
+                randomizer: self.randomizer(),
+                rotation: self.rotation(),
+                mode: self.game_mode.mode(),
+                line_goal: self.line_goal,
+                messiness: self.config.messiness(),
+            },
+            inputs: self.recording.clone()?,
+        })
+    }
+
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Plays with pieces from `seed` instead of a random one.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.reseed(seed);
+        self
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.queue.clear();
+        self.colors.clear();
+        self.bag.clear();
+        self.history.clear();
+    }
+
+    /// Name of the kind of game being played.
+    pub fn mode(&self) -> &'static str {
+        if self.playback.is_some() {
+            "replay"
+        } else if self.versus.is_some() {
+            "versus"
+        } else if self.puzzle.is_some() {
+            "puzzle"
+        } else if self.trainer.is_some() {
+            "opener"
         } else {
-            Line::from(vec![
-                " press ".into(),
-                "<Q>".blue().bold(),
-                " to quit ".into(),
-            ])
+            self.game_mode.mode().name()
+        }
+    }
+
+    /// Name the statistics of the game are kept under: the kind of game,
+    /// the level of a game started past the first, what the mode adds and
+    /// the preset played by.
+    pub fn stats_name(&self) -> String {
+        let mut name = self.mode().to_owned();
+        if self.start_level > 1 {
+            name += &format!(" level {}", self.start_level);
+        }
+        if let Some(added) = self.game_mode.stats_name(&self.mode_view()) {
+            name += &format!(" {added}");
+        }
+        if let Some(preset) = self.config.preset {
+            name += &format!(" {}", preset.name());
+        }
+        name
+    }
+
+    /// The clock of the mode being played, if it's played against one.
+    fn timer(&self) -> Option<Timer> {
+        self.game_mode.timer()
+    }
+
+    /// What the rules of the mode see of the game.
+    fn mode_view(&self) -> ModeView {
+        let best = self.best.as_ref();
+        ModeView {
+            score: self.score,
+            lines: self.lines,
+            line_goal: self.line_goal,
+            garbage_height: self.garbage_height,
+            splits: self.splits.len(),
+            time: self.game_time(),
+            cleared_at: self.cleared_at,
+            finished: self.finished,
+            garbage_rows: self.garbage_rows(),
+            players: self.royale.as_ref().map(|royale| {
+                let total = royale.bots.len() + 1;
+                (royale.left() + 1, total)
+            }),
+            best_time: best.and_then(|best| best.best_time),
+            best_score: best
+                .filter(|best| best.games > 0)
+                .map(|best| best.best_score),
+        }
+    }
+
+    /// Ends the game if the rules of the mode say it's over.
+    fn check_outcome(&mut self) {
+        if self.finished.is_some() {
+            return;
+        }
+        if let Some((reason, at)) = self.game_mode.outcome(&self.mode_view()) {
+            self.finished = Some(at);
+            self.end(reason);
+        }
+    }
+
+    /// Time the game has taken, stopping when it ends.
+    fn game_time(&self) -> Duration {
+        self.finished
+            .or(self.topped_out)
+            .unwrap_or_else(|| self.clock.elapsed())
+    }
+
+    /// Summary of the game for the statistics, unless it was a replay,
+    /// practice or played in slow motion.
+    pub fn result(&self) -> Option<GameResult> {
+        if self.playback.is_some() || self.practice.is_some() || self.clock.slowed() {
+            return None;
+        }
+
+        let view = self.mode_view();
+        Some(GameResult {
+            mode: self.stats_name(),
+            score: self.game_mode.score(&view),
+            lines: self.lines as u64,
+            tetrises: self.tetrises as u64,
+            time: self.game_time(),
+            goal_time: self.game_mode.goal_time(&view),
+            splits: self.splits.clone(),
+            heatmap: self.heatmap.clone(),
+            analysis: Analysis::new(&self.locks),
+            end: self.game_over.unwrap_or(GameOver::Quit),
+            seed: self.seed,
+        })
+    }
+
+    /// Names of the player and opponent of a one-on-one versus match that
+    /// started, and whether the player won. Quitting or losing the
+    /// connection before it's over loses it, as the server has it forfeited.
+    pub fn match_result(&self) -> Option<(&str, &str, bool)> {
+        let versus = self.versus.as_ref()?;
+        let [opponent] = &versus.opponents[..] else {
+            return None;
         };
+        Some((
+            versus.client.name(),
+            &opponent.name,
+            versus.won.unwrap_or(false),
+        ))
+    }
 
-        let block = Block::bordered()
-            .title(title.centered())
-            .title_bottom(title_bottom.centered())
-            .border_set(border::THICK);
+    /// Settings an opponent has to share to play against this game.
+    pub fn handshake(&self) -> Handshake {
+        Handshake {
+            version: PROTOCOL_VERSION,
+            ruleset: RULESET.to_owned(),
+            width: self.board.width(),
+            height: self.board.height(),
+            attack: self.config.attack.clone(),
+            items: self.items.is_some(),
+            players: 2,
+        }
+    }
 
-        Canvas::default()
-            .block(block)
-            .x_bounds([0.0, self.board.width() as f64])
-            .y_bounds([0.0, self.board.height() as f64])
-            .marker(Marker::HalfBlock)
-            .paint(|ctx| {
-                for x in 0..self.board.width() {
-                    for y in 0..self.board.height() {
-                        self.fill_square(ctx, x, y);
+    /// Plays an item battle, once joined to a room with [`Tetris::with_client`].
+    pub fn with_items(mut self) -> Self {
+        self.items = Some(VecDeque::new());
+        self
+    }
+
+    /// Plays a versus match over `client` instead of a single player game.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.versus = Some(Versus {
+            client,
+            opponents: Vec::new(),
+            targeting: Targeting::default(),
+            manual: 0,
+            pending_garbage: 0,
+            pending_items: Vec::new(),
+            topped_out: false,
+            won: None,
+        });
+        self
+    }
+
+    /// Whether the pieces are moving, i.e. not waiting for or done with a
+    /// match, puzzle, opener or run.
+    fn playing(&self) -> bool {
+        self.finished.is_none()
+            && self.topped_out.is_none()
+            && self
+                .versus
+                .as_ref()
+                .is_none_or(|v| !v.opponents.is_empty() && !v.topped_out && v.won.is_none())
+            && self.puzzle.as_ref().is_none_or(|p| p.solved().is_none())
+            && self.trainer.as_ref().is_none_or(|t| !t.done())
+    }
+
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        self.clock.restart();
+        // gravity goes by game time, so it slows down with the clock
+        let mut last_update = self.clock.elapsed();
+        while !self.exit {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            self.handle_messages()?;
+            if event::poll(Duration::from_millis(self.config.poll))? {
+                self.handle_events()?;
+            }
+            if std::mem::take(&mut self.suspend) {
+                suspend(terminal)?;
+            }
+            #[cfg(feature = "discord")]
+            self.update_presence();
+            if let Some(pause) = &self.pause {
+                if pause.resume_at.is_some_and(|at| Instant::now() >= at) {
+                    // time spent paused doesn't count towards the game
+                    self.clock.resume();
+                    self.pause = None;
+                }
+                last_update = self.clock.elapsed();
+                continue;
+            }
+            if self.versus.is_none() && self.game_over_shown() {
+                self.exit();
+            }
+            if self.game_over.is_none() {
+                self.check_outcome();
+            }
+            self.reload_config();
+            self.update_royale();
+            self.update_hint();
+            if self.playback.is_some() {
+                self.play_back();
+            } else if self.clearing.is_some() && self.stepping.is_none() {
+                // gravity waits for the next piece
+                if self
+                    .clearing
+                    .as_ref()
+                    .is_some_and(|c| self.clock.elapsed() >= c.until)
+                {
+                    self.apply(Action::Tick);
+                }
+                last_update = self.clock.elapsed();
+            } else if self.zone.until.is_some() && self.stepping.is_none() {
+                // gravity stops until the zone ends
+                if self.zone.until.is_some_and(|t| self.clock.elapsed() >= t) {
+                    self.apply(Action::Tick);
+                }
+                last_update = self.clock.elapsed();
+            } else if !self.playing()
+                || self.stepping.is_some()
+                || self.practice.as_ref().is_some_and(|p| p.gravity_off)
+            {
+                last_update = self.clock.elapsed();
+            } else if self.lock_delay_due() {
+                // the resting block locks as soon as its delay runs out
+                self.apply(Action::Tick);
+                last_update = self.clock.elapsed();
+            } else if self.clock.elapsed() >= last_update + self.gravity() {
+                self.apply(Action::Tick);
+                last_update = self.clock.elapsed();
+            }
+        }
+        #[cfg(feature = "discord")]
+        {
+            self.presence = None;
+        }
+
+        Ok(())
+    }
+
+    /// Shows what's being played on Discord.
+    #[cfg(feature = "discord")]
+    pub fn with_presence(mut self, presence: Presence) -> Self {
+        self.presence = Some(presence);
+        self
+    }
+
+    /// Brings what's shown on Discord up to date with the game.
+    #[cfg(feature = "discord")]
+    fn update_presence(&mut self) {
+        let details = format!("Playing {}", self.mode());
+        let state = if self.pause.is_some() {
+            "Paused".to_owned()
+        } else if let Some(reason) = self.game_over {
+            format!("Game over: {reason}")
+        } else {
+            format!("Level {}, {} lines", self.level(), self.lines)
+        };
+        let elapsed = self.clock.elapsed();
+        if let Some(presence) = &mut self.presence {
+            presence.update(&details, &state, elapsed);
+        }
+    }
+
+    /// Time between gravity ticks, which gets shorter as the score, less
+    /// drop points, or, by some speed curves, the level goes up.
+    fn gravity(&self) -> Duration {
+        if let Some(interval) = self.script_gravity {
+            return interval;
+        }
+        if let Some(interval) = self.config.speed_curve.interval(self.level()) {
+            return interval;
+        }
+        // what singles would have scored on the levels skipped
+        let skipped = 5 * self.start_level * (self.start_level - 1);
+        let scored = (self.score - self.dropped).max(0);
+        let speedup = 20 * (scored as u64 + skipped as u64);
+        let interval = cmp::max(
+            self.config.gravity.saturating_sub(speedup),
+            self.config.min_gravity,
+        );
+        Duration::from_millis(interval)
+    }
+
+    /// Whether gravity has sped up as far as it goes.
+    fn fastest(&self) -> bool {
+        let fastest = self
+            .config
+            .speed_curve
+            .interval(usize::MAX)
+            .unwrap_or(Duration::from_millis(self.config.min_gravity));
+        self.gravity() <= fastest
+    }
+
+    /// Changes the state of the game, as pressing a key or gravity would.
+    pub fn apply(&mut self, action: Action) {
+        let now = self.now();
+        if let Some(recording) = &mut self.recording {
+            recording.push(Input { time: now, action });
+        }
+        // the next tick spawns the next piece, nothing moves until then
+        if self.clearing.is_some() && action != Action::Tick {
+            return;
+        }
+        // gravity doesn't tick in the zone, only when it ends
+        if self.zone.until.is_some() && action == Action::Tick {
+            self.end_zone();
+            return;
+        }
+        self.save_rewind();
+
+        if matches!(action, Action::Left | Action::Right | Action::Rotate)
+            && self.board.current_block().is_some()
+        {
+            self.inputs += 1;
+        }
+        if action != Action::Tick {
+            self.keys += 1;
+        }
+
+        // only a block already resting restarts its lock delay by moving
+        let rested = self.board.try_down().is_err();
+        let moved = match action {
+            Action::Left => self.board.left().is_ok(),
+            Action::Right => self.board.right().is_ok(),
+            Action::Rotate => match self.rotation() {
+                Rotation::Simple => self.board.rotate().is_ok(),
+                Rotation::Srs => {
+                    let from = self.board.current_block().map(block::Block::orientation);
+                    let kicks = from.map_or(&[][..], |from| self.piece.kicks(from));
+                    self.board.rotate_kicked(kicks).is_ok()
+                }
+            },
+            Action::Drop => {
+                let rows = self.board.hard_drop();
+                self.score_drop(rows, true);
+                true
+            }
+            Action::SoftDrop => {
+                let moved = self.board.down().is_ok();
+                if moved {
+                    self.score_drop(1, false);
+                }
+                moved
+            }
+            Action::Tick => {
+                self.hook(|script, game| script.on_tick(game));
+                self.board.down().is_ok()
+            }
+            Action::Hold => self.hold().unwrap_or_else(|_| {
+                self.top_out(GameOver::BlockOut);
+                false
+            }),
+            Action::Zone => {
+                self.start_zone();
+                false
+            }
+        };
+        if moved {
+            self.rotated = action == Action::Rotate;
+        }
+
+        let grounded = self.board.try_down().is_err();
+        let lock = match action {
+            Action::Drop => true,
+            Action::Hold | Action::Zone => false,
+            // replays before v2 locked the block the moment it came to rest
+            _ if self.replay_version() < 2 => (moved || action == Action::Tick) && grounded,
+            // failing to fall is resting on the stack
+            Action::Tick => !moved && self.lock_delay_over(now),
+            // replays before v5 spent a reset on coming to rest too
+            _ if self.replay_version() < 5 => {
+                if moved && grounded {
+                    self.reset_lock_delay(now);
+                }
+                false
+            }
+            Action::Left | Action::Right | Action::Rotate => {
+                if moved && rested {
+                    self.reset_lock_delay(now);
+                }
+                false
+            }
+            Action::SoftDrop => false,
+        };
+        if grounded {
+            self.lock_delay.since.get_or_insert(now);
+        } else {
+            self.lock_delay.since = None;
+        }
+        if lock {
+            if let Err(reason) = self.lock() {
+                self.top_out(reason);
+            }
+        }
+        if let Some(solved) = self.puzzle.as_ref().and_then(|p| p.solved()) {
+            self.end(if solved {
+                GameOver::Goal
+            } else {
+                GameOver::OutOfPieces
+            });
+        }
+        if self.trainer.as_ref().is_some_and(|t| t.done()) && self.finished.is_none() {
+            self.finished = Some(self.clock.elapsed());
+            self.end(GameOver::Goal);
+        }
+        if moved || lock {
+            self.send_board();
+        }
+    }
+
+    /// Format of the replay played back, or the one this game is recorded
+    /// in.
+    fn replay_version(&self) -> u32 {
+        self.playback
+            .as_ref()
+            .map_or(REPLAY_VERSION, |playback| playback.version)
+    }
+
+    /// Milliseconds into the game, or into the replayed game at the input
+    /// being replayed, so replays lock pieces just as the game recorded.
+    fn now(&self) -> u64 {
+        match &self.playback {
+            Some(playback) => playback.time,
+            None => self.clock.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// Milliseconds a block resting on the stack waits before locking in
+    /// this game.
+    fn lock_delay(&self) -> u64 {
+        match &self.playback {
+            Some(playback) => playback.lock_delay,
+            None => self.config.lock_delay,
+        }
+    }
+
+    /// Whether the resting block has waited out its lock delay by `now`.
+    fn lock_delay_over(&mut self, now: u64) -> bool {
+        // replays before v5 waited a gravity tick instead, and another one
+        // after every reset
+        if self.replay_version() < 5 {
+            return !std::mem::take(&mut self.lock_delay.reset);
+        }
+        // without a block there's nothing to wait for
+        if self.board.current_block().is_none() {
+            return true;
+        }
+        let delay = self.lock_delay();
+        self.lock_delay
+            .since
+            .is_some_and(|since| now >= since + delay)
+    }
+
+    /// Whether the lock delay of the resting block ran out before gravity
+    /// ticks again, which a delay of 0 leaves to gravity.
+    fn lock_delay_due(&self) -> bool {
+        let delay = self.lock_delay();
+        delay > 0
+            && self
+                .lock_delay
+                .since
+                .is_some_and(|since| self.now() >= since + delay)
+    }
+
+    /// Locks resting blocks on the next tick instead of after the lock
+    /// delay, for games where time doesn't pass.
+    pub(crate) fn lock_on_tick(&mut self) {
+        self.config.lock_delay = 0;
+    }
+
+    /// Lock delay resets allowed in this game.
+    fn lock_resets(&self) -> LockResets {
+        match &self.playback {
+            Some(playback) => playback.lock_resets,
+            None => self.config.lock_resets(self.mode()),
+        }
+    }
+
+    /// How the pieces of this game are picked.
+    fn randomizer(&self) -> Randomizer {
+        match &self.playback {
+            Some(playback) => playback.randomizer,
+            None => self.config.randomizer,
+        }
+    }
+
+    /// How pieces turn in this game.
+    fn rotation(&self) -> Rotation {
+        match &self.playback {
+            Some(playback) => playback.rotation,
+            None => self.config.rotation,
+        }
+    }
+
+    /// What cleared lines score in this game.
+    fn scoring(&self) -> Scoring {
+        match &self.playback {
+            Some(playback) => playback.scoring,
+            None => self.config.scoring,
+        }
+    }
+
+    /// Scores dropping the current piece `rows` rows, by hard drop if `hard`.
+    fn score_drop(&mut self, rows: usize, hard: bool) {
+        let points = scoring::drop_points(rows, hard) as i32;
+        self.score += points;
+        self.dropped += points;
+    }
+
+    /// Milliseconds between clearing rows and spawning the next piece.
+    fn line_clear_delay(&self) -> u64 {
+        match &self.playback {
+            Some(playback) => playback.line_clear_delay,
+            None => self.config.line_clear_delay,
+        }
+    }
+
+    /// Whether clearing lines charges the zone meter in this game.
+    fn zone_enabled(&self) -> bool {
+        match &self.playback {
+            Some(playback) => playback.zone,
+            None => {
+                self.config.zone
+                    && self.versus.is_none()
+                    && self.puzzle.is_none()
+                    && self.trainer.is_none()
+            }
+        }
+    }
+
+    /// Spends the zone meter, if it's at least a quarter full, to stop
+    /// gravity for as long as it was charged.
+    fn start_zone(&mut self) {
+        if !self.zone_enabled() || self.zone.until.is_some() || self.zone.meter < ZONE_METER / 4 {
+            return;
+        }
+
+        let time = ZONE_TIME_PER_LINE * std::mem::take(&mut self.zone.meter) as u32;
+        debug!(?time, "zone");
+        self.zone.until = Some(self.clock.elapsed() + time);
+    }
+
+    /// Clears the rows gathered at the bottom during the zone all at once,
+    /// and starts the falling block over from the top.
+    fn end_zone(&mut self) {
+        self.zone.until = None;
+        let falling = self.board.remove_block().is_some();
+        let lines = self.board.clear_filled_rows();
+        debug!(lines, "zone ended");
+        let score = self.score;
+        self.count_lines(lines);
+        if lines > 0 {
+            let row = self.board.height() - lines.div_ceil(2);
+            self.popup(self.score - score, format!("{lines} LINE ZONE"), row);
+        }
+        if falling && self.spawn(self.piece).is_err() {
+            self.top_out(GameOver::BlockOut);
+        }
+    }
+
+    /// How often pieces can be held in this game.
+    fn hold_mode(&self) -> HoldMode {
+        match &self.playback {
+            Some(playback) => playback.hold,
+            // holding would throw the placements out of order
+            None if self.trainer.is_some() => HoldMode::Disabled,
+            None => self.config.hold,
+        }
+    }
+
+    /// Whether the falling block can go into hold.
+    fn can_hold(&self) -> bool {
+        match self.hold_mode() {
+            HoldMode::Standard => !self.hold.used,
+            HoldMode::Unlimited => true,
+            HoldMode::Disabled => false,
+        }
+    }
+
+    /// Swaps the falling block with the held piece, or holds it and spawns the
+    /// next piece if none is held. Returns whether the block was held.
+    fn hold(&mut self) -> Result<bool> {
+        if !self.can_hold() {
+            return Ok(false);
+        }
+        if self.board.current_block().is_none() {
+            return Ok(false);
+        }
+
+        let color = self
+            .board
+            .current_block()
+            .and_then(|block| {
+                let (x, y) = block.coords()[0];
+                *self.board.get(x as usize, y as usize)
+            })
+            .map(|tile| tile.color);
+        self.board.remove_block();
+        let held = self.hold.piece.replace(self.piece);
+        let held_color = std::mem::replace(&mut self.hold.color, color);
+        self.hold.used = true;
+        debug!(piece = %self.piece.name(), "hold");
+        match held {
+            Some(shape) => self.spawn_colored(shape, held_color)?,
+            None => self.spawn_next()?,
+        }
+        Ok(true)
+    }
+
+    /// Restarts the lock delay of the resting block at `now`, if it has
+    /// resets left.
+    fn reset_lock_delay(&mut self, now: u64) {
+        if self.lock_resets().allows(self.lock_delay.resets) {
+            self.lock_delay.resets += 1;
+            self.lock_delay.since = Some(now);
+            self.lock_delay.reset = true;
+            trace!(resets = self.lock_delay.resets, "lock delay reset");
+        }
+    }
+
+    /// Checks the internal invariants of the game, for tests and fuzzing
+    /// driving it with [`Tetris::apply`]: the board is consistent and the
+    /// score never went down since the last check, except through practice
+    /// mode's reset and undo or a script.
+    pub fn check_invariants(&mut self) -> Result<()> {
+        self.board.check_invariants()?;
+        if self.score < self.checked_score && self.practice.is_none() && self.script.is_none() {
+            bail!(
+                "score went down from {} to {}",
+                self.checked_score,
+                self.score
+            );
+        }
+        if self.tetrises * 4 > self.lines {
+            bail!("{} tetrises but only {} lines", self.tetrises, self.lines);
+        }
+
+        self.checked_score = self.score;
+        Ok(())
+    }
+
+    /// Applies the replayed inputs that are due.
+    fn play_back(&mut self) {
+        self.play_until(self.clock.elapsed().as_millis() as u64);
+    }
+
+    /// Applies the replayed inputs up to `time` milliseconds into the game.
+    fn play_until(&mut self, time: u64) {
+        while let Some(playback) = &mut self.playback {
+            match playback.inputs.get(playback.next) {
+                Some(input) if input.time <= time => {
+                    let action = input.action;
+                    playback.time = input.time;
+                    playback.next += 1;
+                    trace!(time = input.time, ?action, "replay input");
+                    self.apply(action);
+                }
+                None if playback.next == playback.inputs.len() => {
+                    // compare with the log of the recorded game to spot desyncs
+                    info!(score = self.score, lines = self.lines, "replay finished");
+                    playback.next += 1;
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Settles the falling block, if any, and spawns the next one, failing
+    /// with the reason if that tops out.
+    fn lock(&mut self) -> Result<(), GameOver> {
+        let mut optimal = None;
+        // looked at before the lines under the piece can clear
+        let holes = self.board.holes_under_block();
+        if let Some(block) = self.board.current_block() {
+            let cells = block
+                .coords()
+                .iter()
+                .map(|&(x, y)| (x as usize, y as usize));
+            optimal = self.finesse.optimal(block.coords());
+            debug!(
+                piece = %self.piece.name(),
+                cells = ?block.coords(),
+                inputs = self.inputs,
+                ?optimal,
+                "placed"
+            );
+            self.heatmap.add(cells);
+            if let Some(trainer) = &mut self.trainer {
+                let hit = trainer.on_lock(block.coords(), self.board.height());
+                debug!(hit, "opener placement");
+            }
+        }
+        if self.board.current_block().is_some() && self.zone.until.is_some() {
+            let rows = self.board.sink_filled_rows();
+            trace!(rows, "lock in zone");
+            self.hold.used = false;
+            self.log_lock(Clear::default(), optimal, holes);
+            if self.config.sparkline == Sparkline::Pps {
+                self.pace.add(self.clock.elapsed(), 1);
+            }
+        } else if self.board.current_block().is_some() {
+            let tspin = self.is_tspin();
+            let immobile = self.board.is_immobile();
+            let rows = self.board.filled_rows();
+            let before = (!rows.is_empty() && self.line_clear_delay() > 0).then(|| {
+                let mut board = self.board.clone();
+                for &y in &rows {
+                    for x in 0..board.width() {
+                        if let Some(tile) = *board.get(x, y) {
+                            let state = TileState::Fading;
+                            board.set(x, y, Tile { state, ..tile });
+                        }
                     }
                 }
+                board
+            });
+            let lines = self.board.clear_filled_rows();
+            let score = self.score;
+            self.count_lines(lines);
+            if lines > 0 {
+                self.hook(|script, game| script.on_clear(game, lines));
+            }
+            if self.zone_enabled() {
+                self.zone.meter = (self.zone.meter + lines).min(ZONE_METER);
+            }
+            let clear = Clear {
+                lines,
+                tspin,
+                spin: !tspin && self.rotated && immobile,
+                perfect: lines > 0 && self.board.is_empty(),
+            };
+            debug!(?clear, score = self.score, "lock");
+            self.log_lock(clear, optimal, holes);
+            let amount = match self.config.sparkline {
+                Sparkline::Off => 0,
+                Sparkline::Pps => 1,
+                Sparkline::Attack => {
+                    u64::from(self.config.attack.lines(&clear, self.combo.unwrap_or(0)))
+                }
+            };
+            if amount > 0 {
+                self.pace.add(self.clock.elapsed(), amount);
+            }
+            if let Some(&row) = rows.get(rows.len() / 2) {
+                self.popup(self.score - score, clear_name(&clear), row);
+            }
+            if let Some(puzzle) = &mut self.puzzle {
+                puzzle.on_lock(&clear);
+            }
+            self.exchange_garbage(&clear)
+                .map_err(|_| GameOver::GarbageOut)?;
+            self.mode_event(ModeEvent::Lock { lines })
+                .map_err(|_| GameOver::GarbageOut)?;
+            self.rise_script_garbage()
+                .map_err(|_| GameOver::GarbageOut)?;
+            self.hold.used = false;
+
+            if let Some(board) = before {
+                let delay = Duration::from_millis(self.line_clear_delay());
+                self.clearing = Some(Clearing {
+                    board,
+                    until: self.clock.elapsed() + delay,
+                });
+                return Ok(());
+            }
+        }
+
+        self.clearing = None;
+        self.spawn_next().map_err(|_| GameOver::BlockOut)
+    }
+
+    /// Adds a lock that made `clear` to the log the analysis after the game
+    /// is worked out from, with the fewest inputs the piece could have taken
+    /// and the `holes` it covered up.
+    fn log_lock(&mut self, clear: Clear, optimal: Option<u32>, holes: usize) {
+        self.locks.push(LockEvent {
+            clear,
+            combo: self.combo,
+            stack_height: self.board.stack_height(),
+            holes,
+            inputs: self.inputs,
+            optimal,
+            keys: std::mem::take(&mut self.keys),
+        });
+    }
+
+    /// Floats `+points name` up from `row` for a moment, if anything was
+    /// scored.
+    fn popup(&mut self, points: i32, name: String, row: usize) {
+        if points <= 0 {
+            return;
+        }
+        let at = self.clock.elapsed();
+        self.popups.retain(|popup| at - popup.at < POPUP_TIME);
+        self.popups.push(Popup {
+            text: format!("+{points} {name}"),
+            row,
+            at,
+        });
+        if self.score_log.len() == SCORE_LOG_LENGTH {
+            self.score_log.pop_front();
+        }
+        self.score_log.push_back((name, points));
+    }
+
+    /// Scores `lines` cleared at once and keeps track of the progress they
+    /// make towards the goal of the game.
+    fn count_lines(&mut self, lines: usize) {
+        let level = self.level();
+        let fastest = self.fastest();
+        self.score += self.scoring().points(lines, self.multiplier()) as i32;
+        self.lines += lines;
+        // gravity speeds up with the score, so it can top out between levels
+        let text = match (self.level() > level, !fastest && self.fastest()) {
+            (true, true) => Some(format!("LEVEL {} · MAX SPEED", self.level())),
+            (true, false) => Some(format!("LEVEL {}", self.level())),
+            (false, true) => Some("MAX SPEED".to_owned()),
+            (false, false) => None,
+        };
+        if let Some(text) = text {
+            debug!(level = self.level(), gravity = ?self.gravity(), "speed up");
+            self.banner = Some((text, self.clock.elapsed()));
+        }
+        if lines == 4 {
+            self.tetrises += 1;
+        }
+        if lines > 0 {
+            self.cleared_at = self.clock.elapsed();
+            self.combo = Some(self.combo.map_or(0, |combo| combo + 1));
+        } else {
+            self.combo = None;
+        }
+        if let Some(grading) = &mut self.grading {
+            grading.update(self.score as u64, self.clock.elapsed());
+        }
+        if lines > 0 && self.mode_event(ModeEvent::Clear { lines }).is_err() {
+            self.top_out(GameOver::GarbageOut);
+        }
+        self.check_outcome();
+    }
+
+    /// Rows with garbage left in them, dug through in a dig.
+    fn garbage_rows(&self) -> usize {
+        (0..self.board.height())
+            .filter(|&y| {
+                (0..self.board.width()).any(|x| {
+                    self.board
+                        .get(x, y)
+                        .is_some_and(|tile| tile.state == TileState::Garbage)
+                })
             })
-            .render(area, buf);
+            .count()
+    }
+
+    /// Spawns the next piece of the puzzle, opener, practice or random
+    /// sequence.
+    fn spawn_next(&mut self) -> Result<()> {
+        let shape = match (&mut self.puzzle, &self.trainer) {
+            (Some(puzzle), _) => match puzzle.next_shape() {
+                Some(shape) => Some(shape),
+                None => return Ok(()),
+            },
+            (None, Some(trainer)) => match trainer.next_shape() {
+                Some(shape) => Some(shape),
+                None => return Ok(()),
+            },
+            (None, None) => self.practice.as_mut().and_then(|p| p.next.take()),
+        };
+        let (shape, color) = match shape {
+            Some(shape) => (shape, self.legacy_color()),
+            None => {
+                // draw ahead so the previewed pieces are the ones to come
+                while self.queue.len() <= self.config.next(self.mode()) {
+                    let shape = self.draw_piece();
+                    self.queue.push_back(shape);
+                    if let Some(color) = self.legacy_color() {
+                        self.colors.push_back(color);
+                    }
+                }
+                (self.queue.pop_front().unwrap(), self.colors.pop_front())
+            }
+        };
+        self.drought = match shape {
+            PieceKind::I => 0,
+            _ => self.drought + 1,
+        };
+        self.spawn_colored(shape, color)
+    }
+
+    /// A random color for the next piece when playing back a replay before
+    /// v3, which drew one for every piece right after the piece itself.
+    fn legacy_color(&mut self) -> Option<Color> {
+        (self.replay_version() < 3).then(|| *LEGACY_COLORS.choose(&mut self.rng).unwrap())
+    }
+
+    /// Pieces yet to be dealt from the current 7-bag, all seven if the last
+    /// bag has just run out, or `None` if the pieces don't come from bags.
+    fn bag_remaining(&self) -> Option<Vec<PieceKind>> {
+        if self.randomizer() != Randomizer::Bag
+            || self.puzzle.is_some()
+            || self.trainer.is_some()
+            || self.practice.is_some()
+        {
+            return None;
+        }
+        // pieces are drawn ahead, so the bag being dealt from is split
+        // between the queue and the bag drawn from last
+        let upcoming: Vec<_> = self
+            .queue
+            .iter()
+            .chain(self.bag.iter().rev())
+            .copied()
+            .collect();
+        Some(match upcoming.len() % PieceKind::ALL.len() {
+            0 => PieceKind::ALL.to_vec(),
+            left => upcoming[..left].to_vec(),
+        })
+    }
+
+    /// Picks the next random piece, the way the randomizer of the game does.
+    fn draw_piece(&mut self) -> PieceKind {
+        match self.randomizer() {
+            Randomizer::Random => *PieceKind::ALL.choose(&mut self.rng).unwrap(),
+            Randomizer::Bag => {
+                if self.bag.is_empty() {
+                    self.bag = PieceKind::ALL.to_vec();
+                    self.bag.shuffle(&mut self.rng);
+                }
+                self.bag.pop().unwrap()
+            }
+            Randomizer::Tgm => {
+                if self.history.is_empty() {
+                    // the first piece is never one leaving an overhang
+                    self.history.extend(TGM_HISTORY);
+                    let first = [PieceKind::I, PieceKind::J, PieceKind::L, PieceKind::T];
+                    let shape = *first.choose(&mut self.rng).unwrap();
+                    self.history.pop_front();
+                    self.history.push_back(shape);
+                    return shape;
+                }
+                let mut shape = *PieceKind::ALL.choose(&mut self.rng).unwrap();
+                for _ in 1..TGM_ROLLS {
+                    if !self.history.contains(&shape) {
+                        break;
+                    }
+                    shape = *PieceKind::ALL.choose(&mut self.rng).unwrap();
+                }
+                self.history.pop_front();
+                self.history.push_back(shape);
+                shape
+            }
+            Randomizer::Nes => {
+                // the NES rolls one of eight, the eighth meaning roll again
+                let last = self.history.pop_back();
+                let roll = self.rng.random_range(0..=PieceKind::ALL.len());
+                let shape = match PieceKind::ALL.get(roll) {
+                    Some(&shape) if Some(shape) != last => shape,
+                    _ => *PieceKind::ALL.choose(&mut self.rng).unwrap(),
+                };
+                self.history.push_back(shape);
+                shape
+            }
+        }
+    }
+
+    fn spawn(&mut self, shape: PieceKind) -> Result<()> {
+        self.spawn_colored(shape, None)
+    }
+
+    /// Spawns `shape` in `color`, or in the color of the piece if `None`.
+    fn spawn_colored(&mut self, shape: PieceKind, color: Option<Color>) -> Result<()> {
+        self.hook(|script, game| script.on_spawn(game, shape.name()));
+        self.piece = shape;
+        self.rotated = false;
+        self.inputs = 0;
+        self.lock_delay = LockDelay::default();
+        debug!(piece = %shape.name(), "spawn");
+        // replays before v4 turned pieces about their first cell
+        let block = if self.replay_version() < 4 {
+            block::Block::new(shape.cells())
+        } else {
+            shape.block()
+        };
+        let tile = Tile {
+            color: color.unwrap_or(shape.color()),
+            ..Tile::piece(shape)
+        };
+        self.board.spawn(block, tile)?;
+
+        if let Some(practice) = &mut self.practice {
+            practice.history.push(Snapshot {
+                at: self.clock.elapsed(),
+                board: self.board.clone(),
+                piece: self.piece,
+                score: self.score,
+                dropped: self.dropped,
+                lines: self.lines,
+                tetrises: self.tetrises,
+                hold: self.hold.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Keeps the state of a practice game every [`REWIND_INTERVAL`] to rewind
+    /// to, forgetting what's older than [`REWIND_TIME`].
+    fn save_rewind(&mut self) {
+        let at = self.clock.elapsed();
+        let Some(practice) = &mut self.practice else {
+            return;
+        };
+        if practice
+            .rewind
+            .back()
+            .is_some_and(|last| at < last.at + REWIND_INTERVAL)
+        {
+            return;
+        }
+
+        while practice
+            .rewind
+            .front()
+            .is_some_and(|first| at > first.at + REWIND_TIME)
+        {
+            practice.rewind.pop_front();
+        }
+        practice.rewind.push_back(Snapshot {
+            at,
+            board: self.board.clone(),
+            piece: self.piece,
+            score: self.score,
+            dropped: self.dropped,
+            lines: self.lines,
+            tetrises: self.tetrises,
+            hold: self.hold.clone(),
+        });
+    }
+
+    /// Goes back to the state in `snapshot`, forgetting the practice
+    /// history after it.
+    fn restore(&mut self, snapshot: Snapshot) {
+        if let Some(practice) = &mut self.practice {
+            practice.history.retain(|s| s.at <= snapshot.at);
+            practice.rewind.retain(|s| s.at < snapshot.at);
+        }
+        self.board = snapshot.board;
+        self.piece = snapshot.piece;
+        self.score = snapshot.score;
+        self.dropped = snapshot.dropped;
+        self.lines = snapshot.lines;
+        self.tetrises = snapshot.tetrises;
+        self.hold = snapshot.hold;
+        self.clearing = None;
+        self.rotated = false;
+        self.lock_delay = LockDelay::default();
+    }
+
+    /// Whether the falling block is a T that was rotated into a spot with at
+    /// least three of the corners around its center filled.
+    fn is_tspin(&self) -> bool {
+        let Some(block) = self.board.current_block() else {
+            return false;
+        };
+        if self.piece != PieceKind::T || !self.rotated {
+            return false;
+        }
+
+        // rotation keeps the center of a T as its first coordinate
+        let (cx, cy) = block.coords()[0];
+        let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            .iter()
+            .filter(|(dx, dy)| {
+                let (x, y) = (cx + dx, cy + dy);
+                x < 0
+                    || y < 0
+                    || x as usize >= self.board.width()
+                    || y as usize >= self.board.height()
+                    || self.board.get(x as usize, y as usize).is_some()
+            })
+            .count();
+        corners >= 3
+    }
+
+    /// Ends the game, or the player's part in a match, once the stack has
+    /// turned gray.
+    fn top_out(&mut self, reason: GameOver) {
+        info!(score = self.score, lines = self.lines, %reason, "top out");
+        self.topped_out.get_or_insert(self.clock.elapsed());
+        self.end(reason);
+        if let Some(versus) = &mut self.versus {
+            // the server decides the result
+            versus.topped_out = true;
+            let _ = versus.client.send(&Message::Lost);
+        }
+    }
+
+    /// Rows of the stack turned gray so far after topping out, from the
+    /// bottom.
+    fn gray_rows(&self) -> usize {
+        let Some(at) = self.topped_out else {
+            return 0;
+        };
+        let elapsed = self.clock.elapsed().saturating_sub(at);
+        (elapsed.as_millis() / GAME_OVER_ROW_TIME.as_millis()) as usize
+    }
+
+    /// Whether the stack has turned gray and been shown for a while.
+    fn game_over_shown(&self) -> bool {
+        self.topped_out.is_some_and(|at| {
+            let animation = GAME_OVER_ROW_TIME * self.board.height() as u32 + GAME_OVER_PAUSE;
+            self.clock.elapsed() >= at + animation
+        })
+    }
+
+    /// Sends garbage for `clear`, after canceling what's pending with it, and
+    /// receives any pending garbage left.
+    fn exchange_garbage(&mut self, clear: &Clear) -> Result<()> {
+        if let Some(royale) = &mut self.royale {
+            let attack = self.config.attack.lines(clear, self.combo.unwrap_or(0));
+            let pending = std::mem::take(&mut royale.pending_garbage);
+            let (lines, pending) = attack::cancel(attack, pending);
+            royale.attack(None, lines);
+            let hole = royale.rng.random_range(0..self.board.width());
+            return self
+                .board
+                .add_garbage(pending as usize, hole, Tile::garbage());
+        }
+        let Some(versus) = &mut self.versus else {
+            return Ok(());
+        };
+
+        let attack = self.config.attack.lines(clear, self.combo.unwrap_or(0));
+        let pending = std::mem::take(&mut versus.pending_garbage);
+        let (lines, pending) = attack::cancel(attack, pending);
+        if lines > 0 {
+            // a lost connection shows up in `handle_messages`
+            let _ = versus.client.send(&Message::Garbage { lines, from: 0 });
+        }
+
+        let pending = pending as usize;
+        debug!(
+            attack,
+            sent = lines,
+            received = pending,
+            combo = ?self.combo,
+            "garbage"
+        );
+        let items = std::mem::take(&mut versus.pending_items);
+        // holes don't come from `rng` to keep both players' pieces in sync
+        let hole = rand::rng().random_range(0..self.board.width());
+        self.board.add_garbage(pending, hole, Tile::garbage())?;
+
+        if let Some(won) = &mut self.items {
+            for _ in 0..clear.lines {
+                if won.len() < ITEM_SLOTS {
+                    won.push_back(*Item::ALL.choose(&mut rand::rng()).unwrap());
+                }
+            }
+        }
+        for item in items {
+            self.apply_item(item)?;
+        }
+        Ok(())
+    }
+
+    /// Lets the bots of a royale due to move drop their pieces, and ends the
+    /// royale as won once none are left.
+    fn update_royale(&mut self) {
+        let now = self.clock.elapsed();
+        if !self.playing() {
+            return;
+        }
+        let Some(royale) = &mut self.royale else {
+            return;
+        };
+        royale.update(now, &self.config.attack);
+        if royale.left() == 0 {
+            info!("royale won");
+            self.finished = Some(now);
+            self.end(GameOver::Won);
+        }
+    }
+
+    /// Plans where a bot would drop the falling piece and the next ones, or
+    /// asks the engine where to drop the falling one, if the plan is shown
+    /// and the board or the pieces changed since.
+    fn update_hint(&mut self) {
+        let shown = self.config.plan || self.engine.is_some();
+        if !shown || !self.playing() || self.clearing.is_some() {
+            self.hint = None;
+            return;
+        }
+        let mut stack = self.board.clone();
+        stack.remove_block();
+        let stack = stack.to_text(|_| 1);
+        let pieces: Vec<_> = std::iter::once(self.piece)
+            .chain(self.upcoming())
+            .take(PLAN_PIECES)
+            .collect();
+        if self
+            .hint
+            .as_ref()
+            .is_some_and(|hint| hint.stack == stack && hint.pieces == pieces)
+        {
+            return;
+        }
+        let upcoming: Vec<_> = std::iter::once(self.piece).chain(self.upcoming()).collect();
+        let line = match &mut self.engine {
+            Some(engine) => match engine.suggest(&self.board, &upcoming, |tile| tile.kind) {
+                Ok(block) => {
+                    let mut after = self.board.clone();
+                    after.remove_block();
+                    let lines = bot::lock(&mut after, &block, Tile::garbage());
+                    vec![Move { block, lines }]
+                }
+                Err(err) => {
+                    // the built-in bot plans instead, if the plan is shown
+                    warn!("{} failed to suggest a move: {err:#}", engine.name());
+                    self.engine = None;
+                    self.hint = None;
+                    return;
+                }
+            },
+            None => bot::plan(&self.board, &pieces, Tile::garbage(), &Weights::default())
+                .map_or(Vec::new(), |plan| plan.line),
+        };
+        self.hint = Some(Hint {
+            stack,
+            pieces,
+            line,
+        });
+    }
+
+    /// Tells the rules of the mode of `event` and does what they ask,
+    /// failing if the garbage they push up tops the stack out.
+    fn mode_event(&mut self, event: ModeEvent) -> Result<()> {
+        let view = self.mode_view();
+        for effect in self.game_mode.on_event(event, &view) {
+            match effect {
+                Effect::Garbage(rows) => {
+                    for _ in 0..rows {
+                        let hole = self.rng.random_range(0..self.board.width());
+                        self.board.add_garbage(1, hole, Tile::garbage())?;
+                    }
+                }
+                Effect::Split => self.splits.push(self.cleared_at),
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes up the rows of garbage a script asked for, each with its own
+    /// hole.
+    fn rise_script_garbage(&mut self) -> Result<()> {
+        for _ in 0..std::mem::take(&mut self.script_garbage) {
+            let hole = self.rng.random_range(0..self.board.width());
+            self.board.add_garbage(1, hole, Tile::garbage())?;
+        }
+        Ok(())
+    }
+
+    /// Takes on the changes to the config file since the last frame, if
+    /// it's watched, keeping the old settings if it's invalid.
+    fn reload_config(&mut self) {
+        let Some(loaded) = self.watcher.as_ref().and_then(config::Watcher::try_recv) else {
+            return;
+        };
+        match loaded {
+            Ok(config) => {
+                info!("config reloaded");
+                self.config.reload(&config);
+            }
+            Err(err) => warn!("failed to reload the config: {err:#}"),
+        }
+    }
+
+    /// Runs a hook of the script, if any, keeping the score, gravity and
+    /// garbage it leaves. A script that fails is dropped, so the game goes on
+    /// without it.
+    fn hook(&mut self, call: impl FnOnce(&Script, &mut Hooked) -> Result<()>) {
+        let Some(script) = self.script.take() else {
+            return;
+        };
+        let gravity = self.gravity().as_millis() as u64;
+        let mut game = Hooked {
+            score: self.score,
+            lines: self.lines,
+            level: self.level(),
+            gravity,
+            garbage: self.script_garbage,
+        };
+        if let Err(err) = call(&script, &mut game) {
+            warn!("script failed: {err:#}");
+            return;
+        }
+        self.score = game.score;
+        if game.gravity != gravity {
+            self.script_gravity = Some(Duration::from_millis(game.gravity.max(1)));
+        }
+        self.script_garbage = game.garbage;
+        self.script = Some(script);
+    }
+
+    /// Sends the player's attacks in a free-for-all to whoever `targeting`
+    /// picks, once joined to a room with [`Tetris::with_client`].
+    pub fn with_targeting(mut self, targeting: Targeting) -> Self {
+        if let Some(versus) = &mut self.versus {
+            versus.targeting = targeting;
+        }
+        self
+    }
+
+    /// Picks the next opponent still playing to attack by hand.
+    fn cycle_target(&mut self) {
+        let Some(versus) = &mut self.versus else {
+            return;
+        };
+        let count = versus.opponents.len();
+        let start = match versus.targeting {
+            Targeting::Manual => versus.manual + 1,
+            _ => 0,
+        };
+        let Some(next) = (start..start + count)
+            .map(|i| i % count)
+            .find(|&i| !versus.opponents[i].out)
+        else {
+            return;
+        };
+        versus.targeting = Targeting::Manual;
+        versus.manual = next;
+        debug!(next, "target");
+        let _ = versus.client.send(&Message::Target {
+            targeting: Targeting::Manual,
+            opponent: next,
+        });
+    }
+
+    /// Uses the oldest item won on the player, or on the opponent, taking
+    /// effect when they next lock a piece.
+    fn use_item(&mut self, on_opponent: bool) {
+        let (Some(versus), Some(items)) = (&mut self.versus, &mut self.items) else {
+            return;
+        };
+        let Some(item) = items.pop_front() else {
+            return;
+        };
+
+        debug!(?item, on_opponent, "use item");
+        if on_opponent {
+            let _ = versus.client.send(&Message::Item { item, from: 0 });
+        } else {
+            versus.pending_items.push(item);
+        }
+    }
+
+    /// Applies the effect of `item` to the board, failing if it pushed the
+    /// stack out of the top.
+    fn apply_item(&mut self, item: Item) -> Result<()> {
+        // nothing here comes from `rng` to keep both players' pieces in sync
+        let mut rng = rand::rng();
+        debug!(?item, "item");
+        match item {
+            Item::AddLine => {
+                let hole = rng.random_range(0..self.board.width());
+                self.board.add_garbage(1, hole, Tile::garbage())?;
+            }
+            Item::ClearLine => self.board.clear_row(self.board.height() - 1),
+            Item::Scramble => {
+                for y in 0..self.board.height() {
+                    self.board
+                        .rotate_row(y, rng.random_range(0..self.board.width()));
+                }
+            }
+            Item::Swap => {
+                if let Some(next) = self.queue.front_mut() {
+                    *next = *PieceKind::ALL.choose(&mut rng).unwrap();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn send_board(&mut self) {
+        let Some(versus) = &mut self.versus else {
+            return;
+        };
+
+        let rows = (0..self.board.height())
+            .map(|y| {
+                (0..self.board.width())
+                    .map(|x| match self.board.get(x, y) {
+                        Some(_) => '#',
+                        None => '.',
+                    })
+                    .collect()
+            })
+            .collect();
+        let _ = versus.client.send(&Message::Board { rows, from: 0 });
+    }
+
+    fn handle_messages(&mut self) -> Result<()> {
+        let Some(versus) = &mut self.versus else {
+            return Ok(());
+        };
+
+        let mut handicap_rows = 0;
+        loop {
+            let msg = match versus.client.try_recv() {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(_) if versus.won.is_some() => break,
+                Err(_) => {
+                    warn!("lost connection to server");
+                    bail!("lost connection to server")
+                }
+            };
+            match msg {
+                Message::Start {
+                    opponents,
+                    seed,
+                    handicap,
+                } => {
+                    info!(?opponents, seed, handicap, "match started");
+                    versus.opponents = opponents
+                        .into_iter()
+                        .map(|name| Opponent {
+                            name,
+                            rows: Vec::new(),
+                            out: false,
+                        })
+                        .collect();
+                    let _ = versus.client.send(&Message::Target {
+                        targeting: versus.targeting,
+                        opponent: versus.manual,
+                    });
+                    self.seed = seed;
+                    self.rng = ChaCha8Rng::seed_from_u64(seed);
+                    self.queue.clear();
+                    self.bag.clear();
+                    self.history.clear();
+                    handicap_rows = handicap;
+                }
+                Message::Board { rows, from } => {
+                    if let Some(opponent) = versus.opponents.get_mut(from) {
+                        opponent.rows = rows;
+                    }
+                }
+                Message::Garbage { lines, .. } => versus.pending_garbage += lines,
+                Message::Item { item, .. } => versus.pending_items.push(item),
+                Message::Eliminated { from } => {
+                    if let Some(opponent) = versus.opponents.get_mut(from) {
+                        info!(opponent.name, "opponent out");
+                        opponent.out = true;
+                    }
+                }
+                Message::GameOver { won } => {
+                    info!(won, "match over");
+                    versus.won = Some(won);
+                    self.game_over.get_or_insert(if won {
+                        GameOver::Won
+                    } else {
+                        GameOver::Defeated
+                    });
+                }
+                Message::Error { reason } => {
+                    warn!(reason, "server error");
+                    return Err(anyhow!(reason));
+                }
+                _ => {}
+            }
+        }
+
+        // the first piece only spawns once the match has started, so the
+        // handicap is in place before it
+        self.add_cheese(handicap_rows);
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let board_width = self.board.width() as u16 * self.scale * 2 + 2;
+        let board_height = self.board.height() as u16 * self.scale + 2;
+        let log_height = if self.config.score_log {
+            SCORE_LOG_LENGTH as u16 + 2
+        } else {
+            0
+        };
+        let panel_width = PREVIEW_WIDTH as u16 * PREVIEW_SCALE * 2 + 2;
+        let panel_height = preview_height(1)
+            + preview_height(self.config.next(self.mode()))
+            + self.stats_height()
+            + self.bag_height()
+            + self.timer_height()
+            + self.sparkline_height();
+        let (opponents_width, opponents_height) = match &self.versus {
+            Some(versus) if versus.opponents.len() > 1 => (
+                versus.opponents.len() as u16 * (self.board.width() as u16 + 2),
+                self.board.height().div_ceil(2) as u16 + 3,
+            ),
+            Some(_) => (board_width, board_height),
+            None => match &self.royale {
+                Some(royale) => {
+                    let (columns, rows) = self.bot_grid(royale, board_height);
+                    let (width, height) = self.bot_board_size();
+                    (columns * width, rows * height)
+                }
+                None => (0, 0),
+            },
+        };
+        let mut area = Rect {
+            x: 0,
+            y: 0,
+            width: board_width + panel_width + opponents_width,
+            height: cmp::max(board_height + log_height, panel_height).max(opponents_height),
+        };
+        let outer = self
+            .versus
+            .as_ref()
+            .filter(|_| self.config.frame)
+            .map(|versus| {
+                let opponents = match &versus.opponents[..] {
+                    [] => "?".to_owned(),
+                    opponents => {
+                        let names: Vec<_> = opponents.iter().map(|o| o.name.as_str()).collect();
+                        names.join(", ")
+                    }
+                };
+                let players = format!(" {} vs {opponents} ", versus.client.name());
+                Block::bordered()
+                    .title(Line::from(format!(" {} ", self.mode()).bold()).centered())
+                    .title_bottom(Line::from(players.bold()).centered())
+                    .border_set(self.config.border.set())
+            });
+        if outer.is_some() {
+            area.width += 2;
+            area.height += 2;
+        }
+        if area.intersection(frame.area()) != area {
+            frame.render_widget("too small", frame.area());
+            return;
+        }
+        if let Some(outer) = outer {
+            let inner = outer.inner(area);
+            frame.render_widget(outer, area);
+            area = inner;
+        }
+
+        let [own, panel, opponent] = Layout::horizontal([
+            Constraint::Length(board_width),
+            Constraint::Length(panel_width),
+            Constraint::Fill(1),
+        ])
+        .areas(area);
+        let [own, log] = Layout::vertical([
+            Constraint::Length(board_height),
+            Constraint::Length(log_height),
+        ])
+        .areas(own);
+        frame.render_widget(self, own);
+        self.render_score_log(log, frame.buffer_mut());
+        self.render_panel(panel, frame.buffer_mut());
+        if let Some(versus) = &self.versus {
+            self.render_opponent(versus, opponent, frame.buffer_mut());
+        }
+        if let Some(royale) = &self.royale {
+            self.render_bots(royale, board_height, opponent, frame.buffer_mut());
+        }
+        if self.config.theme == Theme::GameBoy {
+            recolor(frame.buffer_mut(), GAME_BOY_SHADES);
+        }
+    }
+
+    /// Pieces to come after the falling one, as far as they're known.
+    fn upcoming(&self) -> Vec<PieceKind> {
+        match (&self.puzzle, &self.trainer) {
+            (Some(puzzle), _) => puzzle.queue().collect(),
+            (None, Some(trainer)) => trainer.queue().collect(),
+            (None, None) => self.queue.iter().copied().collect(),
+        }
+    }
+
+    /// Draws the hold box, grayed out while holding isn't allowed, and the
+    /// pieces to come beside the board.
+    fn render_panel(&self, area: Rect, buf: &mut Buffer) {
+        let next: Vec<_> = self
+            .upcoming()
+            .into_iter()
+            .take(self.config.next(self.mode()))
+            .map(|shape| (shape, shape.color()))
+            .collect();
+        let [hold, next_area, bag, stats, timer, sparkline] = Layout::vertical([
+            Constraint::Length(preview_height(1)),
+            Constraint::Length(preview_height(next.len())),
+            Constraint::Length(self.bag_height()),
+            Constraint::Length(self.stats_height()),
+            Constraint::Length(self.timer_height()),
+            Constraint::Length(self.sparkline_height()),
+        ])
+        .areas(area);
+
+        let (border, color) = if self.can_hold() {
+            (Color::Reset, None)
+        } else {
+            (Color::DarkGray, Some(Color::DarkGray))
+        };
+        let held: Vec<_> = self
+            .hold
+            .piece
+            .map(|shape| (shape, color.unwrap_or_else(|| shape.color())))
+            .into_iter()
+            .collect();
+        let block = self.boxed(" hold ").border_style(border);
+        render_preview(block, &held, 1, &self.config.skin, hold, buf);
+        if !next.is_empty() {
+            let block = self.boxed(" next ");
+            render_preview(block, &next, next.len(), &self.config.skin, next_area, buf);
+        }
+        if let Some(remaining) = self.bag_remaining().filter(|_| self.config.bag_peek) {
+            let cells: Vec<_> = PieceKind::ALL
+                .into_iter()
+                .map(|kind| {
+                    if remaining.contains(&kind) {
+                        kind.name().to_string().fg(kind.color()).bold()
+                    } else {
+                        "·".dark_gray()
+                    }
+                })
+                .collect();
+            Paragraph::new(Line::from(cells).centered())
+                .block(self.boxed(" bag "))
+                .render(bag, buf);
+        }
+
+        let pieces = self.locks.len();
+        let keys: u32 = self.locks.iter().map(|lock| lock.keys).sum();
+        let kpp = format!(" {:.1} kpp ", f64::from(keys) / pieces.max(1) as f64);
+        let block = self
+            .boxed(" level ")
+            .title_bottom(Line::from(kpp).centered());
+        let mut lines = vec![
+            Line::from(self.level().to_string().bold()).centered(),
+            Line::from(vec![
+                "×".into(),
+                self.multiplier().to_string().blue().bold(),
+            ])
+            .centered(),
+        ];
+        if self.zone_enabled() {
+            // a segment for every quarter of the meter
+            let quarters = self.zone.meter * 4 / ZONE_METER;
+            let meter = format!("{}{}", "▰".repeat(quarters), "▱".repeat(4 - quarters));
+            let color = if quarters > 0 {
+                Color::Blue
+            } else {
+                Color::DarkGray
+            };
+            lines.push(Line::from(meter.fg(color)).centered());
+        }
+        if let Some(items) = &self.items {
+            let items: String = (0..ITEM_SLOTS)
+                .map(|i| items.get(i).map_or('·', |item| item.symbol()))
+                .collect();
+            lines.push(Line::from(items.blue().bold()).centered());
+        }
+        if self.config.finesse {
+            let analysis = Analysis::new(&self.locks);
+            lines.push(
+                Line::from(vec![
+                    analysis.finesse_faults.to_string().red().bold(),
+                    format!(" {:.0}%", analysis.finesse_rate()).into(),
+                ])
+                .centered(),
+            );
+        }
+        if self.config.drought {
+            let count = self.drought.to_string();
+            let count = if self.drought > DROUGHT_PIECES {
+                count.red().bold()
+            } else {
+                count.bold()
+            };
+            lines.push(Line::from(vec!["no I ".into(), count]).centered());
+        }
+        Paragraph::new(lines).block(block).render(stats, buf);
+        self.render_timer(timer, buf);
+        self.render_sparkline(sparkline, buf);
+    }
+
+    /// Draws the time taken, or left in modes counting down, turning red
+    /// near the end.
+    fn render_timer(&self, area: Rect, buf: &mut Buffer) {
+        let Some(timer) = self.timer() else {
+            return;
+        };
+        let shown = timer.shown(self.game_time());
+        let time = stats::format_lap(shown).bold();
+        let time = match timer {
+            Timer::Down(_) if shown <= TIMER_WARNING => time.red(),
+            _ => time,
+        };
+        let title = match timer {
+            Timer::Up => " time ",
+            Timer::Down(_) => " time left ",
+        };
+        Paragraph::new(Line::from(time).centered())
+            .block(self.boxed(title))
+            .render(area, buf);
+    }
+
+    /// Rows taken by the timer, if the mode has one.
+    fn timer_height(&self) -> u16 {
+        if self.timer().is_some() {
+            TIMER_HEIGHT
+        } else {
+            0
+        }
+    }
+
+    /// Plots the configured pace over the last minute, squeezed into as many
+    /// bars as fit in `area`.
+    fn render_sparkline(&self, area: Rect, buf: &mut Buffer) {
+        let unit = match self.config.sparkline {
+            Sparkline::Off => return,
+            Sparkline::Pps => "pps",
+            Sparkline::Attack => "apm",
+        };
+        let samples = self.pace.samples(self.clock.elapsed());
+        let total: u64 = samples.iter().sum();
+        let secs = samples.len().max(1) as f64;
+        let rate = match self.config.sparkline {
+            Sparkline::Pps => total as f64 / secs,
+            _ => total as f64 * 60.0 / secs,
+        };
+        let title = format!(" {rate:.1} {unit} ");
+        let block = self.boxed(&title);
+
+        let bars = block.inner(area).width as usize;
+        // tenths, to tell averages apart when a bar covers several seconds
+        let data: Vec<u64> = (0..bars)
+            .filter_map(|i| {
+                let chunk = &samples[i * samples.len() / bars..(i + 1) * samples.len() / bars];
+                let sum: u64 = chunk.iter().sum();
+                (!chunk.is_empty()).then(|| sum * 10 / chunk.len() as u64)
+            })
+            .collect();
+        SparklineWidget::default()
+            .block(block)
+            .data(&data)
+            .style(Style::new().fg(Color::Blue))
+            .render(area, buf);
+    }
+
+    /// Rows taken by the box of pieces left in the bag, if it's shown.
+    fn bag_height(&self) -> u16 {
+        if self.config.bag_peek && self.bag_remaining().is_some() {
+            BAG_HEIGHT
+        } else {
+            0
+        }
+    }
+
+    /// Rows taken by the sparkline, if there is one.
+    fn sparkline_height(&self) -> u16 {
+        match self.config.sparkline {
+            Sparkline::Off => 0,
+            _ => SPARKLINE_HEIGHT,
+        }
+    }
+
+    /// Rows taken by the level box, with the zone meter, items, finesse
+    /// faults and the drought counter if there are any.
+    fn stats_height(&self) -> u16 {
+        STATS_HEIGHT
+            + u16::from(self.zone_enabled())
+            + u16::from(self.items.is_some())
+            + u16::from(self.config.finesse)
+            + u16::from(self.config.drought)
+    }
+
+    /// Draws the opponent's board beside the player's, or a miniature of
+    /// every opponent's in a free-for-all.
+    fn render_opponent(&self, versus: &Versus, area: Rect, buf: &mut Buffer) {
+        if versus.opponents.len() > 1 {
+            let width = self.board.width() as u16 + 2;
+            let height = self.board.height().div_ceil(2) as u16 + 2;
+            let [boards, targeting] =
+                Layout::vertical([Constraint::Length(height), Constraint::Length(1)]).areas(area);
+            let areas =
+                Layout::horizontal(versus.opponents.iter().map(|_| Constraint::Length(width)))
+                    .split(boards);
+            for (i, (opponent, &area)) in versus.opponents.iter().zip(areas.iter()).enumerate() {
+                let targeted = versus.targeting == Targeting::Manual && versus.manual == i;
+                self.render_miniature(opponent, targeted, area, buf);
+            }
+            Line::from(vec![
+                " target: ".into(),
+                versus.targeting.name().bold(),
+                ", ".into(),
+                "<T>".blue().bold(),
+                " to pick".into(),
+            ])
+            .render(targeting, buf);
+            return;
+        }
+
+        let opponent = versus.opponents.first();
+        let title = match opponent {
+            Some(opponent) => Line::from(format!(" {} ", opponent.name).bold()),
+            None => Line::from(" waiting for opponent ".bold()),
+        };
+        let block = Block::bordered()
+            .title(title.centered())
+            .border_set(self.config.border.set());
+
+        Canvas::default()
+            .block(block)
+            .x_bounds([0.0, self.board.width() as f64])
+            .y_bounds([0.0, self.board.height() as f64])
+            .marker(Marker::HalfBlock)
+            .paint(|ctx| {
+                for (y, row) in opponent.iter().flat_map(|o| o.rows.iter()).enumerate() {
+                    for (x, cell) in row.chars().enumerate() {
+                        if cell != '.' {
+                            self.fill_square(ctx, x, y, Color::Gray);
+                        }
+                    }
+                }
+            })
+            .render(area, buf);
+    }
+
+    /// Draws an opponent's board a character per cell across and two cells
+    /// down, in red when `targeted` by hand and grayed out once they're out.
+    fn render_miniature(&self, opponent: &Opponent, targeted: bool, area: Rect, buf: &mut Buffer) {
+        let color = if opponent.out {
+            Color::DarkGray
+        } else {
+            Color::Gray
+        };
+        let border = if targeted && !opponent.out {
+            Color::Red
+        } else {
+            color
+        };
+        let block = Block::bordered()
+            .title(Line::from(format!(" {} ", opponent.name).bold()).centered())
+            .border_set(self.config.border.set())
+            .fg(border);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let filled = |x: usize, y: usize| {
+            opponent
+                .rows
+                .get(y)
+                .and_then(|row| row.chars().nth(x))
+                .is_some_and(|c| c != '.')
+        };
+        for (row, y) in (0..self.board.height()).step_by(2).enumerate() {
+            for x in 0..self.board.width() {
+                let symbol = match (filled(x, y), filled(x, y + 1)) {
+                    (true, true) => "█",
+                    (true, false) => "▀",
+                    (false, true) => "▄",
+                    (false, false) => continue,
+                };
+                let position = Position::new(inner.x + x as u16, inner.y + row as u16);
+                if inner.contains(position) {
+                    buf[position].set_symbol(symbol).set_fg(color);
+                }
+            }
+        }
+    }
+
+    /// Width and height of the board of a bot in a royale, drawn a
+    /// character per two cells across and four down.
+    fn bot_board_size(&self) -> (u16, u16) {
+        (
+            self.board.width().div_ceil(2) as u16 + 2,
+            self.board.height().div_ceil(4) as u16 + 2,
+        )
+    }
+
+    /// Columns and rows the boards of the bots of a royale are laid out in,
+    /// as many rows as fit beside the player's board `board_height` high.
+    fn bot_grid(&self, royale: &Royale, board_height: u16) -> (u16, u16) {
+        let (_, height) = self.bot_board_size();
+        let bots = royale.bots.len() as u16;
+        let rows = (board_height / height).clamp(1, bots.max(1));
+        (bots.div_ceil(rows), rows)
+    }
+
+    /// Draws the boards of the bots of a royale in a grid, grayed out once
+    /// they're out.
+    fn render_bots(&self, royale: &Royale, board_height: u16, area: Rect, buf: &mut Buffer) {
+        let (columns, _) = self.bot_grid(royale, board_height);
+        let (width, height) = self.bot_board_size();
+        for (i, bot) in royale.bots.iter().enumerate() {
+            let (column, row) = (i as u16 % columns, i as u16 / columns);
+            let cell = Rect::new(
+                area.x + column * width,
+                area.y + row * height,
+                width,
+                height,
+            )
+            .intersection(area);
+            let color = if bot.is_out() {
+                Color::DarkGray
+            } else {
+                Color::Gray
+            };
+            let block = Block::bordered()
+                .border_set(self.config.border.set())
+                .fg(color);
+            let inner = block.inner(cell);
+            block.render(cell, buf);
+
+            let board = bot.board();
+            let filled = |x: usize, y: usize| {
+                x < board.width() && y < board.height() && board.get(x, y).is_some()
+            };
+            for (row, y) in (0..board.height()).step_by(4).enumerate() {
+                for (column, x) in (0..board.width()).step_by(2).enumerate() {
+                    // braille dots are numbered down the left column, then
+                    // the right, with the bottom row last
+                    let dots = [
+                        (0, 0),
+                        (0, 1),
+                        (0, 2),
+                        (1, 0),
+                        (1, 1),
+                        (1, 2),
+                        (0, 3),
+                        (1, 3),
+                    ];
+                    let bits = dots
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &(dx, dy))| filled(x + dx, y + dy))
+                        .fold(0, |bits, (bit, _)| bits | 1 << bit);
+                    let position = Position::new(inner.x + column as u16, inner.y + row as u16);
+                    if bits > 0 && inner.contains(position) {
+                        let symbol = char::from_u32(0x2800 + bits).unwrap_or(' ');
+                        buf[position].set_char(symbol).set_fg(color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lists the points of the last scoring clears under the board, the
+    /// latest in bold.
+    fn render_score_log(&self, area: Rect, buf: &mut Buffer) {
+        if !self.config.score_log {
+            return;
+        }
+        let latest = self.score_log.len().saturating_sub(1);
+        let lines: Vec<_> = self
+            .score_log
+            .iter()
+            .enumerate()
+            .map(|(i, (name, points))| {
+                let line = Line::from(vec![
+                    format!(" {name} ").into(),
+                    format!("+{points}").blue().bold(),
+                ]);
+                if i == latest {
+                    line.bold()
+                } else {
+                    line
+                }
+            })
+            .collect();
+        Paragraph::new(lines)
+            .block(self.boxed(" points "))
+            .render(area, buf);
+    }
+
+    /// A box titled `title` in the configured border style.
+    fn boxed<'a>(&self, title: &'a str) -> Block<'a> {
+        Block::bordered()
+            .title(Line::from(title.bold()).centered())
+            .border_set(self.config.border.set())
+    }
+
+    fn fill_square(&self, ctx: &mut Context<'_>, x: usize, y: usize, color: Color) {
+        fill_square(ctx, x, y, self.board.height(), self.scale, color);
+    }
+
+    fn handle_events(&mut self) -> Result<()> {
+        match event::read()? {
+            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                match key_event.code {
+                    KeyCode::Char('q') => {
+                        self.end(GameOver::Quit);
+                        self.exit();
+                    }
+                    KeyCode::Char('z') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        // come back to a paused game, the opponent won't wait
+                        if self.versus.is_none() {
+                            match &mut self.pause {
+                                Some(pause) => pause.resume_at = None,
+                                None => self.toggle_pause(),
+                            }
+                        }
+                        self.suspend = true;
+                    }
+                    KeyCode::Char('p') if self.versus.is_none() => self.toggle_pause(),
+                    KeyCode::Char('s') => self.screenshot(),
+                    KeyCode::Char('d') => self.save_board_text(),
+                    KeyCode::Char('y') if self.pause.is_some() => self.copy_seed(),
+                    _ if self.pause.is_some() => {}
+                    _ if !self.playing() || self.playback.is_some() => {}
+                    KeyCode::Left => self.input(Action::Left),
+                    KeyCode::Right => self.input(Action::Right),
+                    KeyCode::Up => self.input(Action::Rotate),
+                    KeyCode::Down => self.input(Action::Drop),
+                    KeyCode::Char(' ') => self.input(Action::SoftDrop),
+                    KeyCode::Char('c') => self.input(Action::Hold),
+                    KeyCode::Char('z') => self.input(Action::Zone),
+                    KeyCode::Char('1') if self.items.is_some() => self.use_item(false),
+                    KeyCode::Char('2') if self.items.is_some() => self.use_item(true),
+                    KeyCode::Char('n') if self.stepping.is_some() => self.step(),
+                    KeyCode::Char('t')
+                        if self.versus.as_ref().is_some_and(|v| v.opponents.len() > 1) =>
+                    {
+                        self.cycle_target()
+                    }
+                    KeyCode::Char(c) if self.practice.is_some() => self.practice_key(c),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Colors of the cells of the board, falling piece included, for a
+    /// screenshot.
+    pub fn screenshot_cells(&self) -> screenshot::Cells {
+        (0..self.board.height())
+            .map(|y| {
+                (0..self.board.width())
+                    .map(|x| self.board.get(x, y).map(|tile| rgb(tile.color)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Saves the board to `path` as a PNG, or an SVG if it ends in `.svg`.
+    pub fn save_screenshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        screenshot::save(&self.screenshot_cells(), path)
+    }
+
+    /// Plays the whole replay back at once, taking the board `fps` times a
+    /// second. Frames where nothing changed are merged, and the last one is
+    /// held for a moment.
+    pub fn replay_frames(&mut self, fps: u32) -> Vec<(screenshot::Cells, Duration)> {
+        let step = Duration::from_millis(1000 / u64::from(fps.max(1)));
+        let end = self
+            .playback
+            .as_ref()
+            .and_then(|playback| playback.inputs.last())
+            .map_or(0, |input| input.time);
+
+        let mut frames: Vec<(screenshot::Cells, Duration)> = Vec::new();
+        let mut time = Duration::ZERO;
+        loop {
+            self.play_until(time.as_millis() as u64);
+            let cells = self.screenshot_cells();
+            match frames.last_mut() {
+                Some((last, shown)) if *last == cells => *shown += step,
+                _ => frames.push((cells, step)),
+            }
+            if time.as_millis() as u64 >= end {
+                break;
+            }
+            time += step;
+        }
+        if let Some((_, shown)) = frames.last_mut() {
+            *shown += LAST_FRAME_TIME;
+        }
+        frames
+    }
+
+    /// Saves the board to `screenshots` in the [`config::data_dir`],
+    /// flashing whether that worked.
+    fn screenshot(&mut self) {
+        let saved = saved_path("screenshots", self.mode(), "png").and_then(|path| {
+            self.save_screenshot(&path)?;
+            Ok(path)
+        });
+        let text = match saved {
+            Ok(path) => {
+                info!(path = %path.display(), "screenshot");
+                "SCREENSHOT SAVED"
+            }
+            Err(err) => {
+                warn!("{err:#}");
+                "SCREENSHOT FAILED"
+            }
+        };
+        self.banner = Some((text.to_owned(), self.clock.elapsed()));
+    }
+
+    /// Saves the board as text to `boards` in the [`config::data_dir`],
+    /// flashing whether that worked.
+    fn save_board_text(&mut self) {
+        let saved = saved_path("boards", self.mode(), "txt").and_then(|path| {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&path, self.board_text())?;
+            Ok(path)
+        });
+        let text = match saved {
+            Ok(path) => {
+                info!(path = %path.display(), "board saved");
+                "BOARD SAVED"
+            }
+            Err(err) => {
+                warn!("failed to save the board: {err:#}");
+                "SAVING FAILED"
+            }
+        };
+        self.banner = Some((text.to_owned(), self.clock.elapsed()));
+    }
+
+    /// Copies the seed of the pieces to the clipboard, flashing whether that
+    /// worked.
+    fn copy_seed(&mut self) {
+        let text = match clipboard::copy(&self.seed.to_string()) {
+            Ok(()) => "SEED COPIED",
+            Err(err) => {
+                warn!("{err:#}");
+                "COPYING FAILED"
+            }
+        };
+        self.banner = Some((text.to_owned(), self.clock.elapsed()));
+    }
+
+    /// Pauses, counts down to resume when paused, and pauses again when
+    /// counting down.
+    fn toggle_pause(&mut self) {
+        let playing = self.playing();
+        match &mut self.pause {
+            Some(pause) => {
+                pause.resume_at = match pause.resume_at {
+                    Some(_) => None,
+                    None => Some(Instant::now() + RESUME_COUNTDOWN),
+                };
+            }
+            None if playing => {
+                self.clock.pause();
+                self.pause = Some(Pause { resume_at: None });
+            }
+            None => {}
+        }
+    }
+
+    /// Applies a player's action, or buffers it until the next step.
+    fn input(&mut self, action: Action) {
+        match &mut self.stepping {
+            Some(stepping) => stepping.buffered.push(action),
+            None => self.apply(action),
+        }
+    }
+
+    /// Applies the buffered inputs followed by a single gravity tick.
+    fn step(&mut self) {
+        let Some(stepping) = &mut self.stepping else {
+            return;
+        };
+
+        stepping.frames += 1;
+        for action in std::mem::take(&mut stepping.buffered) {
+            self.apply(action);
+        }
+        self.apply(Action::Tick);
+    }
+
+    /// Picks the next piece with `1`-`7`, toggles gravity with `g`, resets the
+    /// board with `r`, undoes the last placement with `u`, rewinds with `b`
+    /// and slows the game down with `m`.
+    fn practice_key(&mut self, key: char) {
+        let Some(practice) = &mut self.practice else {
+            return;
+        };
+
+        match key {
+            '1'..='7' => {
+                let i = key as usize - '1' as usize;
+                practice.next = Some(PieceKind::ALL[i]);
+            }
+            'g' => practice.gravity_off = !practice.gravity_off,
+            'r' => {
+                self.board = Board::new(self.board.width(), self.board.height());
+                self.score = 0;
+                self.dropped = 0;
+                self.lines = 0;
+                self.tetrises = 0;
+                self.hold = Hold::default();
+                self.zone = Zone::default();
+                practice.history.clear();
+                practice.rewind.clear();
+                let _ = self.lock();
+            }
+            'u' if practice.history.len() > 1 => {
+                // go back to the previous piece as it spawned
+                practice.history.pop();
+                let snapshot = practice.history.last().unwrap().clone();
+                self.restore(snapshot);
+            }
+            'm' => {
+                // full speed, half and a quarter in turn
+                let speed = match self.clock.speed() {
+                    FULL_SPEED => FULL_SPEED / 2,
+                    speed if speed > FULL_SPEED / 4 => FULL_SPEED / 4,
+                    _ => FULL_SPEED,
+                };
+                debug!(speed, "slow motion");
+                self.clock.set_speed(speed);
+            }
+            'b' => {
+                // a step further back with every press
+                if let Some(snapshot) = practice.rewind.pop_back() {
+                    debug!(at = ?snapshot.at, "rewind");
+                    self.restore(snapshot);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Records why the game ended, unless it already had.
+    fn end(&mut self, reason: GameOver) {
+        if self.game_over.is_none() {
+            self.game_over = Some(reason);
+            self.celebrate_best();
+        }
+    }
+
+    /// Flashes a banner, and sends a desktop notification if configured,
+    /// when the game just ended beats the personal best.
+    fn celebrate_best(&mut self) {
+        let (Some(best), Some(result)) = (&self.best, self.result()) else {
+            return;
+        };
+        if !best.beaten_by(&result) {
+            return;
+        }
+        info!(mode = result.mode.as_str(), "personal best");
+        self.banner = Some(("NEW PERSONAL BEST".to_owned(), self.clock.elapsed()));
+        if self.config.notify {
+            let body = result.summary();
+            let shown = notify_rust::Notification::new()
+                .appname("tetris-rust")
+                .summary("New personal best!")
+                .body(&body)
+                .show();
+            if let Err(err) = shown {
+                warn!("failed to send notification: {err}");
+            }
+        }
+    }
+
+    /// Why the game ended, if it has.
+    pub fn game_over(&self) -> Option<GameOver> {
+        self.game_over
+    }
+
+    fn exit(&mut self) {
+        self.exit = true;
+    }
+
+    /// Goes up every 10 lines from the level the game started at.
+    pub fn level(&self) -> usize {
+        self.start_level + self.lines / 10
+    }
+
+    /// What cleared lines score, the current level as in the guideline.
+    pub fn multiplier(&self) -> usize {
+        self.level()
+    }
+}
+
+/// What a clear is called on its popup, like `T-SPIN DOUBLE`.
+fn clear_name(clear: &Clear) -> String {
+    if clear.perfect {
+        return "PERFECT CLEAR".into();
+    }
+    let lines = ["", "SINGLE", "DOUBLE", "TRIPLE", "TETRIS"]
+        .get(clear.lines)
+        .copied()
+        .unwrap_or("TETRIS");
+    if clear.tspin {
+        format!("T-SPIN {lines}")
+    } else if clear.spin {
+        format!("SPIN {lines}")
+    } else {
+        lines.into()
+    }
+}
+
+/// A file named after `mode` and the time in `dir` of the [`config::data_dir`].
+fn saved_path(dir: &str, mode: &str, extension: &str) -> Result<PathBuf> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(config::data_dir()?
+        .join(dir)
+        .join(format!("{mode}-{secs}.{extension}")))
+}
+
+/// Greens of the Game Boy screen, from the darkest.
+const GAME_BOY_SHADES: [Color; 4] = [
+    Color::Rgb(15, 56, 15),
+    Color::Rgb(48, 98, 48),
+    Color::Rgb(139, 172, 15),
+    Color::Rgb(155, 188, 15),
+];
+
+/// Redraws everything in `buf` in `shades`, from the darkest, picking the
+/// one as light as each color. The terminal's own colors are kept.
+fn recolor(buf: &mut Buffer, shades: [Color; 4]) {
+    let shade = |color: Color| {
+        if color == Color::Reset {
+            return color;
+        }
+        let [r, g, b] = rgb(color).map(u32::from);
+        // perceived lightness, from 0 to 255
+        let light = (299 * r + 587 * g + 114 * b) / 1000;
+        shades[(light as usize * shades.len() / 256).min(shades.len() - 1)]
+    };
+    for cell in &mut buf.content {
+        cell.fg = shade(cell.fg);
+        cell.bg = shade(cell.bg);
+    }
+}
+
+/// Red, green and blue of `color`, as a typical terminal shows it.
+fn rgb(color: Color) -> [u8; 3] {
+    let index = match color {
+        Color::Rgb(r, g, b) => return [r, g, b],
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White | Color::Reset => 15,
+        Color::Indexed(i) => i,
+    };
+    // the 256 color palette of xterm
+    const BASIC: [[u8; 3]; 16] = [
+        [0, 0, 0],
+        [205, 0, 0],
+        [0, 205, 0],
+        [205, 205, 0],
+        [0, 0, 238],
+        [205, 0, 205],
+        [0, 205, 205],
+        [229, 229, 229],
+        [127, 127, 127],
+        [255, 0, 0],
+        [0, 255, 0],
+        [255, 255, 0],
+        [92, 92, 255],
+        [255, 0, 255],
+        [0, 255, 255],
+        [255, 255, 255],
+    ];
+    let level = |i: u8| if i == 0 { 0 } else { 55 + 40 * i };
+    match index {
+        0..16 => BASIC[index as usize],
+        16..232 => {
+            let i = index - 16;
+            [level(i / 36), level(i / 6 % 6), level(i % 6)]
+        }
+        _ => [8 + 10 * (index - 232); 3],
+    }
+}
+
+/// Colors replays before v3 drew from for every piece.
+const LEGACY_COLORS: [Color; 6] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+];
+
+/// Restores the terminal and stops the process as Ctrl+Z would outside of
+/// raw mode, taking the terminal over again once resumed with `fg`.
+#[cfg(unix)]
+fn suspend(terminal: &mut DefaultTerminal) -> Result<()> {
+    ratatui::restore();
+    // SAFETY: raising a signal has no memory safety requirements
+    if unsafe { libc::raise(libc::SIGTSTP) } != 0 {
+        warn!("failed to suspend: {}", std::io::Error::last_os_error());
+    }
+
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+    terminal.clear()?;
+    Ok(())
+}
+
+/// There's no job control to hand the terminal back to.
+#[cfg(not(unix))]
+fn suspend(_terminal: &mut DefaultTerminal) -> Result<()> {
+    Ok(())
+}
+
+/// Rows taken by a box previewing `pieces` pieces, one above the other.
+fn preview_height(pieces: usize) -> u16 {
+    if pieces == 0 {
+        return 0;
+    }
+    let cells = pieces * (PREVIEW_HEIGHT + 1) - 1;
+    cells as u16 * PREVIEW_SCALE + 2
+}
+
+/// Draws `pieces` one above the other in `block` with room for `slots` of
+/// them.
+fn render_preview(
+    block: Block<'_>,
+    pieces: &[(PieceKind, Color)],
+    slots: usize,
+    skin: &Skin,
+    area: Rect,
+    buf: &mut Buffer,
+) {
+    let inner = block.inner(area);
+    let height = slots * (PREVIEW_HEIGHT + 1) - 1;
+
+    Canvas::default()
+        .block(block)
+        .x_bounds([0.0, PREVIEW_WIDTH as f64])
+        .y_bounds([0.0, height as f64])
+        .marker(Marker::HalfBlock)
+        .paint(|ctx| {
+            for (i, (shape, color)) in pieces.iter().enumerate() {
+                // pieces with a glyph are drawn with it alone
+                if skin.glyph(shape.name()).is_some() {
+                    continue;
+                }
+                let top = (i * (PREVIEW_HEIGHT + 1)) as f64;
+                for (x, y) in preview_cells(*shape) {
+                    paint_square(ctx, x, top + y, height as f64, PREVIEW_SCALE, *color);
+                }
+            }
+        })
+        .render(area, buf);
+
+    for (i, &(shape, color)) in pieces.iter().enumerate() {
+        let Some(glyph) = skin.glyph(shape.name()) else {
+            continue;
+        };
+        let top = i * (PREVIEW_HEIGHT + 1);
+        for (x, y) in preview_cells(shape) {
+            // glyphs fill whole rows, so pieces centered on half a row are
+            // drawn from the row above
+            let left = inner.x + (x * 2.0 * f64::from(PREVIEW_SCALE)) as u16;
+            let y = (top + y as usize) as u16 * PREVIEW_SCALE;
+            for col in 0..2 * PREVIEW_SCALE {
+                for row in 0..PREVIEW_SCALE {
+                    if let Some(cell) = buf.cell_mut((left + col, inner.y + y + row)) {
+                        cell.set_char(glyph)
+                            .set_style(Style::new().fg(color).bg(Color::Reset));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Cells of `shape` as it's previewed: lying flat with as many cells as it
+/// can along the bottom, like pieces spawn in the guideline, and centered in
+/// a box [`PREVIEW_WIDTH`] by [`PREVIEW_HEIGHT`] cells, halfway between
+/// cells if need be.
+fn preview_cells(kind: PieceKind) -> Vec<(f64, f64)> {
+    let mut block = kind.block();
+    let mut flat = None;
+    for _ in 0..4 {
+        let (_, (_, bottom)) = block.bounding_box();
+        let on_bottom = block.coords().iter().filter(|c| c.1 == bottom).count();
+        if block.width() >= block.height()
+            && flat.as_ref().is_none_or(|&(_, most)| on_bottom > most)
+        {
+            flat = Some((block.clone(), on_bottom));
+        }
+        block = block.rotate();
+    }
+    let (block, _) = flat.unwrap_or((block, 0));
+
+    let block = block.normalized();
+    let dx = (PREVIEW_WIDTH as i32 - block.width()) as f64 / 2.0;
+    let dy = (PREVIEW_HEIGHT as i32 - block.height()) as f64 / 2.0;
+    block
+        .coords()
+        .iter()
+        .map(|&(x, y)| (x as f64 + dx, y as f64 + dy))
+        .collect()
+}
+
+/// Fills cell (`x`, `y`) of a board drawn at `scale` in `inner` with
+/// `glyph`, over the solid block painted there.
+fn fill_glyph(
+    buf: &mut Buffer,
+    inner: Rect,
+    x: usize,
+    y: usize,
+    scale: u16,
+    glyph: char,
+    color: Color,
+) {
+    // every cell is `scale` rows of twice as many columns
+    let (width, height) = (2 * scale, scale);
+    let (left, top) = (inner.x + x as u16 * width, inner.y + y as u16 * height);
+    for row in 0..height {
+        for col in 0..width {
+            if let Some(cell) = buf.cell_mut((left + col, top + row)) {
+                cell.set_char(glyph)
+                    .set_style(Style::new().fg(color).bg(Color::Reset));
+            }
+        }
+    }
+}
+
+/// Paints cell (`x`, `y`) of a board `height` cells high drawn at `scale`.
+pub(crate) fn fill_square(
+    ctx: &mut Context<'_>,
+    x: usize,
+    y: usize,
+    height: usize,
+    scale: u16,
+    color: Color,
+) {
+    paint_square(ctx, x as f64, y as f64, height as f64, scale, color);
+}
+
+/// Paints the square cell of a board `height` cells high drawn at `scale`
+/// from (`x`, `y`), which can be between cells.
+fn paint_square(ctx: &mut Context<'_>, x: f64, y: f64, height: f64, scale: u16, color: Color) {
+    let cx = x;
+    let cy = height - y - 1.0;
+    let line_count = 2 * scale;
+    for i in 0..line_count {
+        ctx.draw(&canvas::Line {
+            x1: cx + 1.0 / line_count as f64,
+            y1: cy + i as f64 / line_count as f64,
+            x2: cx + 1.0,
+            y2: cy + i as f64 / line_count as f64,
+            color,
+        });
+    }
+}
+
+fn fumen_tile(cell: Cell) -> Tile {
+    match cell {
+        Cell::I => Tile::piece(PieceKind::I),
+        Cell::L => Tile::piece(PieceKind::L),
+        Cell::O => Tile::piece(PieceKind::O),
+        Cell::Z => Tile::piece(PieceKind::Z),
+        Cell::T => Tile::piece(PieceKind::T),
+        Cell::J => Tile::piece(PieceKind::J),
+        Cell::S => Tile::piece(PieceKind::S),
+        Cell::Gray => Tile::garbage(),
+    }
+}
+
+impl Widget for &Tetris {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let title = if let Some(puzzle) = &self.puzzle {
+            Line::from(format!(" goal: {} ", puzzle.goal()).bold())
+        } else if let Some(trainer) = &self.trainer {
+            let (step, _) = trainer.progress();
+            let step = (step + 1).min(trainer.len());
+            Line::from(format!(" {} {step}/{} ", trainer.name(), trainer.len()).bold())
+        } else if let Some(grading) = &self.grading {
+            Line::from(vec![
+                format!(" level {} ", self.level()).bold(),
+                " grade ".into(),
+                grading.grade().to_string().blue().bold(),
+                " ".into(),
+            ])
+        } else if self.game_mode.mode() != Mode::Marathon {
+            Line::from(format!(" {} ", self.game_mode.mode().name()).bold())
+        } else if !self.config.title.is_empty() {
+            Line::from(format!(" {} ", self.config.title).bold())
+        } else {
+            Line::default()
+        };
+        let won = self.versus.as_ref().and_then(|v| v.won);
+        let solved = self.puzzle.as_ref().and_then(|p| p.solved());
+        let title_bottom = if let Some(pause) = &self.pause {
+            match pause.resume_at {
+                Some(at) => {
+                    let left = at.saturating_duration_since(Instant::now());
+                    Line::from(vec![
+                        " resuming in ".into(),
+                        (left.as_secs() + 1).to_string().blue().bold(),
+                        " ".into(),
+                    ])
+                }
+                None => Line::from(vec![
+                    " paused, ".into(),
+                    "<P>".blue().bold(),
+                    " to resume ".into(),
+                ]),
+            }
+        } else if let Some(until) = self.zone.until {
+            let left = until.saturating_sub(self.clock.elapsed());
+            Line::from(vec![
+                " zone ".into(),
+                (left.as_secs() + 1).to_string().blue().bold(),
+                "s ".into(),
+            ])
+        } else if let Some(won) = won {
+            Line::from(if won {
+                " you win! ".green().bold()
+            } else {
+                " you lose ".red().bold()
+            })
+        } else if let Some(solved) = solved {
+            Line::from(if solved {
+                " solved! ".green().bold()
+            } else {
+                " failed ".red().bold()
+            })
+        } else if let (Some(royale), Some(_)) = (&self.royale, self.topped_out) {
+            let place = format!("#{} of {}", royale.left() + 1, royale.bots.len() + 1);
+            Line::from(format!(" game over: {place} ").red().bold())
+        } else if let (Some(_), Some(reason)) = (self.topped_out, self.game_over) {
+            Line::from(format!(" game over: {reason} ").red().bold())
+        } else if let Some(puzzle) = &self.puzzle {
+            let queue: String = puzzle
+                .queue()
+                .map(|shape| format!(" {}", shape.name()))
+                .collect();
+            Line::from(vec![" next:".into(), queue.blue().bold(), " ".into()])
+        } else if let Some(trainer) = &self.trainer {
+            let accuracy = format!("{:.0}%", trainer.accuracy());
+            Line::from(vec![
+                if trainer.done() {
+                    " done! accuracy: ".into()
+                } else {
+                    " accuracy: ".into()
+                },
+                if trainer.done() {
+                    accuracy.green().bold()
+                } else {
+                    accuracy.blue().bold()
+                },
+                " ".into(),
+            ])
+        } else if let Some(banner) = self
+            .finished
+            .and_then(|_| self.game_mode.banner(&self.mode_view()))
+        {
+            banner
+        } else if let Some(stepping) = &self.stepping {
+            let buffered: String = stepping
+                .buffered
+                .iter()
+                .map(|action| match action {
+                    Action::Left => " ←",
+                    Action::Right => " →",
+                    Action::Rotate => " ↻",
+                    Action::Drop => " ↓",
+                    Action::SoftDrop => " ⇣",
+                    Action::Tick => " ·",
+                    Action::Hold => " ⇄",
+                    Action::Zone => " ◎",
+                })
+                .collect();
+            Line::from(vec![
+                " frame ".into(),
+                stepping.frames.to_string().blue().bold(),
+                buffered.into(),
+                " ".into(),
+            ])
+        } else if let Some(practice) = &self.practice {
+            let next = practice.next.map_or('?', PieceKind::name);
+            let gravity = if practice.gravity_off { "off" } else { "on" };
+            let mut line = Line::from(vec![
+                " next: ".into(),
+                next.to_string().blue().bold(),
+                " gravity: ".into(),
+                gravity.blue().bold(),
+                " ".into(),
+            ]);
+            if self.clock.speed() < FULL_SPEED {
+                line.push_span("speed: ");
+                line.push_span(format!("{}%", self.clock.speed()).blue().bold());
+                line.push_span(" ");
+            }
+            line
+        } else if let Some(progress) = self.game_mode.progress(&self.mode_view()) {
+            progress
+        } else if self.playback.is_some() {
+            Line::from(vec![
+                " replay ".into(),
+                "<Q>".blue().bold(),
+                " to quit ".into(),
+            ])
+        } else if self.score > 0 {
+            Line::from(vec![
+                " score: ".into(),
+                self.score.to_string().blue().bold(),
+                " ".into(),
+            ])
+        } else {
+            Line::from(vec![
+                " press ".into(),
+                "<Q>".blue().bold(),
+                " to quit ".into(),
+            ])
+        };
+
+        let block = Block::bordered()
+            .title(title.centered())
+            .title_bottom(title_bottom.centered())
+            .border_set(self.config.border.set());
+
+        let inner = block.inner(area);
+        let view = self.view();
+        // the board stays hidden until counting down to resume
+        let hidden = self.pause.as_ref().is_some_and(|p| p.resume_at.is_none());
+        Canvas::default()
+            .block(block)
+            .x_bounds([0.0, self.board.width() as f64])
+            .y_bounds([0.0, self.board.height() as f64])
+            .marker(Marker::HalfBlock)
+            .paint(|ctx| {
+                for x in 0..self.board.width() {
+                    for y in 0..self.board.height() {
+                        let color = if hidden {
+                            Color::Reset
+                        } else {
+                            view.color(x, y)
+                        };
+                        self.fill_square(ctx, x, y, color);
+                    }
+                }
+            })
+            .render(area, buf);
+        if !hidden {
+            for x in 0..self.board.width() {
+                for y in 0..self.board.height() {
+                    let (Layer::Stack(tile) | Layer::Active(tile)) = view.get(x, y) else {
+                        continue;
+                    };
+                    let name = tile.kind.map_or('G', PieceKind::name);
+                    let Some(glyph) = self.config.skin.glyph(name) else {
+                        continue;
+                    };
+                    fill_glyph(buf, inner, x, y, self.scale, glyph, view.color(x, y));
+                }
+            }
+            self.render_danger_zone(&view, inner, buf);
+            self.render_grid(&view, inner, buf);
+            self.render_target(inner, buf);
+            self.render_ghost(&view, inner, buf);
+            self.render_hint(&view, inner, buf);
+            self.render_pending(&view, inner, buf);
+            self.render_popups(inner, buf);
+            self.render_banner(inner, buf);
+        } else {
+            self.render_seed(inner, buf);
+            self.render_banner(inner, buf);
+        }
+    }
+}
+
+impl Tetris {
+    /// The board as it's drawn: the rows being cleared or gathered in the
+    /// zone fading, the falling piece and its ghost on top of the stack.
+    fn view(&self) -> BoardView {
+        let (board, fading) = match &self.clearing {
+            Some(clearing) => (&clearing.board, Vec::new()),
+            None if self.zone.until.is_some() => (&self.board, self.board.filled_rows()),
+            None => (&self.board, Vec::new()),
+        };
+        // the board of a clear still has the piece that locked
+        let active = match &self.clearing {
+            Some(_) => None,
+            None => board.current_block(),
+        };
+        let mut cells: Vec<Vec<Layer>> = (0..board.height())
+            .map(|y| {
+                (0..board.width())
+                    .map(|x| match *board.get(x, y) {
+                        None => Layer::Empty,
+                        Some(tile)
+                            if active
+                                .is_some_and(|b| b.coords().contains(&(x as i32, y as i32))) =>
+                        {
+                            Layer::Active(tile)
+                        }
+                        Some(tile) if fading.contains(&y) => Layer::Stack(Tile {
+                            state: TileState::Fading,
+                            ..tile
+                        }),
+                        Some(tile) => Layer::Stack(tile),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        if let (Some(block), Some(ghost), false) =
+            (active, board.ghost(), self.config.ghost == Ghost::Off)
+        {
+            let (x0, y0) = block.coords()[0];
+            let color = board
+                .get(x0 as usize, y0 as usize)
+                .map_or(Color::Gray, |tile| tile.color);
+            for &(x, y) in ghost.coords() {
+                let cell = &mut cells[y as usize][x as usize];
+                if *cell == Layer::Empty {
+                    *cell = Layer::Ghost(color);
+                }
+            }
+        }
+
+        BoardView {
+            cells,
+            gray_rows: self.gray_rows(),
+            pending: self
+                .versus
+                .as_ref()
+                .map_or(0, |versus| versus.pending_garbage as usize),
+        }
+    }
+
+    /// Draws the labels of recent clears over the board drawn in `inner`,
+    /// drifting up and fading as they age.
+    fn render_popups(&self, inner: Rect, buf: &mut Buffer) {
+        let now = self.clock.elapsed();
+        for popup in &self.popups {
+            let age = now.saturating_sub(popup.at);
+            if age >= POPUP_TIME {
+                continue;
+            }
+            let progress = age.as_secs_f64() / POPUP_TIME.as_secs_f64();
+            let rise = (progress * f64::from(POPUP_RISE)) as u16;
+            let Some(y) = (popup.row as u16 * self.scale).checked_sub(rise) else {
+                continue;
+            };
+            let text = match progress {
+                p if p < 0.5 => popup.text.clone().white().bold(),
+                p if p < 0.75 => popup.text.clone().gray(),
+                _ => popup.text.clone().dark_gray(),
+            };
+            let area = Rect::new(inner.x, inner.y + y, inner.width, 1).intersection(inner);
+            Line::from(text).centered().render(area, buf);
+        }
+    }
+
+    /// Shows the seed of the pieces above the middle of the board drawn in
+    /// `inner`, while it's hidden by a pause.
+    fn render_seed(&self, inner: Rect, buf: &mut Buffer) {
+        let y = inner.y + (inner.height / 2).saturating_sub(3);
+        let lines = [
+            Line::from(vec![" seed ".into(), self.seed.to_string().blue().bold()]),
+            Line::from(vec!["<Y>".blue().bold(), " to copy".into()]),
+        ];
+        for (i, line) in lines.into_iter().enumerate() {
+            let area = Rect::new(inner.x, y + i as u16, inner.width, 1);
+            line.centered().render(area.intersection(inner), buf);
+        }
+    }
+
+    /// Flashes the latest level up across the middle of the board drawn in
+    /// `inner` for a moment.
+    fn render_banner(&self, inner: Rect, buf: &mut Buffer) {
+        let Some((text, at)) = &self.banner else {
+            return;
+        };
+        let age = self.clock.elapsed().saturating_sub(*at);
+        if age >= BANNER_TIME {
+            return;
+        }
+        let text = format!(" {text} ").yellow().bold();
+        let text = if (age.as_millis() / BANNER_FLASH.as_millis()).is_multiple_of(2) {
+            text.reversed()
+        } else {
+            text
+        };
+        let area = Rect::new(inner.x, inner.y + inner.height / 2, inner.width, 1);
+        Line::from(text)
+            .centered()
+            .render(area.intersection(inner), buf);
+    }
+
+    /// Tints the empty cells of the top [`DANGER_ROWS`] rows of `view`,
+    /// drawn in `inner`.
+    fn render_danger_zone(&self, view: &BoardView, inner: Rect, buf: &mut Buffer) {
+        let (width, height) = (2 * self.scale, self.scale);
+        for y in 0..DANGER_ROWS.min(self.board.height()) {
+            for x in 0..self.board.width() {
+                if !matches!(view.get(x, y), Layer::Empty | Layer::Ghost(_)) {
+                    continue;
+                }
+                let area = Rect {
+                    x: inner.x + x as u16 * width,
+                    y: inner.y + y as u16 * height,
+                    width,
+                    height,
+                };
+                buf.set_style(area.intersection(inner), Style::new().bg(DANGER_COLOR));
+            }
+        }
+    }
+
+    /// Draws the configured guides on the empty cells of `view`, drawn in
+    /// `inner`.
+    fn render_grid(&self, view: &BoardView, inner: Rect, buf: &mut Buffer) {
+        if self.config.grid == Grid::Off {
+            return;
+        }
+
+        let (width, height) = (2 * self.scale, self.scale);
+        for x in 0..self.board.width() {
+            for y in 0..self.board.height() {
+                if !matches!(view.get(x, y), Layer::Empty | Layer::Ghost(_)) {
+                    continue;
+                }
+                let left = inner.x + x as u16 * width;
+                let top = inner.y + y as u16 * height;
+                for row in 0..height {
+                    for col in 0..width {
+                        let Some(cell) = buf.cell_mut((left + col, top + row)) else {
+                            continue;
+                        };
+                        match self.config.grid {
+                            Grid::Lines if col == 0 && x > 0 => {
+                                cell.set_symbol("▏").set_fg(GRID_COLOR);
+                            }
+                            Grid::Checker if (x + y) % 2 == 0 => {
+                                cell.set_bg(GRID_COLOR);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Outlines where the falling piece goes in the opener being trained,
+    /// in the color of the piece.
+    fn render_target(&self, inner: Rect, buf: &mut Buffer) {
+        let Some(trainer) = &self.trainer else {
+            return;
+        };
+        let (Some(shape), Some(target)) =
+            (trainer.next_shape(), trainer.target(self.board.height()))
+        else {
+            return;
+        };
+        let style = Style::new().fg(shape.color());
+
+        let (width, height) = (2 * self.scale, self.scale);
+        for (x, y) in target {
+            if self.board.get(x as usize, y as usize).is_some() {
+                continue;
+            }
+            let left = inner.x + x as u16 * width;
+            let top = inner.y + y as u16 * height;
+            for row in 0..height {
+                for col in 0..width {
+                    if let Some(cell) = buf.cell_mut((left + col, top + row)) {
+                        cell.set_symbol(outline_symbol(col, row, width, height))
+                            .set_style(style);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draws the ghost cells of `view` in the configured style, over the
+    /// board drawn in `inner`.
+    fn render_ghost(&self, view: &BoardView, inner: Rect, buf: &mut Buffer) {
+        // every cell is `scale` rows of twice as many columns
+        let (width, height) = (2 * self.scale, self.scale);
+        for x in 0..self.board.width() {
+            for y in 0..self.board.height() {
+                let Layer::Ghost(color) = view.get(x, y) else {
+                    continue;
+                };
+                let left = inner.x + x as u16 * width;
+                let top = inner.y + y as u16 * height;
+                for row in 0..height {
+                    for col in 0..width {
+                        let symbol = match self.config.ghost {
+                            Ghost::Outline => outline_symbol(col, row, width, height),
+                            Ghost::Dim => "░",
+                            Ghost::Char(c) if col % 2 == 0 => &c.to_string(),
+                            _ => " ",
+                        };
+                        if let Some(cell) = buf.cell_mut((left + col, top + row)) {
+                            cell.set_symbol(symbol).set_style(Style::new().fg(color));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Numbers the empty cells of `view`, drawn in `inner`, that the pieces
+    /// of the plan would fill in the order they come. The plan is only
+    /// shown up to its first clear, since the rows move after it.
+    fn render_hint(&self, view: &BoardView, inner: Rect, buf: &mut Buffer) {
+        let Some(hint) = &self.hint else {
+            return;
+        };
+        let (width, height) = (2 * self.scale, self.scale);
+        let shown = hint
+            .line
+            .iter()
+            .position(|step| step.lines > 0)
+            .map_or(hint.line.len(), |clear| clear + 1);
+        for (i, (step, kind)) in hint.line.iter().zip(&hint.pieces).take(shown).enumerate() {
+            let number = (i + 1).to_string();
+            for &(x, y) in step.block.coords() {
+                let (x, y) = (x as usize, y as usize);
+                if !matches!(view.get(x, y), Layer::Empty | Layer::Ghost(_)) {
+                    continue;
+                }
+                let left = inner.x + x as u16 * width;
+                let top = inner.y + y as u16 * height;
+                for row in 0..height {
+                    for col in 0..width {
+                        let symbol = if col % 2 == 0 { number.as_str() } else { " " };
+                        if let Some(cell) = buf.cell_mut((left + col, top + row)) {
+                            cell.set_symbol(symbol)
+                                .set_style(Style::new().fg(kind.color()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Marks the rows of garbage waiting to rise on the left border of the
+    /// board drawn in `inner`, from the bottom.
+    fn render_pending(&self, view: &BoardView, inner: Rect, buf: &mut Buffer) {
+        let Some(x) = inner.x.checked_sub(1) else {
+            return;
+        };
+        let rows = view.pending.min(self.board.height()) as u16 * self.scale;
+        let bottom = inner.y + self.board.height() as u16 * self.scale;
+        for y in bottom - rows..bottom {
+            if let Some(cell) = buf.cell_mut((x, y)) {
+                cell.set_symbol("┃").set_fg(Color::Red);
+            }
+        }
+    }
+}
+
+/// Part of the outline of a cell `width` columns by `height` rows at column
+/// `col` and row `row` of it.
+fn outline_symbol(col: u16, row: u16, width: u16, height: u16) -> &'static str {
+    if height == 1 {
+        return match col {
+            0 => "[",
+            _ if col == width - 1 => "]",
+            _ => " ",
+        };
+    }
+
+    let (top, bottom) = (row == 0, row == height - 1);
+    let (left, right) = (col == 0, col == width - 1);
+    match (top, bottom, left, right) {
+        (true, _, true, _) => "┌",
+        (true, _, _, true) => "┐",
+        (_, true, true, _) => "└",
+        (_, true, _, true) => "┘",
+        (true, _, _, _) | (_, true, _, _) => "─",
+        (_, _, true, _) | (_, _, _, true) => "│",
+        _ => " ",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modes::SURVIVAL_START;
+
+    /// Puts an I in play at the top of `tetris` in place of the falling
+    /// piece.
+    fn spawn_i(tetris: &mut Tetris) {
+        tetris.board.remove_block();
+        tetris.piece = PieceKind::I;
+        tetris
+            .board
+            .spawn(PieceKind::I.block(), Tile::piece(PieceKind::I))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        const ACTIONS: [Action; 7] = [
+            Action::Left,
+            Action::Right,
+            Action::Rotate,
+            Action::Drop,
+            Action::Tick,
+            Action::Hold,
+            Action::Zone,
+        ];
+
+        for seed in 0..20 {
+            let mut inputs = ChaCha8Rng::seed_from_u64(seed);
+            let config = Config {
+                line_clear_delay: seed % 2 * 300,
+                zone: seed % 3 == 0,
+                ..Default::default()
+            };
+            let mut tetris = Tetris::new(10, 20, 1).with_seed(seed).with_config(config);
+            for _ in 0..2000 {
+                tetris.apply(*ACTIONS.choose(&mut inputs).unwrap());
+                if let Err(e) = tetris.check_invariants() {
+                    panic!("seed {seed}: {e}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hold() {
+        let holds = |hold| {
+            let config = Config {
+                hold,
+                ..Default::default()
+            };
+            let mut tetris = Tetris::new(10, 20, 1).with_seed(0).with_config(config);
+            tetris.apply(Action::Tick);
+            let first = tetris.piece;
+            let held = tetris.hold().unwrap();
+            if held {
+                assert_eq!(tetris.hold.piece, Some(first));
+            }
+            [held, tetris.hold().unwrap()]
+        };
+
+        assert_eq!(holds(HoldMode::Standard), [true, false]);
+        assert_eq!(holds(HoldMode::Unlimited), [true, true]);
+        assert_eq!(holds(HoldMode::Disabled), [false, false]);
+
+        // locking the piece allows holding again
+        let mut tetris = Tetris::new(10, 20, 1).with_seed(0);
+        tetris.apply(Action::Tick);
+        assert!(tetris.hold().unwrap());
+        tetris.apply(Action::Drop);
+        assert!(tetris.hold().unwrap());
+    }
+
+    #[test]
+    fn test_recolor() {
+        let mut buf = Buffer::empty(Rect::new(0, 0, 3, 1));
+        buf[(0, 0)].set_fg(Color::Black);
+        buf[(1, 0)].set_fg(PieceKind::O.color()).set_bg(Color::Blue);
+        recolor(&mut buf, GAME_BOY_SHADES);
+        assert_eq!(buf[(0, 0)].fg, GAME_BOY_SHADES[0]);
+        assert_eq!(buf[(1, 0)].fg, GAME_BOY_SHADES[2]);
+        assert_eq!(buf[(1, 0)].bg, GAME_BOY_SHADES[0]);
+        assert_eq!(buf[(2, 0)].fg, Color::Reset);
+    }
+
+    #[test]
+    fn test_board_view() {
+        let row: Vec<_> = (0..10).map(|x| x != 0).collect();
+        let mut tetris = Tetris::new(10, 20, 1).with_field(&[row]).unwrap();
+        spawn_i(&mut tetris);
+
+        let view = tetris.view();
+        let active = Layer::Active(Tile::piece(PieceKind::I));
+        assert_eq!(view.get(4, 0), active);
+        assert_eq!(view.get(7, 0), active);
+        assert_eq!(view.get(4, 18), Layer::Ghost(Color::Cyan));
+        assert_eq!(view.get(3, 18), Layer::Empty);
+        assert_eq!(view.get(0, 19), Layer::Empty);
+        assert_eq!(view.get(1, 19), Layer::Stack(Tile::garbage()));
+        assert_eq!(view.color(1, 19), Color::Gray);
+
+        tetris.config.ghost = Ghost::Off;
+        assert_eq!(tetris.view().get(4, 18), Layer::Empty);
+    }
+
+    #[test]
+    fn test_danger_zone() {
+        let mut tetris = Tetris::new(10, 20, 1);
+        tetris.board.set(0, 2, Tile::garbage());
+        let area = Rect::new(0, 0, 22, 22);
+        let mut buf = Buffer::empty(area);
+        tetris.render(area, &mut buf);
+        // inside the border, every cell is two columns wide
+        assert_eq!(buf[(3, 1)].bg, DANGER_COLOR);
+        assert_eq!(buf[(3, 4)].bg, DANGER_COLOR);
+        assert_ne!(buf[(1, 3)].bg, DANGER_COLOR);
+        assert_ne!(buf[(3, 5)].bg, DANGER_COLOR);
+    }
+
+    #[test]
+    fn test_piece_colors() {
+        let mut tetris = Tetris::new(10, 20, 1);
+        for _ in 0..20 {
+            tetris.apply(Action::Tick);
+            if let Some(block) = tetris.board.current_block() {
+                let (x, y) = block.coords()[0];
+                let tile = *tetris.board.get(x as usize, y as usize);
+                assert_eq!(tile, Some(Tile::piece(tetris.piece)));
+            }
+            tetris.apply(Action::Drop);
+        }
+        assert_eq!(PieceKind::T.color(), Color::Magenta);
+    }
+
+    #[test]
+    fn test_next_queue() {
+        let pieces = |next| {
+            let config = Config {
+                next,
+                ..Default::default()
+            };
+            let mut tetris = Tetris::new(10, 20, 1).with_seed(7).with_config(config);
+            let mut pieces = Vec::new();
+            for _ in 0..10 {
+                tetris.apply(Action::Drop);
+                assert_eq!(tetris.queue.len(), next);
+                if let Some(&shape) = tetris.queue.front() {
+                    pieces.push(shape);
+                }
+            }
+            pieces
+        };
+
+        // previewing doesn't change the sequence
+        let previewed = pieces(6);
+        assert!(pieces(0).is_empty());
+        assert_eq!(pieces(1), previewed);
+    }
+
+    #[test]
+    fn test_preview_cells() {
+        let cells = |shape| {
+            let mut cells = preview_cells(shape);
+            cells.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            cells
+        };
+        assert_eq!(
+            cells(PieceKind::I),
+            [(0.0, 0.5), (1.0, 0.5), (2.0, 0.5), (3.0, 0.5)]
+        );
+        assert_eq!(
+            cells(PieceKind::O),
+            [(1.0, 0.0), (1.0, 1.0), (2.0, 0.0), (2.0, 1.0)]
+        );
+        // J, L and T lie flat on three cells, like they spawn in the guideline
+        assert_eq!(
+            cells(PieceKind::J),
+            [(0.5, 0.0), (0.5, 1.0), (1.5, 1.0), (2.5, 1.0)]
+        );
+        assert_eq!(
+            cells(PieceKind::L),
+            [(0.5, 1.0), (1.5, 1.0), (2.5, 0.0), (2.5, 1.0)]
+        );
+        assert_eq!(
+            cells(PieceKind::T),
+            [(0.5, 1.0), (1.5, 0.0), (1.5, 1.0), (2.5, 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_bag() {
+        let config = Config {
+            randomizer: Randomizer::Bag,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1).with_seed(7).with_config(config);
+        let pieces: Vec<_> = (0..21).map(|_| tetris.draw_piece()).collect();
+        for bag in pieces.chunks(7) {
+            let mut names: Vec<_> = bag.iter().map(|&shape| shape.name()).collect();
+            names.sort();
+            assert_eq!(names, ['I', 'J', 'L', 'O', 'S', 'T', 'Z']);
+        }
+    }
+
+    #[test]
+    fn test_bag_remaining() {
+        let config = Config {
+            randomizer: Randomizer::Bag,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1).with_seed(7).with_config(config);
+        assert_eq!(tetris.bag_remaining(), Some(PieceKind::ALL.to_vec()));
+
+        let mut dealt = Vec::new();
+        for _ in 0..10 {
+            tetris.board.remove_block();
+            tetris.spawn_next().unwrap();
+            dealt.push(tetris.piece);
+            let remaining = tetris.bag_remaining().unwrap();
+            let in_bag = dealt.len() % 7;
+            if in_bag == 0 {
+                assert_eq!(remaining.len(), 7);
+                continue;
+            }
+            // what's left is what the current bag hasn't dealt yet
+            assert_eq!(remaining.len(), 7 - in_bag);
+            let bag_dealt = &dealt[dealt.len() - in_bag..];
+            assert!(remaining.iter().all(|kind| !bag_dealt.contains(kind)));
+        }
+
+        let tetris = Tetris::new(10, 20, 1);
+        assert_eq!(tetris.bag_remaining(), None);
+    }
+
+    #[test]
+    fn test_tgm_randomizer() {
+        let config = Config {
+            randomizer: Randomizer::Tgm,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1).with_seed(3).with_config(config);
+        let pieces: Vec<_> = (0..700).map(|_| tetris.draw_piece()).collect();
+        assert!(!matches!(
+            pieces[0],
+            PieceKind::O | PieceKind::S | PieceKind::Z
+        ));
+        assert_eq!(tetris.history.len(), TGM_HISTORY.len());
+        // repeats are rarer than the one in seven of a plain random pick
+        let repeats = pieces.windows(2).filter(|w| w[0] == w[1]).count();
+        assert!(repeats < 30, "{repeats} repeats");
+    }
+
+    #[test]
+    fn test_nes_randomizer() {
+        let config = Config {
+            randomizer: Randomizer::Nes,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1).with_seed(5).with_config(config);
+        let pieces: Vec<_> = (0..700).map(|_| tetris.draw_piece()).collect();
+        assert_eq!(tetris.history.len(), 1);
+        // a repeat needs both rolls to land on it, about one time in 28
+        let repeats = pieces.windows(2).filter(|w| w[0] == w[1]).count();
+        assert!((5..60).contains(&repeats), "{repeats} repeats");
+        for kind in PieceKind::ALL {
+            assert!(pieces.contains(&kind));
+        }
+    }
+
+    #[test]
+    fn test_drought() {
+        let mut tetris = Tetris::new(10, 20, 1).with_mode(Mode::Practice);
+        for shape in [PieceKind::I, PieceKind::O, PieceKind::T, PieceKind::S] {
+            tetris.practice.as_mut().unwrap().next = Some(shape);
+            tetris.board.remove_block();
+            tetris.spawn_next().unwrap();
+        }
+        assert_eq!(tetris.drought, 3);
+        tetris.practice.as_mut().unwrap().next = Some(PieceKind::I);
+        tetris.board.remove_block();
+        tetris.spawn_next().unwrap();
+        assert_eq!(tetris.drought, 0);
+    }
+
+    #[test]
+    fn test_drop_scoring() {
+        let config = Config {
+            scoring: Scoring::Guideline,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1).with_config(config);
+        tetris.piece = PieceKind::O;
+        tetris
+            .board
+            .spawn(PieceKind::O.block(), Tile::piece(PieceKind::O))
+            .unwrap();
+
+        // soft drops move a row down and leave the piece falling
+        tetris.apply(Action::SoftDrop);
+        tetris.apply(Action::SoftDrop);
+        assert_eq!(tetris.score, 2);
+        assert_eq!(
+            tetris.board.current_block().map(|b| b.bounding_box().0 .1),
+            Some(2)
+        );
+        // the O falls the 16 rows left to the floor
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.score, 2 + 2 * 16);
+
+        // level scoring counts drops too, but they don't speed gravity up
+        let mut tetris = Tetris::new(10, 20, 1);
+        let gravity = tetris.gravity();
+        tetris.apply(Action::Tick);
+        tetris.apply(Action::SoftDrop);
+        tetris.apply(Action::Drop);
+        assert!(tetris.score > 1);
+        assert_eq!(tetris.score, tetris.dropped);
+        assert_eq!(tetris.gravity(), gravity);
+    }
+
+    #[test]
+    fn test_score_multiplier() {
+        // an I piece spawns flat over columns 4 to 7
+        let row = (0..10).map(|x| !(4..8).contains(&x)).collect();
+        let mut tetris = Tetris::new(10, 20, 1).with_field(&[row]).unwrap();
+        tetris.lines = 10;
+        spawn_i(&mut tetris);
+
+        assert_eq!(tetris.multiplier(), 2);
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.lines, 11);
+        assert_eq!(tetris.score - tetris.dropped, 2);
+    }
+
+    #[test]
+    fn test_zone() {
+        let row: Vec<_> = (0..10).map(|x| !(4..8).contains(&x)).collect();
+        let config = Config {
+            zone: true,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1)
+            .with_config(config)
+            .with_field(&[row.clone(), row])
+            .unwrap();
+        let drop_i = |tetris: &mut Tetris| {
+            spawn_i(tetris);
+            tetris.apply(Action::Drop);
+        };
+
+        // not charged enough yet
+        tetris.apply(Action::Zone);
+        assert_eq!(tetris.zone.until, None);
+        tetris.zone.meter = ZONE_METER / 4 - 1;
+        drop_i(&mut tetris);
+        assert_eq!(tetris.lines, 1);
+        assert_eq!(tetris.zone.meter, ZONE_METER / 4);
+
+        tetris.apply(Action::Zone);
+        assert_eq!(tetris.zone.meter, 0);
+        assert!(tetris.zone.until.is_some());
+
+        // the row gathers at the bottom instead of clearing
+        drop_i(&mut tetris);
+        assert_eq!(tetris.lines, 1);
+        assert_eq!(tetris.board.filled_rows(), [19]);
+
+        tetris.apply(Action::Tick);
+        assert_eq!(tetris.zone.until, None);
+        assert_eq!(tetris.lines, 2);
+        assert_eq!(tetris.score - tetris.dropped, 2);
+        assert!(tetris.board.filled_rows().is_empty());
+        assert!(tetris.board.current_block().is_some());
+    }
+
+    #[test]
+    fn test_apply_item() {
+        let row: Vec<_> = (0..10).map(|x| x != 3).collect();
+        let mut tetris = Tetris::new(10, 20, 1).with_field(&[row]).unwrap();
+        let filled = |tetris: &Tetris| {
+            (0..20)
+                .filter(|&y| (0..10).any(|x| tetris.board.get(x, y).is_some()))
+                .count()
+        };
+        tetris.board.remove_block();
+
+        tetris.apply_item(Item::AddLine).unwrap();
+        assert_eq!(filled(&tetris), 2);
+        tetris.apply_item(Item::ClearLine).unwrap();
+        assert_eq!(filled(&tetris), 1);
+        assert_eq!(tetris.board.get(3, 19), &None);
+
+        // scrambling keeps every row as full as it was
+        tetris.apply_item(Item::Scramble).unwrap();
+        let cells = (0..10).filter(|&x| tetris.board.get(x, 19).is_some());
+        assert_eq!(cells.count(), 9);
+
+        tetris.queue.push_back(PieceKind::I);
+        tetris.apply_item(Item::Swap).unwrap();
+        assert_eq!(tetris.queue.len(), 1);
+    }
+
+    #[test]
+    fn test_map() {
+        let map: Map = "board:\nX.........\n#XXXXXXXXX\n".parse().unwrap();
+        let tetris = Tetris::new(10, 20, 1).with_map(&map).unwrap();
+        assert_eq!(tetris.board.get(0, 18), &Some(Tile::garbage()));
+        assert!(tetris
+            .board
+            .get(0, 19)
+            .is_some_and(|tile| tile.state == TileState::Indestructible));
+        assert!(tetris.board.filled_rows().is_empty());
+
+        let wide: Map = "board:\n...........\n".parse().unwrap();
+        assert!(Tetris::new(10, 20, 1).with_map(&wide).is_err());
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut tetris = Tetris::new(10, 20, 1).with_mode(Mode::Practice);
+        let filled = |tetris: &Tetris| {
+            (0..20)
+                .flat_map(|y| (0..10).map(move |x| (x, y)))
+                .filter(|&(x, y)| tetris.board.get(x, y).is_some())
+                .count()
+        };
+        tetris.apply(Action::Tick);
+        tetris.clock.skip(Duration::from_secs(1));
+        tetris.apply(Action::Drop);
+        assert_eq!(filled(&tetris), 8);
+
+        // back to just before the drop, then to before the first spawn
+        tetris.practice_key('b');
+        assert_eq!(filled(&tetris), 4);
+        assert!(tetris.board.current_block().is_some());
+        assert_eq!(tetris.practice.as_ref().unwrap().history.len(), 1);
+        tetris.practice_key('b');
+        assert_eq!(filled(&tetris), 0);
+        tetris.practice_key('b');
+        assert_eq!(filled(&tetris), 0);
+
+        // only the last 10 seconds are kept
+        tetris.apply(Action::Tick);
+        tetris.clock.skip(Duration::from_secs(20));
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.practice.as_ref().unwrap().rewind.len(), 1);
+    }
+
+    #[test]
+    fn test_slow_motion() {
+        let mut tetris = Tetris::new(10, 20, 1).with_mode(Mode::Practice);
+        tetris.practice_key('m');
+        assert_eq!(tetris.clock.speed(), 50);
+        tetris.practice_key('m');
+        assert_eq!(tetris.clock.speed(), 25);
+        tetris.practice_key('m');
+        assert_eq!(tetris.clock.speed(), FULL_SPEED);
+
+        let slowed = Tetris::new(10, 20, 1).with_speed(50);
+        assert!(slowed.result().is_none());
+        assert!(Tetris::new(10, 20, 1).result().is_some());
+    }
+
+    #[test]
+    fn test_old_replays() {
+        let play = |version| {
+            let mut header = Tetris::new(10, 20, 1).record().replay().unwrap().header;
+            header.version = version;
+            let replay = Replay {
+                header,
+                inputs: Vec::new(),
+            };
+            Tetris::from_replay(replay, 1).unwrap()
+        };
+        let turned = |version| {
+            let mut tetris = play(version);
+            tetris.spawn(PieceKind::I).unwrap();
+            tetris.apply(Action::SoftDrop);
+            tetris.apply(Action::SoftDrop);
+            tetris.apply(Action::Rotate);
+            let mut cells = tetris.board.current_block().unwrap().coords().to_vec();
+            cells.sort();
+            cells
+        };
+
+        // before v2 pieces locked as soon as they landed
+        for (version, locks) in [(1, 1), (REPLAY_VERSION, 0)] {
+            let mut tetris = play(version);
+            tetris.apply(Action::Tick);
+            for _ in 0..20 {
+                tetris.apply(Action::SoftDrop);
+            }
+            assert_eq!(tetris.locks.len(), locks);
+        }
+
+        // before v3 a color was drawn after every piece
+        let mut tetris = play(2);
+        tetris.apply(Action::Tick);
+        let mut rng = ChaCha8Rng::seed_from_u64(tetris.seed);
+        assert_eq!(tetris.piece, *PieceKind::ALL.choose(&mut rng).unwrap());
+        let (x, y) = tetris.board.current_block().unwrap().coords()[0];
+        let color = *LEGACY_COLORS.choose(&mut rng).unwrap();
+        assert_eq!(
+            tetris.board.get(x as usize, y as usize).unwrap().color,
+            color
+        );
+        assert_eq!(tetris.queue[0], *PieceKind::ALL.choose(&mut rng).unwrap());
+
+        // before v4 pieces turned about their first cell
+        assert_eq!(turned(3), [(5, 1), (5, 2), (5, 3), (5, 4)]);
+        assert_eq!(turned(REPLAY_VERSION), [(6, 1), (6, 2), (6, 3), (6, 4)]);
+    }
+
+    #[test]
+    fn test_replay_mode() {
+        let recorded = Tetris::new(10, 20, 1)
+            .with_mode(Mode::Sprint)
+            .with_line_goal(20)
+            .record();
+        let tetris = Tetris::from_replay(recorded.replay().unwrap(), 1).unwrap();
+        assert_eq!(tetris.game_mode.mode(), Mode::Sprint);
+        assert_eq!(tetris.line_goal, 20);
+
+        // a dig is dug from the same cheese
+        let recorded = Tetris::new(10, 20, 1)
+            .with_seed(7)
+            .with_mode(Mode::Dig)
+            .record();
+        let tetris = Tetris::from_replay(recorded.replay().unwrap(), 1).unwrap();
+        assert_eq!(tetris.game_mode.mode(), Mode::Dig);
+        assert_eq!(tetris.board_text(), recorded.board_text());
+    }
+
+    #[test]
+    fn test_replay_frames() {
+        let input = |time, action| Input { time, action };
+        let replay = Replay {
+            header: Tetris::new(10, 20, 1).record().replay().unwrap().header,
+            inputs: vec![
+                input(0, Action::Tick),
+                input(150, Action::Left),
+                input(300, Action::Drop),
+            ],
+        };
+        let mut tetris = Tetris::from_replay(replay, 1).unwrap();
+        let frames = tetris.replay_frames(10);
+
+        let shown: Vec<_> = frames.iter().map(|(_, shown)| shown.as_millis()).collect();
+        assert_eq!(shown, [200, 100, 100 + LAST_FRAME_TIME.as_millis()]);
+        assert!(frames[1].0[19].iter().all(Option::is_none));
+        assert!(frames[2].0[19].iter().any(Option::is_some));
+    }
+
+    #[test]
+    fn test_board_text() {
+        let text = "8 8 8 8 8 8 8 8 0 9;\n1 2 3 4 5 6 7 0 0 9;\n";
+        let mut tetris = Tetris::new(10, 20, 1).with_board_text(text).unwrap();
+        assert!(tetris
+            .board
+            .get(9, 19)
+            .is_some_and(|tile| tile.state == TileState::Indestructible));
+        assert_eq!(tetris.board.get(0, 18), &Some(Tile::garbage()));
+        tetris.apply(Action::Tick);
+        assert!(tetris.board.current_block().is_some());
+
+        // the falling piece isn't part of the board
+        let dumped = tetris.board_text();
+        assert!(dumped.starts_with(&"0 0 0 0 0 0 0 0 0 0;\n".repeat(18)));
+        assert!(dumped.ends_with(text));
+
+        assert!(Tetris::new(10, 20, 1).with_board_text("1 1;").is_err());
+        assert!(Tetris::new(10, 20, 1)
+            .with_board_text("1 1 1 1 1 1 1 1 1 10;")
+            .is_err());
+    }
+
+    #[test]
+    fn test_screenshot_cells() {
+        let map: Map = "board:\nX.........\n#XXXXXXXXX\n".parse().unwrap();
+        let tetris = Tetris::new(10, 20, 1).with_map(&map).unwrap();
+        let cells = tetris.screenshot_cells();
+        assert_eq!((cells.len(), cells[0].len()), (20, 10));
+        assert_eq!(cells[18][0], Some([229, 229, 229]));
+        assert_eq!(cells[18][1], None);
+        assert_eq!(cells[19][0], Some(rgb(SOLID_COLOR)));
+
+        assert_eq!(rgb(Color::Indexed(208)), [255, 135, 0]);
+        assert_eq!(rgb(Color::Indexed(240)), [88, 88, 88]);
+        assert_eq!(rgb(Color::Cyan), [0, 205, 205]);
+    }
+
+    #[test]
+    fn test_dig() {
+        let config = Config {
+            messiness: 0,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1)
+            .with_config(config)
+            .with_mode(Mode::Dig);
+        assert_eq!(tetris.garbage_rows(), DIG_ROWS);
+        // one straight well
+        let hole = (0..10)
+            .find(|&x| tetris.board.get(x, 19).is_none())
+            .unwrap();
+        assert!((10..20).all(|y| tetris.board.get(hole, y).is_none()));
+
+        for _ in 1..DIG_ROWS {
+            tetris.board.clear_row(19);
+        }
+        tetris.board.set(hole, 19, Tile::piece(PieceKind::I));
+        let lines = tetris.board.clear_filled_rows();
+        tetris.count_lines(lines);
+        assert_eq!(tetris.garbage_rows(), 0);
+        assert_eq!(tetris.game_over, Some(GameOver::Goal));
+        assert!(tetris.result().unwrap().goal_time.is_some());
+    }
+
+    #[test]
+    fn test_handicap() {
+        let mut tetris = Tetris::new(10, 20, 1).with_config(Config::default());
+        tetris.add_cheese(3);
+        assert_eq!(tetris.garbage_rows(), 3);
+        // the top row is always left free
+        let mut tetris = Tetris::new(10, 20, 1).with_config(Config::default());
+        tetris.add_cheese(50);
+        assert_eq!(tetris.garbage_rows(), 19);
+    }
+
+    #[test]
+    fn test_personal_best() {
+        let best = ModeStats {
+            games: 1,
+            best_score: 10,
+            best_time: None,
+        };
+        let mut tetris = Tetris::new(10, 20, 1).with_personal_best(Some(&best));
+        tetris.score = 10;
+        tetris.top_out(GameOver::BlockOut);
+        assert_eq!(tetris.banner, None);
+
+        let mut tetris = Tetris::new(10, 20, 1).with_personal_best(Some(&best));
+        tetris.score = 11;
+        tetris.top_out(GameOver::BlockOut);
+        assert_eq!(tetris.banner.unwrap().0, "NEW PERSONAL BEST");
+    }
+
+    #[test]
+    fn test_line_goal() {
+        let mut tetris = Tetris::new(10, 20, 1)
+            .with_mode(Mode::Sprint)
+            .with_line_goal(20);
+        tetris.count_lines(4);
+        assert_eq!(tetris.game_over, None);
+        tetris.lines = 19;
+        tetris.count_lines(1);
+        assert_eq!(tetris.game_over, Some(GameOver::Goal));
+        let result = tetris.result().unwrap();
+        assert_eq!(result.mode, "sprint 20");
+        assert!(result.goal_time.is_some());
+
+        let sprint = Tetris::new(10, 20, 1).with_mode(Mode::Sprint);
+        assert_eq!(sprint.stats_name(), "sprint");
+    }
+
+    #[test]
+    fn test_start_level() {
+        let mut tetris = Tetris::new(10, 20, 1)
+            .with_config(Config::default())
+            .with_start_level(3);
+        assert_eq!(tetris.level(), 3);
+        assert!(tetris.gravity() < Tetris::new(10, 20, 1).gravity());
+        tetris.count_lines(1);
+        assert_eq!(tetris.score, 3);
+        tetris.count_lines(9);
+        assert_eq!(tetris.level(), 4);
+        assert_eq!(tetris.stats_name(), "marathon level 3");
+
+        // replays start where the command line lets games start
+        let mut replay = tetris.record().replay().unwrap();
+        assert!(Tetris::from_replay(replay.clone(), 1).is_ok());
+        replay.header.start_level = 0;
+        assert!(Tetris::from_replay(replay, 1).is_err());
+    }
+
+    #[test]
+    fn test_b_type() {
+        let mut tetris = Tetris::new(10, 20, 1)
+            .with_config(Config::default())
+            .with_mode(Mode::BType)
+            .with_start_level(5)
+            .with_garbage_height(3);
+        assert_eq!(tetris.garbage_rows(), B_TYPE_HEIGHTS[3]);
+        tetris.count_lines(4);
+        assert_eq!(tetris.score, 20);
+        tetris.lines = B_TYPE_LINES - 1;
+        tetris.count_lines(1);
+        assert_eq!(tetris.game_over, Some(GameOver::Goal));
+        assert_eq!(tetris.result().unwrap().mode, "b-type level 5 height 3");
+    }
+
+    #[test]
+    fn test_survival() {
+        let mut tetris = Tetris::new(10, 20, 1).with_mode(Mode::Survival);
+        tetris.apply(Action::Tick);
+        tetris.clock.skip(SURVIVAL_START);
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.garbage_rows(), 1);
+        tetris.top_out(GameOver::BlockOut);
+        assert_eq!(tetris.result().unwrap().score, tetris.game_time().as_secs());
+    }
+
+    #[test]
+    fn test_royale() {
+        let mut tetris = Tetris::new(10, 20, 1)
+            .with_mode(Mode::Royale)
+            .with_bots(3)
+            .with_difficulty(Difficulty::Easy);
+        let slowest = Duration::from_millis(Difficulty::Easy.skill().reaction.end);
+        tetris.apply(Action::Tick);
+        let royale = tetris.royale.as_mut().unwrap();
+        assert_eq!(royale.bots.len(), 3);
+        // the player only attacks bots
+        royale.attack(None, 4);
+        assert_eq!(royale.bots.iter().map(Bot::pending).sum::<u32>(), 4);
+        assert_eq!(royale.pending_garbage, 0);
+        // garbage from the bots rises after the player's next lock
+        royale.pending_garbage = 2;
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.garbage_rows(), 2);
+
+        tetris.clock.skip(slowest);
+        tetris.update_royale();
+        let royale = tetris.royale.as_mut().unwrap();
+        assert!(royale.bots.iter().all(|bot| !bot.board().is_empty()));
+        assert_eq!(royale.left(), 3);
+
+        // outlasting every bot wins
+        for bot in &mut royale.bots {
+            bot.receive(100);
+        }
+        tetris.clock.skip(slowest);
+        tetris.update_royale();
+        assert_eq!(tetris.royale.as_ref().unwrap().left(), 0);
+        assert_eq!(tetris.game_over, Some(GameOver::Won));
+        assert!(tetris.finished.is_some());
+
+        // the same seed attacks the same bots
+        let royale = |seed| {
+            let mut tetris = Tetris::new(10, 20, 1)
+                .with_seed(seed)
+                .with_mode(Mode::Royale);
+            let royale = tetris.royale.as_mut().unwrap();
+            for _ in 0..20 {
+                royale.attack(None, 1);
+            }
+            royale.bots.iter().map(Bot::pending).collect::<Vec<_>>()
+        };
+        assert_eq!(royale(7), royale(7));
+    }
+
+    #[test]
+    fn test_hint() {
+        let mut tetris = Tetris::new(10, 20, 1).with_config(Config {
+            plan: true,
+            ..Config::default()
+        });
+        tetris.apply(Action::Tick);
+        tetris.update_hint();
+        let hint = tetris.hint.as_ref().unwrap();
+        assert_eq!(hint.pieces[0], tetris.piece);
+        assert_eq!(hint.line.len(), PLAN_PIECES);
+        // on an empty board the falling piece goes to the floor
+        let planned = hint.line[0].block.clone();
+        assert!(planned.coords().iter().any(|&(_, y)| y == 19));
+        // moving the falling piece doesn't change the plan
+        tetris.apply(Action::Left);
+        tetris.update_hint();
+        assert_eq!(tetris.hint.as_ref().unwrap().line[0].block, planned);
+
+        // the plan moves on with the pieces
+        tetris.apply(Action::Drop);
+        tetris.update_hint();
+        assert_eq!(tetris.hint.as_ref().unwrap().pieces[0], tetris.piece);
+
+        tetris.config.plan = false;
+        tetris.update_hint();
+        assert!(tetris.hint.is_none());
+    }
+
+    #[test]
+    fn test_seed() {
+        let dig = || {
+            Tetris::new(10, 20, 1)
+                .with_seed(42)
+                .with_config(Config::default())
+                .with_mode(Mode::Dig)
+        };
+        let (first, second) = (dig(), dig());
+        assert_eq!(first.board_text(), second.board_text());
+        assert_eq!(first.result().unwrap().seed, 42);
+    }
+
+    #[test]
+    fn test_level_up_banner() {
+        let mut tetris = Tetris::new(10, 20, 1);
+        tetris.count_lines(4);
+        assert_eq!(tetris.banner, None);
+
+        tetris.lines = 8;
+        tetris.count_lines(2);
+        assert_eq!(tetris.banner.as_ref().unwrap().0, "LEVEL 2");
+
+        // 5 points short of the fastest gravity with the default config
+        tetris.score = 25;
+        tetris.count_lines(4);
+        assert_eq!(tetris.banner.as_ref().unwrap().0, "MAX SPEED");
+
+        tetris.banner = None;
+        tetris.count_lines(4);
+        assert_eq!(tetris.banner, None);
+    }
+
+    #[test]
+    fn test_pace() {
+        let mut pace = Pace::default();
+        pace.add(Duration::from_millis(500), 1);
+        pace.add(Duration::from_millis(900), 1);
+        pace.add(Duration::from_millis(2100), 3);
+        assert_eq!(pace.samples(Duration::from_millis(3000)), [2, 0, 3]);
+        // the current second isn't over yet
+        assert_eq!(pace.samples(Duration::from_millis(2900)), [2, 0]);
+
+        pace.add(Duration::from_secs(70), 1);
+        assert_eq!(pace.seconds.len(), 1);
+        let samples = pace.samples(Duration::from_secs(71));
+        assert_eq!(samples.len(), PACE_SECONDS as usize);
+        assert_eq!(samples.last(), Some(&1));
+    }
+
+    #[test]
+    fn test_finesse() {
+        let mut tetris = Tetris::new(10, 20, 1);
+        // rotating an O doesn't move it, so it's always wasted
+        let placements = [
+            &[Action::Rotate, Action::Drop][..],
+            &[Action::Left, Action::Right, Action::Drop],
+            &[Action::Drop],
+        ];
+        for actions in placements {
+            tetris.board.remove_block();
+            tetris.spawn(PieceKind::O).unwrap();
+            for &action in actions {
+                tetris.apply(action);
+            }
+        }
+        assert_eq!(tetris.locks[1].inputs, 2);
+        // drops are keys too
+        assert_eq!(tetris.locks[1].keys, 3);
+
+        let analysis = Analysis::new(&tetris.locks);
+        assert_eq!(analysis.finesse_pieces, 3);
+        assert_eq!(analysis.finesse_faults, 2);
+    }
+
+    #[test]
+    fn test_opener() {
+        let opener: Opener = "name: test\nqueue: IO\nboard:\n........22\n....111122\n"
+            .parse()
+            .unwrap();
+        let mut tetris = Tetris::new(10, 20, 1).with_opener(&opener).unwrap();
+        assert!(!tetris.can_hold());
+
+        tetris.apply(Action::Tick);
+        assert_eq!(tetris.piece, PieceKind::I);
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.piece, PieceKind::O);
+        // dropped onto the I instead of beside it
+        tetris.apply(Action::Drop);
+
+        let trainer = tetris.trainer.as_ref().unwrap();
+        assert!(trainer.done());
+        assert_eq!(trainer.accuracy(), 50.0);
+        assert!(tetris.finished.is_some());
+        assert_eq!(tetris.game_over, Some(GameOver::Goal));
+        assert!(tetris.board.current_block().is_none());
+    }
+
+    #[test]
+    fn test_popups() {
+        let row: Vec<_> = (0..10).map(|x| !(4..8).contains(&x)).collect();
+        let above: Vec<_> = (0..10).map(|x| x == 0).collect();
+        let mut tetris = Tetris::new(10, 20, 1).with_field(&[above, row]).unwrap();
+        spawn_i(&mut tetris);
+
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.popups.len(), 1);
+        assert_eq!(tetris.popups[0].text, "+1 SINGLE");
+        assert_eq!(tetris.popups[0].row, 19);
+        assert_eq!(tetris.score_log, [("SINGLE".to_owned(), 1)]);
+
+        // placing without clearing scores nothing to show
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.popups.len(), 1);
+
+        let clear = Clear {
+            lines: 2,
+            tspin: true,
+            ..Default::default()
+        };
+        assert_eq!(clear_name(&clear), "T-SPIN DOUBLE");
+    }
+
+    #[test]
+    fn test_srs() {
+        // a T-spin triple slot with the overhang on the left
+        let field: Vec<Vec<bool>> = [
+            "##........",
+            "#.........",
+            "#.########",
+            "#..#######",
+            "#.########",
+        ]
+        .iter()
+        .map(|row| row.chars().map(|c| c == '#').collect())
+        .collect();
+        let triple = |rotation| {
+            let config = Config {
+                rotation,
+                ..Default::default()
+            };
+            let mut tetris = Tetris::new(10, 20, 1)
+                .with_config(config)
+                .with_field(&field)
+                .unwrap();
+            tetris.board.remove_block();
+            tetris.piece = PieceKind::T;
+            tetris
+                .board
+                .spawn(PieceKind::T.block(), Tile::piece(PieceKind::T))
+                .unwrap();
+            // turned to point up, then slid in under the overhang
+            for action in [Action::SoftDrop, Action::Rotate, Action::Rotate] {
+                tetris.apply(action);
+            }
+            tetris.apply(Action::Left);
+            tetris.apply(Action::Left);
+            while tetris.board.try_down().is_ok() {
+                tetris.apply(Action::SoftDrop);
+            }
+            tetris.apply(Action::Left);
+            tetris.apply(Action::Rotate);
+            tetris.apply(Action::Drop);
+            tetris.lines
+        };
+        assert_eq!(triple(Rotation::Srs), 3);
+        assert_eq!(triple(Rotation::Simple), 0);
+    }
+
+    #[test]
+    fn test_line_clear_delay() {
+        let row: Vec<_> = (0..10).map(|x| !(4..8).contains(&x)).collect();
+        let config = Config {
+            line_clear_delay: 300,
+            ..Default::default()
+        };
+        let mut tetris = Tetris::new(10, 20, 1)
+            .with_config(config)
+            .with_field(&[row])
+            .unwrap();
+        spawn_i(&mut tetris);
+
+        tetris.apply(Action::Drop);
+        assert_eq!(tetris.lines, 1);
+        assert!(tetris.clearing.is_some());
+        // the cleared row fades out, garbage and piece alike
+        let view = tetris.view();
+        assert_eq!(view.color(0, 19), Color::White);
+        let Layer::Stack(faded) = view.get(4, 19) else {
+            panic!("the piece isn't shown in the cleared row");
+        };
+        assert_eq!(faded.kind, Some(PieceKind::I));
+        assert_eq!(view.get(4, 18), Layer::Empty);
+        assert!(tetris.board.current_block().is_none());
+
+        // moves wait for the next piece
+        tetris.apply(Action::Drop);
+        tetris.apply(Action::Hold);
+        assert!(tetris.board.current_block().is_none());
+
+        tetris.apply(Action::Tick);
+        assert!(tetris.clearing.is_none());
+        assert!(tetris.board.current_block().is_some());
+    }
+
+    #[test]
+    fn test_game_over() {
+        let mut field = vec![vec![false; 10]; 20];
+        field[0] = vec![true; 10];
+        let mut tetris = Tetris::new(10, 20, 1).with_field(&field).unwrap();
+        assert_eq!(tetris.game_over(), None);
+        tetris.apply(Action::Tick);
+        assert_eq!(tetris.game_over(), Some(GameOver::BlockOut));
+        assert_eq!(tetris.result().unwrap().end, GameOver::BlockOut);
+
+        // the first reason sticks
+        tetris.end(GameOver::Quit);
+        assert_eq!(tetris.game_over(), Some(GameOver::BlockOut));
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut tetris = Tetris::new(10, 20, 1);
+        let counting_down = |t: &Tetris| t.pause.as_ref().map(|p| p.resume_at.is_some());
+
+        tetris.toggle_pause();
+        assert_eq!(counting_down(&tetris), Some(false));
+        tetris.toggle_pause();
+        assert_eq!(counting_down(&tetris), Some(true));
+        tetris.toggle_pause();
+        assert_eq!(counting_down(&tetris), Some(false));
+
+        // there's nothing to pause once the game is over
+        let mut tetris = Tetris::new(10, 20, 1);
+        tetris.top_out(GameOver::BlockOut);
+        tetris.toggle_pause();
+        assert_eq!(counting_down(&tetris), None);
+    }
+
+    #[test]
+    fn test_lock_delay() {
+        // a block resting on the stack, with the clock stopped so only
+        // skipping moves it on
+        let resting = |lock_delay, lock_resets, action| {
+            let config = Config {
+                lock_delay,
+                lock_resets,
+                ..Default::default()
+            };
+            let mut tetris = Tetris::new(10, 20, 1).with_seed(0).with_config(config);
+            tetris.clock.pause();
+            tetris.apply(Action::Tick);
+            while tetris.board.try_down().is_ok() {
+                tetris.apply(action);
+            }
+            tetris
+        };
+        let wait = |tetris: &mut Tetris, ms| {
+            tetris.clock.skip(Duration::from_millis(ms));
+            tetris.apply(Action::Tick);
+            // a new block would be free to fall
+            tetris.board.try_down().is_ok()
+        };
+
+        let mut tetris = resting(500, LockResets::Limited(2), Action::Tick);
+        assert!(!wait(&mut tetris, 400));
+        tetris.apply(Action::Left);
+        assert!(!wait(&mut tetris, 400));
+        tetris.apply(Action::Right);
+        assert!(!wait(&mut tetris, 400));
+        // out of resets, the delay runs on from the last one
+        tetris.apply(Action::Left);
+        assert!(!tetris.lock_delay_due());
+        tetris.clock.skip(Duration::from_millis(100));
+        assert!(tetris.lock_delay_due());
+        assert!(wait(&mut tetris, 0));
+
+        // soft dropping onto the stack leaves the reset for moving on it
+        let mut tetris = resting(500, LockResets::Limited(1), Action::SoftDrop);
+        tetris.clock.skip(Duration::from_millis(400));
+        tetris.apply(Action::Left);
+        assert!(!wait(&mut tetris, 400));
+        assert!(wait(&mut tetris, 100));
+
+        // without a delay it locks on the next tick
+        let mut tetris = resting(0, LockResets::Infinite, Action::Tick);
+        assert!(!tetris.lock_delay_due());
+        assert!(wait(&mut tetris, 0));
+    }
+
+    #[test]
+    fn test_match_result() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = Client::connect(
+            listener.local_addr().unwrap(),
+            "room",
+            "alice",
+            Tetris::default().handshake(),
+            crate::protocol::Handicap::default(),
+        )
+        .unwrap();
+        let mut tetris = Tetris::default().with_client(client);
+        // waiting for an opponent isn't a match yet
+        assert_eq!(tetris.match_result(), None);
+
+        let versus = tetris.versus.as_mut().unwrap();
+        versus.opponents.push(Opponent {
+            name: "bob".into(),
+            rows: Vec::new(),
+            out: false,
+        });
+        // quitting midway forfeits
+        assert_eq!(tetris.match_result(), Some(("alice", "bob", false)));
+        tetris.versus.as_mut().unwrap().won = Some(true);
+        assert_eq!(tetris.match_result(), Some(("alice", "bob", true)));
     }
 }
@@ -0,0 +1,256 @@
+use crate::stats::{format_lap, GameResult};
+use anyhow::{bail, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block, Paragraph, Row, Table, Widget},
+    DefaultTerminal,
+};
+use std::cmp::Ordering;
+
+/// Players taking turns at the same games on one machine, round after
+/// round, each round played with pieces from the same seed by everyone.
+///
+/// Every round, each player scores a point for every other player they did
+/// better than.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tournament {
+    players: Vec<String>,
+    rounds: usize,
+    /// Results of the rounds played so far, in the order of `players`.
+    results: Vec<Vec<GameResult>>,
+}
+
+impl Tournament {
+    pub fn new(players: Vec<String>, rounds: usize) -> Result<Self> {
+        if players.len() < 2 {
+            bail!("a tournament needs at least two players");
+        }
+        if rounds == 0 {
+            bail!("a tournament needs at least one round");
+        }
+        Ok(Self {
+            players,
+            rounds,
+            results: Vec::new(),
+        })
+    }
+
+    /// The round and the index of the player up next, unless every round
+    /// has been played.
+    pub fn next_turn(&self) -> Option<(usize, usize)> {
+        match self.results.last() {
+            Some(round) if round.len() < self.players.len() => {
+                Some((self.results.len() - 1, round.len()))
+            }
+            _ if self.results.len() < self.rounds => Some((self.results.len(), 0)),
+            _ => None,
+        }
+    }
+
+    pub fn player(&self, index: usize) -> &str {
+        &self.players[index]
+    }
+
+    /// Records the result of the player whose turn it was.
+    pub fn record(&mut self, result: GameResult) {
+        match self.results.last_mut() {
+            Some(round) if round.len() < self.players.len() => round.push(result),
+            _ => self.results.push(vec![result]),
+        }
+    }
+
+    /// Points of every player so far, from the rounds everyone has played.
+    pub fn points(&self) -> Vec<usize> {
+        let mut points = vec![0; self.players.len()];
+        for round in self
+            .results
+            .iter()
+            .filter(|r| r.len() == self.players.len())
+        {
+            for (i, result) in round.iter().enumerate() {
+                points[i] += round
+                    .iter()
+                    .filter(|other| compare(result, other) == Ordering::Less)
+                    .count();
+            }
+        }
+        points
+    }
+
+    /// Indices of the players from the most points down.
+    pub fn standings(&self) -> Vec<usize> {
+        let points = self.points();
+        let mut order: Vec<usize> = (0..self.players.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(points[i]));
+        order
+    }
+
+    /// Players with the most points, more than one on a tie, once every
+    /// round has been played.
+    pub fn winners(&self) -> Vec<&str> {
+        if self.next_turn().is_some() {
+            return Vec::new();
+        }
+        let points = self.points();
+        let most = points.iter().copied().max().unwrap_or(0);
+        (0..self.players.len())
+            .filter(|&i| points[i] == most)
+            .map(|i| self.player(i))
+            .collect()
+    }
+
+    /// Shows the standings until the next player is ready, returning
+    /// whether to play on; <Q> stops the tournament.
+    pub fn intermission(&self, terminal: &mut DefaultTerminal) -> Result<bool> {
+        loop {
+            terminal.draw(|frame| frame.render_widget(self, frame.area()))?;
+            let Event::Key(key_event) = event::read()? else {
+                continue;
+            };
+            if key_event.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key_event.code {
+                KeyCode::Enter => return Ok(self.next_turn().is_some()),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Orders results from the best: reaching the goal, sooner, then scoring
+/// more and clearing more lines.
+fn compare(a: &GameResult, b: &GameResult) -> Ordering {
+    match (a.goal_time, b.goal_time) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => b.score.cmp(&a.score).then(b.lines.cmp(&a.lines)),
+    }
+}
+
+/// What a result is ranked by, in short.
+fn outcome(result: &GameResult) -> String {
+    match result.goal_time {
+        Some(time) => format_lap(time),
+        None => result.score.to_string(),
+    }
+}
+
+impl Widget for &Tournament {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (title, hint) = match self.next_turn() {
+            Some((round, player)) => (
+                format!(" tournament, round {}/{} ", round + 1, self.rounds),
+                Line::from(vec![
+                    " ".into(),
+                    "<Enter>".blue().bold(),
+                    format!(" when {} is ready, ", self.player(player)).into(),
+                    "<Q>".blue().bold(),
+                    " to quit ".into(),
+                ]),
+            ),
+            None => (
+                " tournament results ".to_owned(),
+                Line::from(vec![" ".into(), "<Q>".blue().bold(), " to quit ".into()]),
+            ),
+        };
+        let block = Block::bordered()
+            .title(Line::from(title.bold()).centered())
+            .title_bottom(hint.centered())
+            .border_set(border::THICK);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [winner, table] =
+            Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]).areas(inner);
+        let winners = self.winners();
+        let line = match winners.as_slice() {
+            [] => Line::default(),
+            [winner] => Line::from(vec![" winner: ".into(), winner.to_string().green().bold()]),
+            _ => Line::from(vec![
+                " tie between ".into(),
+                winners.join(", ").green().bold(),
+            ]),
+        };
+        Paragraph::new(line).render(winner, buf);
+
+        let points = self.points();
+        let rows = self.standings().into_iter().map(|i| {
+            let mut cells = vec![format!(" {}", self.player(i)), points[i].to_string()];
+            cells.extend(
+                self.results
+                    .iter()
+                    .map(|round| round.get(i).map_or("-".to_owned(), outcome)),
+            );
+            Row::new(cells)
+        });
+        let mut header = vec![" player".to_owned(), "points".to_owned()];
+        header.extend((1..=self.rounds).map(|round| format!("round {round}")));
+        let widths = [Constraint::Length(16), Constraint::Length(8)]
+            .into_iter()
+            .chain((0..self.rounds).map(|_| Constraint::Length(10)));
+        Table::new(rows, widths)
+            .header(Row::new(header).bold())
+            .render(table, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        analysis::Analysis,
+        stats::{GameOver, Heatmap},
+    };
+    use std::time::Duration;
+
+    fn result(score: u64, goal_time: Option<u64>) -> GameResult {
+        GameResult {
+            mode: "sprint".to_owned(),
+            score,
+            lines: score,
+            tetrises: 0,
+            time: Duration::from_secs(100),
+            goal_time: goal_time.map(Duration::from_secs),
+            splits: Vec::new(),
+            heatmap: Heatmap::default(),
+            analysis: Analysis::default(),
+            end: GameOver::Quit,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn test_tournament() {
+        let players = ["alice", "bob", "carol"].map(str::to_owned).to_vec();
+        let mut tournament = Tournament::new(players, 2).unwrap();
+        assert_eq!(tournament.next_turn(), Some((0, 0)));
+
+        tournament.record(result(40, Some(70)));
+        tournament.record(result(40, Some(60)));
+        assert_eq!(tournament.next_turn(), Some((0, 2)));
+        // points only count once everyone has played the round
+        assert_eq!(tournament.points(), [0, 0, 0]);
+        tournament.record(result(30, None));
+        assert_eq!(tournament.points(), [1, 2, 0]);
+        assert_eq!(tournament.next_turn(), Some((1, 0)));
+        assert!(tournament.winners().is_empty());
+
+        tournament.record(result(40, Some(50)));
+        tournament.record(result(12, None));
+        tournament.record(result(20, None));
+        assert_eq!(tournament.next_turn(), None);
+        assert_eq!(tournament.points(), [3, 2, 1]);
+        assert_eq!(tournament.standings(), [0, 1, 2]);
+        assert_eq!(tournament.winners(), ["alice"]);
+
+        assert!(Tournament::new(vec!["alice".to_owned()], 1).is_err());
+    }
+}
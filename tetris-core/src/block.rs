@@ -0,0 +1,442 @@
+use alloc::{borrow::ToOwned, vec::Vec};
+use core::fmt;
+#[cfg(feature = "ratatui")]
+use ratatui::style::Color;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Block {
+    coords: Vec<(i32, i32)>,
+    /// Point the block rotates about, in half cells so that it can be
+    /// between cells.
+    pivot: (i32, i32),
+    orientation: Orientation,
+}
+
+/// Rotation state of a block, turned clockwise from how it spawned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Orientation {
+    #[default]
+    Spawn,
+    Right,
+    Reverse,
+    Left,
+}
+
+impl Orientation {
+    /// The state a clockwise turn leads to.
+    pub fn clockwise(self) -> Self {
+        match self {
+            Orientation::Spawn => Orientation::Right,
+            Orientation::Right => Orientation::Reverse,
+            Orientation::Reverse => Orientation::Left,
+            Orientation::Left => Orientation::Spawn,
+        }
+    }
+
+    /// Clockwise turns the state is from spawning.
+    fn turns(self) -> usize {
+        match self {
+            Orientation::Spawn => 0,
+            Orientation::Right => 1,
+            Orientation::Reverse => 2,
+            Orientation::Left => 3,
+        }
+    }
+
+    /// Short name of the state as used by kick tables: `0`, `R`, `2` or `L`.
+    pub fn name(self) -> char {
+        match self {
+            Orientation::Spawn => '0',
+            Orientation::Right => 'R',
+            Orientation::Reverse => '2',
+            Orientation::Left => 'L',
+        }
+    }
+}
+
+/// Offsets the Super Rotation System tries in order for a clockwise turn of
+/// J, L, S, T and Z from each of its states, with y growing down.
+const SRS_KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+];
+
+/// Offsets the Super Rotation System tries for a clockwise turn of I.
+const SRS_I_KICKS: [[(i32, i32); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+];
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The seven tetrominoes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PieceKind {
+    I,
+    O,
+    T,
+    J,
+    L,
+    S,
+    Z,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 7] = [
+        PieceKind::I,
+        PieceKind::O,
+        PieceKind::T,
+        PieceKind::J,
+        PieceKind::L,
+        PieceKind::S,
+        PieceKind::Z,
+    ];
+
+    /// Returns the piece named `name`, one of `IOTJLSZ`.
+    pub fn from_name(name: char) -> Option<Self> {
+        Some(match name.to_ascii_uppercase() {
+            'I' => PieceKind::I,
+            'O' => PieceKind::O,
+            'T' => PieceKind::T,
+            'J' => PieceKind::J,
+            'L' => PieceKind::L,
+            'S' => PieceKind::S,
+            'Z' => PieceKind::Z,
+            _ => return None,
+        })
+    }
+
+    /// Returns the name of the piece, the inverse of [`PieceKind::from_name`].
+    pub fn name(self) -> char {
+        match self {
+            PieceKind::I => 'I',
+            PieceKind::O => 'O',
+            PieceKind::T => 'T',
+            PieceKind::J => 'J',
+            PieceKind::L => 'L',
+            PieceKind::S => 'S',
+            PieceKind::Z => 'Z',
+        }
+    }
+
+    /// Cells of the piece as it spawns.
+    pub fn cells(self) -> &'static [(i32, i32)] {
+        match self {
+            PieceKind::I => &[(1, 0), (0, 0), (2, 0), (3, 0)],
+            PieceKind::O => &[(0, 0), (1, 0), (0, 1), (1, 1)],
+            PieceKind::T => &[(1, 0), (0, 0), (2, 0), (1, 1)],
+            PieceKind::J => &[(1, 2), (1, 0), (1, 1), (0, 2)],
+            PieceKind::L => &[(0, 2), (0, 0), (0, 1), (1, 2)],
+            PieceKind::S => &[(1, 0), (2, 0), (0, 1), (1, 1)],
+            PieceKind::Z => &[(1, 0), (0, 0), (1, 1), (2, 1)],
+        }
+    }
+
+    /// Center of the piece in half cells: the middle of the line of three
+    /// of J, L and T, the middle of the bottom row of S and Z, and the point
+    /// between the middle cells of I and O.
+    fn pivot(self) -> (i32, i32) {
+        match self {
+            PieceKind::I => (3, 1),
+            PieceKind::O => (1, 1),
+            PieceKind::T => (2, 0),
+            PieceKind::J => (2, 2),
+            PieceKind::L => (0, 2),
+            PieceKind::S | PieceKind::Z => (2, 2),
+        }
+    }
+
+    /// The state the piece spawns in as the Super Rotation System counts
+    /// them, which spawns J and L lying flat and T pointing up.
+    fn srs_spawn(self) -> Orientation {
+        match self {
+            PieceKind::T => Orientation::Reverse,
+            PieceKind::J => Orientation::Left,
+            PieceKind::L => Orientation::Right,
+            _ => Orientation::Spawn,
+        }
+    }
+
+    /// Offsets to try in order when the piece turns clockwise from
+    /// `from`, as in the Super Rotation System.
+    pub fn kicks(self, from: Orientation) -> &'static [(i32, i32)] {
+        let state = (self.srs_spawn().turns() + from.turns()) % 4;
+        match self {
+            PieceKind::O => &[(0, 0)],
+            PieceKind::I => &SRS_I_KICKS[state],
+            _ => &SRS_KICKS[state],
+        }
+    }
+
+    /// The standard color of the piece.
+    #[cfg(feature = "ratatui")]
+    pub fn color(self) -> Color {
+        match self {
+            PieceKind::I => Color::Cyan,
+            PieceKind::O => Color::Yellow,
+            PieceKind::T => Color::Magenta,
+            PieceKind::S => Color::Green,
+            PieceKind::Z => Color::Red,
+            PieceKind::J => Color::Blue,
+            PieceKind::L => Color::Indexed(208),
+        }
+    }
+
+    /// The piece as a block, rotating about its center.
+    pub fn block(self) -> Block {
+        Block {
+            coords: self.cells().to_owned(),
+            pivot: self.pivot(),
+            orientation: Orientation::Spawn,
+        }
+    }
+
+    /// The piece as a block turned to `state` as the Super Rotation System
+    /// counts them, where `Spawn` is J, L and T pointing up.
+    pub fn srs_block(self, state: Orientation) -> Block {
+        let turns = (state.turns() + 4 - self.srs_spawn().turns()) % 4;
+        let mut block = self.block();
+        for _ in 0..turns {
+            block = block.rotate();
+        }
+        block
+    }
+}
+
+impl Block {
+    /// Constructs a new block from slice, rotating about its first cell.
+    pub fn new(coords: &[(i32, i32)]) -> Self {
+        Self {
+            coords: coords.to_owned(),
+            pivot: (2 * coords[0].0, 2 * coords[0].1),
+            orientation: Orientation::Spawn,
+        }
+    }
+
+    /// Getter for `coords`.
+    pub fn coords(&self) -> &[(i32, i32)] {
+        &self.coords
+    }
+
+    /// Top left and bottom right cells of the smallest box around the block.
+    pub fn bounding_box(&self) -> ((i32, i32), (i32, i32)) {
+        let xs = self.coords.iter().map(|c| c.0);
+        let ys = self.coords.iter().map(|c| c.1);
+        let (left, right) = (xs.clone().min().unwrap(), xs.max().unwrap());
+        let (top, bottom) = (ys.clone().min().unwrap(), ys.max().unwrap());
+        ((left, top), (right, bottom))
+    }
+
+    /// Number of columns the block spans.
+    pub fn width(&self) -> i32 {
+        let ((left, _), (right, _)) = self.bounding_box();
+        right - left + 1
+    }
+
+    /// Number of rows the block spans.
+    pub fn height(&self) -> i32 {
+        let ((_, top), (_, bottom)) = self.bounding_box();
+        bottom - top + 1
+    }
+
+    /// Returns a new block moved so that its bounding box starts at (0, 0).
+    pub fn normalized(&self) -> Self {
+        let ((left, top), _) = self.bounding_box();
+        self.translate(-left, -top)
+    }
+
+    /// Getter for `orientation`.
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Returns a new block translated from the current by (dx, dy).
+    pub fn translate(&self, dx: i32, dy: i32) -> Self {
+        let (px, py) = self.pivot;
+        Self {
+            coords: self.coords.iter().map(|(x, y)| (x + dx, y + dy)).collect(),
+            pivot: (px + 2 * dx, py + 2 * dy),
+            orientation: self.orientation,
+        }
+    }
+
+    /// Translate one unit to the left.
+    pub fn left(&self) -> Self {
+        self.translate(-1, 0)
+    }
+
+    /// Translate one unit to the right.
+    pub fn right(&self) -> Self {
+        self.translate(1, 0)
+    }
+
+    /// Translate one unit down.
+    pub fn down(&self) -> Self {
+        self.translate(0, 1)
+    }
+
+    /// Returns a new block rotated clockwise by 90 degrees about the center of the block.
+    pub fn rotate(&self) -> Self {
+        self.rotate_about_half(self.pivot)
+    }
+
+    /// Returns a new block rotated clockwise by 90 degrees about `center`.
+    pub fn rotate_about(&self, center: (i32, i32)) -> Self {
+        self.rotate_about_half((2 * center.0, 2 * center.1))
+    }
+
+    /// Rotates clockwise about `center` in half cells, which has to be in
+    /// the middle of a cell or of four for the cells to line up again.
+    fn rotate_about_half(&self, center: (i32, i32)) -> Self {
+        let (cx, cy) = center;
+        // (x, y) goes to (cx + cy - y, cy - cx + x), all in half cells
+        let rotate = |(x, y): (i32, i32)| (cx + cy - y, cy - cx + x);
+        Self {
+            coords: self
+                .coords
+                .iter()
+                .map(|&(x, y)| {
+                    let (x, y) = rotate((2 * x, 2 * y));
+                    (x / 2, y / 2)
+                })
+                .collect(),
+            pivot: rotate(self.pivot),
+            orientation: self.orientation.clockwise(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, Orientation, PieceKind};
+    use alloc::string::String;
+
+    #[test]
+    fn test_block_translate() {
+        // horizontal strip
+        let block = Block::new(&[(0, 0), (1, 0), (2, 0), (3, 0)]);
+
+        assert_eq!(
+            block.translate(-1, 1).coords(),
+            &[(-1, 1), (0, 1), (1, 1), (2, 1)]
+        );
+        assert_eq!(block.left(), block.translate(-1, 0));
+        assert_eq!(block.right(), block.translate(1, 0));
+        assert_eq!(block.down(), block.translate(0, 1));
+        assert_eq!(
+            block.left().right().right().right().down().down().down(),
+            block.translate(2, 3)
+        );
+    }
+
+    #[test]
+    fn test_block_rotate() {
+        // horizontal strip
+        let block = Block::new(&[(0, 0), (1, 0), (2, 0), (3, 0)]);
+
+        assert_eq!(
+            block.rotate_about((0, 0)).coords(),
+            &[(0, 0), (0, 1), (0, 2), (0, 3)]
+        );
+        assert_eq!(
+            block.rotate_about((3, 0)).coords(),
+            &[(3, -3), (3, -2), (3, -1), (3, 0)]
+        );
+        assert_eq!(block.rotate().coords(), &[(0, 0), (0, 1), (0, 2), (0, 3)]);
+        assert_eq!(block.rotate().rotate().rotate().rotate(), block);
+    }
+
+    #[test]
+    fn test_block_pivot() {
+        let cells = |block: &Block| {
+            let mut cells = block.coords().to_vec();
+            cells.sort();
+            cells
+        };
+        // O turns in place
+        let o = PieceKind::O.block().translate(3, 5);
+        assert_eq!(cells(&o.rotate()), cells(&o));
+        // I turns about the point between its middle cells
+        let i = PieceKind::I.block();
+        assert_eq!(cells(&i.rotate()), [(2, -1), (2, 0), (2, 1), (2, 2)]);
+        assert_eq!(
+            cells(&i.rotate().rotate()),
+            [(0, 1), (1, 1), (2, 1), (3, 1)]
+        );
+        // T turns about the middle of its flat side
+        let t = PieceKind::T.block().translate(4, 4);
+        assert_eq!(cells(&t.rotate()), [(4, 4), (5, 3), (5, 4), (5, 5)]);
+        for kind in PieceKind::ALL {
+            let block = kind.block().translate(2, 3);
+            assert_eq!(block.rotate().rotate().rotate().rotate(), block);
+        }
+    }
+
+    #[test]
+    fn test_block_bounds() {
+        let block = PieceKind::J.block().translate(3, -2);
+        assert_eq!(block.bounding_box(), ((3, -2), (4, 0)));
+        assert_eq!((block.width(), block.height()), (2, 3));
+        assert_eq!(block.normalized().bounding_box(), ((0, 0), (1, 2)));
+        // the pivot moves along
+        assert_eq!(block.normalized().rotate(), PieceKind::J.block().rotate());
+
+        let i = PieceKind::I.block().rotate();
+        assert_eq!((i.width(), i.height()), (1, 4));
+    }
+
+    #[test]
+    fn test_orientation() {
+        let block = crate::board::spawned(&PieceKind::T.block(), 10);
+        assert_eq!(block.orientation(), Orientation::Spawn);
+        // moving keeps the state, turning moves on to the next
+        assert_eq!(block.left().down().orientation(), Orientation::Spawn);
+        let states: String = core::iter::successors(Some(block), |b| Some(b.rotate()))
+            .take(5)
+            .map(|b| b.orientation().name())
+            .collect();
+        assert_eq!(states, "0R2L0");
+    }
+
+    #[test]
+    fn test_kicks() {
+        // T spawns pointing down, the state SRS calls 2
+        assert_eq!(
+            PieceKind::T.kicks(Orientation::Spawn),
+            [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)]
+        );
+        assert_eq!(PieceKind::T.kicks(Orientation::Reverse)[4], (-1, 2));
+        assert_eq!(PieceKind::S.kicks(Orientation::Spawn)[1], (-1, 0));
+        assert_eq!(PieceKind::I.kicks(Orientation::Right)[1], (-1, 0));
+        assert_eq!(PieceKind::O.kicks(Orientation::Left), [(0, 0)]);
+
+        // T points up in SRS state 0, two turns from how it spawns
+        let t = PieceKind::T.srs_block(Orientation::Spawn).normalized();
+        let mut cells = t.coords().to_vec();
+        cells.sort();
+        assert_eq!(cells, [(0, 1), (1, 0), (1, 1), (2, 1)]);
+        assert_eq!(t.orientation(), Orientation::Reverse);
+    }
+
+    #[test]
+    fn test_piece_kind() {
+        for (name, kind) in "IOTJLSZ".chars().zip(PieceKind::ALL) {
+            assert_eq!(PieceKind::from_name(name), Some(kind));
+            assert_eq!(kind.name(), name);
+            // the topmost cell spawns in the middle of the top row
+            let spawned = crate::board::spawned(&kind.block(), 10);
+            let top = spawned.coords().iter().min_by_key(|(_, y)| *y);
+            assert_eq!(top, Some(&(5, 0)));
+        }
+        assert_eq!(PieceKind::from_name('t'), Some(PieceKind::T));
+        assert_eq!(PieceKind::from_name('X'), None);
+    }
+}
@@ -0,0 +1,1347 @@
+use crate::block::Block;
+use alloc::{
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use anyhow::{anyhow, bail, Error, Result};
+use core::{fmt, str::FromStr};
+use tracing::trace;
+
+/// Contents of a filled cell.
+pub trait Cell: Clone {
+    /// Whether rows containing this cell can be cleared.
+    fn clearable(&self) -> bool {
+        true
+    }
+}
+
+impl Cell for () {}
+
+/// Cells written as numbers in the text format of [`Board::to_text`], which
+/// boards of them are displayed in and parsed from.
+pub trait Numbered: Cell + Sized {
+    /// Number the cell is written as, never 0.
+    fn number(&self) -> u32;
+
+    /// The cell written as `number`, which isn't 0.
+    fn from_number(number: u32) -> Result<Self>;
+}
+
+/// Every filled cell is the same, written as 1, and any number reads as one.
+impl Numbered for () {
+    fn number(&self) -> u32 {
+        1
+    }
+
+    fn from_number(_number: u32) -> Result<Self> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Board<T: Cell> {
+    board: VecDeque<Vec<Option<T>>>,
+    width: usize,
+    height: usize,
+    current_block: Option<Block>,
+}
+
+impl<T: Cell> Board<T> {
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut board = VecDeque::new();
+        board.resize_with(height, || {
+            let mut row = Vec::new();
+            row.resize_with(width, || None);
+            row
+        });
+        Self {
+            board,
+            width,
+            height,
+            current_block: None,
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The falling block, if any.
+    pub fn current_block(&self) -> Option<&Block> {
+        self.current_block.as_ref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.board.iter().flatten().all(|x| x.is_none())
+    }
+
+    /// Rows from the bottom up to the highest filled cell.
+    pub fn stack_height(&self) -> usize {
+        let top = self
+            .board
+            .iter()
+            .position(|row| row.iter().any(|x| x.is_some()))
+            .unwrap_or(self.height);
+        self.height - top
+    }
+
+    /// Empty cells with a filled cell somewhere above them.
+    pub fn holes(&self) -> usize {
+        (0..self.width)
+            .map(|x| {
+                (0..self.height)
+                    .skip_while(|&y| self.board[y][x].is_none())
+                    .filter(|&y| self.board[y][x].is_none())
+                    .count()
+            })
+            .sum()
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> &Option<T> {
+        &self.board[y][x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: T) {
+        self.board[y][x] = Some(value);
+    }
+
+    pub fn clear(&mut self, x: usize, y: usize) {
+        self.board[y][x] = None;
+    }
+
+    /// Writes the rows as the `board!` test macro takes them: one number per
+    /// cell given by `number`, 0 for empty cells, and a `;` after each row.
+    pub fn to_text(&self, number: impl Fn(&T) -> u32) -> String {
+        self.board
+            .iter()
+            .map(|row| {
+                let cells: Vec<_> = row
+                    .iter()
+                    .map(|cell| cell.as_ref().map_or(0, &number).to_string())
+                    .collect();
+                format!("{};\n", cells.join(" "))
+            })
+            .collect()
+    }
+
+    /// Reads rows written by [`Board::to_text`], turning numbers other than 0
+    /// into cells with `cell`. Blank lines and lines starting with `#` are
+    /// skipped.
+    pub fn from_text(text: &str, cell: impl Fn(u32) -> Result<T>) -> Result<Self> {
+        let mut board = VecDeque::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let row = line
+                .trim_end_matches(';')
+                .split_whitespace()
+                .map(|n| match n.parse() {
+                    Ok(0) => Ok(None),
+                    Ok(n) => cell(n).map(Some),
+                    Err(_) => bail!("expected a number, got {n:?}"),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            board.push_back(row);
+        }
+
+        let width = board.front().map_or(0, Vec::len);
+        if width == 0 {
+            bail!("missing board");
+        }
+        if board.iter().any(|row| row.len() != width) {
+            bail!("rows have to be {width} cells wide");
+        }
+        Ok(Self {
+            height: board.len(),
+            board,
+            width,
+            current_block: None,
+        })
+    }
+
+    /// Rows without an empty or unclearable cell, from top to bottom.
+    pub fn filled_rows(&self) -> Vec<usize> {
+        (0..self.height)
+            .filter(|&y| is_filled(&self.board[y]))
+            .collect()
+    }
+
+    /// Removes the filled rows, moving the rows above them down. The current
+    /// block is considered locked afterwards.
+    pub fn clear_filled_rows(&mut self) -> usize {
+        self.board.retain(|row| !is_filled(row));
+        self.current_block = None;
+
+        // insert new empty rows to maintain height
+        let num_rows = self.height - self.board.len();
+        for _ in 0..num_rows {
+            let mut row = Vec::new();
+            row.resize_with(self.width, || None);
+            self.board.push_front(row);
+        }
+
+        num_rows
+    }
+
+    /// Removes row `y`, moving the rows above it down. The current block is
+    /// considered locked afterwards.
+    pub fn clear_row(&mut self, y: usize) {
+        self.board.remove(y);
+        let mut row = Vec::new();
+        row.resize_with(self.width, || None);
+        self.board.push_front(row);
+        self.current_block = None;
+    }
+
+    /// Shifts row `y` right by `by` cells, wrapping around to the left. The
+    /// current block is considered locked afterwards.
+    pub fn rotate_row(&mut self, y: usize, by: usize) {
+        self.board[y].rotate_right(by % self.width);
+        self.current_block = None;
+    }
+
+    /// Moves the filled rows to the bottom, pushing the rows that were below
+    /// them up, and returns how many there are. The current block is
+    /// considered locked afterwards.
+    pub fn sink_filled_rows(&mut self) -> usize {
+        let (filled, rest): (Vec<_>, Vec<_>) = self.board.drain(..).partition(|row| is_filled(row));
+        let count = filled.len();
+        self.board.extend(rest);
+        self.board.extend(filled);
+        self.current_block = None;
+        count
+    }
+
+    /// Pushes `lines` rows of garbage up from the bottom, each with an empty cell
+    /// at column `hole`. The current block is considered locked afterwards.
+    ///
+    /// Fails if filled cells are pushed out of the top of the board.
+    pub fn add_garbage(&mut self, lines: usize, hole: usize, value: T) -> Result<()> {
+        let lines = lines.min(self.height);
+        let overflow = self
+            .board
+            .range(..lines)
+            .any(|row| row.iter().any(|x| x.is_some()));
+
+        for _ in 0..lines {
+            let mut row = vec![Some(value.clone()); self.width];
+            row[hole] = None;
+            self.board.pop_front();
+            self.board.push_back(row);
+        }
+        self.current_block = None;
+
+        if overflow {
+            bail!("garbage pushed blocks out of the board");
+        }
+        Ok(())
+    }
+
+    /// Checks that every row is `width` cells wide and that the falling block
+    /// lies on filled cells inside the board without overlapping itself.
+    pub fn check_invariants(&self) -> Result<()> {
+        if self.board.len() != self.height {
+            bail!(
+                "board has {} rows instead of {}",
+                self.board.len(),
+                self.height
+            );
+        }
+        if let Some(y) = self.board.iter().position(|row| row.len() != self.width) {
+            bail!(
+                "row {y} is {} cells wide instead of {}",
+                self.board[y].len(),
+                self.width
+            );
+        }
+
+        let Some(block) = &self.current_block else {
+            return Ok(());
+        };
+        let coords = block.coords();
+        for (i, &(x, y)) in coords.iter().enumerate() {
+            if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+                bail!("falling block cell ({x}, {y}) is out of bounds");
+            }
+            if self.get(x as usize, y as usize).is_none() {
+                bail!("falling block cell ({x}, {y}) is not drawn");
+            }
+            if coords[..i].contains(&(x, y)) {
+                bail!("falling block overlaps itself at ({x}, {y})");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_block(&self, block: &Block) -> Result<()> {
+        if !block.coords().iter().all(|&(x, y)| {
+            x >= 0
+                && y >= 0
+                && (x as usize) < self.width
+                && (y as usize) < self.height
+                && self.get(x as usize, y as usize).is_none()
+        }) {
+            bail!("invalid block location");
+        }
+
+        Ok(())
+    }
+
+    fn update_block_impl(&mut self, f: impl FnOnce(Block) -> Block, dry_run: bool) -> Result<()> {
+        // blog idea: double borrow, current_block immutable, board mutable
+        // first clear current
+        let current = self
+            .current_block
+            .take()
+            .ok_or_else(|| anyhow!("current_block is None"))?;
+
+        // get value; should be same across all coords
+        let (x0, y0) = current.coords()[0];
+        let value = self.get(x0 as usize, y0 as usize).clone().unwrap();
+
+        // clear current block
+        for &(x, y) in current.coords() {
+            self.clear(x as usize, y as usize);
+        }
+
+        // check validity of new block
+        let new = f(current.clone());
+        let result = self.check_block(&new);
+
+        // either roll back or draw new block
+        let block = if !dry_run && result.is_ok() {
+            new
+        } else {
+            current
+        };
+        for &(x, y) in block.coords() {
+            self.set(x as usize, y as usize, value.clone());
+        }
+
+        self.current_block = Some(block);
+        result
+    }
+
+    fn update_block(&mut self, f: impl FnOnce(Block) -> Block) -> Result<()> {
+        self.update_block_impl(f, false)
+    }
+
+    fn set_block(&mut self, block: Block, value: T) -> Result<()> {
+        if self.current_block.is_some() {
+            panic!("current_block exists, call update_block instead");
+        }
+
+        self.check_block(&block)?;
+        for &(x, y) in block.coords() {
+            self.set(x as usize, y as usize, value.clone());
+        }
+
+        self.current_block = Some(block);
+        Ok(())
+    }
+
+    /// Whether the falling block could be moved to `block`, i.e. `block` only
+    /// covers empty cells or cells of the falling block.
+    fn fits(&self, block: &Block) -> bool {
+        let current = self.current_block.as_ref();
+        block.coords().iter().all(|&(x, y)| {
+            x >= 0
+                && y >= 0
+                && (x as usize) < self.width
+                && (y as usize) < self.height
+                && (self.get(x as usize, y as usize).is_none()
+                    || current.is_some_and(|b| b.coords().contains(&(x, y))))
+        })
+    }
+
+    /// Where the falling block would land if dropped.
+    pub fn ghost(&self) -> Option<Block> {
+        let mut ghost = self.current_block.clone()?;
+        while self.fits(&ghost.down()) {
+            ghost = ghost.down();
+        }
+        Some(ghost)
+    }
+
+    /// Whether the falling block can't move one cell in any direction.
+    pub fn is_immobile(&self) -> bool {
+        self.current_block.as_ref().is_some_and(|b| {
+            [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .all(|&(dx, dy)| !self.fits(&b.translate(dx, dy)))
+        })
+    }
+
+    /// Empty cells the falling block covers up that weren't holes already.
+    pub fn holes_under_block(&self) -> usize {
+        let Some(block) = &self.current_block else {
+            return 0;
+        };
+        let coords = block.coords();
+        (0..self.width as i32)
+            .filter_map(|x| {
+                let ys = coords.iter().filter(|&&(cx, _)| cx == x).map(|&(_, y)| y);
+                Some((x as usize, ys.clone().min()? as usize, ys.max()? as usize))
+            })
+            // cells under an overhang were holes before the block came
+            .filter(|&(x, top, _)| (0..top).all(|y| self.board[y][x].is_none()))
+            .map(|(x, _, bottom)| {
+                (bottom + 1..self.height)
+                    .take_while(|&y| self.board[y][x].is_none())
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Takes the falling block off the board.
+    pub fn remove_block(&mut self) -> Option<Block> {
+        let block = self.current_block.take()?;
+        for &(x, y) in block.coords() {
+            self.clear(x as usize, y as usize);
+        }
+        Some(block)
+    }
+
+    pub fn spawn(&mut self, block: Block, value: T) -> Result<()> {
+        self.current_block = None;
+        self.set_block(spawned(&block, self.width), value)?;
+
+        Ok(())
+    }
+
+    pub fn left(&mut self) -> Result<()> {
+        self.update_block(|b| b.left())
+    }
+
+    pub fn right(&mut self) -> Result<()> {
+        self.update_block(|b| b.right())
+    }
+
+    pub fn down(&mut self) -> Result<()> {
+        self.update_block(|b| b.down())
+    }
+
+    pub fn rotate(&mut self) -> Result<()> {
+        // blog idea: double borrow of self
+        let width = self.width;
+        self.update_block(|b| rotated(&b, width))
+    }
+
+    /// Rotates the falling block clockwise, moved by the first of `kicks`
+    /// that makes it fit.
+    pub fn rotate_kicked(&mut self, kicks: &[(i32, i32)]) -> Result<()> {
+        let current = self
+            .current_block
+            .as_ref()
+            .ok_or_else(|| anyhow!("current_block is None"))?;
+        let rotated = current.rotate();
+        let (from, to) = (current.orientation(), rotated.orientation());
+        let &(dx, dy) = kicks
+            .iter()
+            .find(|&&(dx, dy)| self.fits(&rotated.translate(dx, dy)))
+            .ok_or_else(|| anyhow!("no room to rotate"))?;
+        if (dx, dy) != (0, 0) {
+            trace!(dx, dy, %from, %to, "kick");
+        }
+        self.update_block(|_| rotated.translate(dx, dy))
+    }
+
+    /// Drops the falling block as far as it goes and returns how many rows
+    /// it fell.
+    pub fn hard_drop(&mut self) -> usize {
+        // FIXME: use binary search to optimize this
+        let mut rows = 0;
+        while self.down().is_ok() {
+            rows += 1;
+        }
+        rows
+    }
+
+    pub fn try_down(&mut self) -> Result<()> {
+        self.update_block_impl(|b| b.down(), true)
+    }
+}
+
+/// Where `block` spawns on a board `width` cells wide: its topmost cell in
+/// the top row, at the center.
+pub fn spawned(block: &Block, width: usize) -> Block {
+    let (x, y) = *block.coords().iter().min_by_key(|(_, y)| *y).unwrap();
+    block.translate((width / 2) as i32 - x, -y)
+}
+
+/// `block` rotated clockwise, kicked back inside a board `width` cells wide
+/// if it sticks out of the side.
+pub fn rotated(block: &Block, width: usize) -> Block {
+    let width = width as i32;
+    let rotated = block.rotate();
+    let (from, to) = (block.orientation(), rotated.orientation());
+
+    let ((min, _), (max, _)) = rotated.bounding_box();
+    if min < 0 {
+        trace!(dx = -min, %from, %to, "wall kick");
+        return rotated.translate(-min, 0);
+    }
+    if max >= width {
+        trace!(dx = width - max - 1, %from, %to, "wall kick");
+        return rotated.translate(width - max - 1, 0);
+    }
+
+    rotated
+}
+
+/// The rows in the format of [`Board::to_text`], without the falling block
+/// if it's been removed.
+impl<T: Numbered> fmt::Display for Board<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text(T::number))
+    }
+}
+
+/// Reads a board written as by [`Board::to_text`].
+impl<T: Numbered> FromStr for Board<T> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::from_text(s, T::from_number)
+    }
+}
+
+/// Whether `row` can be cleared: every cell is filled and clearable.
+fn is_filled<T: Cell>(row: &[Option<T>]) -> bool {
+    row.iter().all(|x| x.as_ref().is_some_and(Cell::clearable))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::PieceKind;
+
+    impl Cell for i32 {}
+
+    impl Numbered for i32 {
+        fn number(&self) -> u32 {
+            *self as u32
+        }
+
+        fn from_number(number: u32) -> Result<Self> {
+            match number {
+                1..=7 => Ok(number as i32),
+                _ => bail!("unknown cell {number}"),
+            }
+        }
+    }
+
+    /// A cell that is `true` when it can't be cleared.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Solid(bool);
+
+    impl Cell for Solid {
+        fn clearable(&self) -> bool {
+            !self.0
+        }
+    }
+
+    /// Constructs a Board from integers. For testing purposes only
+    ///
+    /// ```
+    /// let b = board! {
+    ///     0 0 0;
+    ///     1 1 1;
+    ///     2 2 2;
+    /// };
+    /// ```
+    macro_rules! board {
+        ($($($x:expr)+);+ $(;)?) => {
+            {
+                let board = VecDeque::from(vec![$(vec![$(match $x {
+                    0 => None,
+                    x => Some(x)
+                }),+]),+]);
+                let width = board[0].len();
+                let height = board.len();
+                Board { board, width, height, current_block: None }
+            }
+        };
+    }
+
+    #[test]
+    fn test_board_new() {
+        let board = Board::<()>::new(4, 8);
+        for x in 0..4 {
+            for y in 0..8 {
+                assert_eq!(board.get(x, y), &None);
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_clear_rows() {
+        let mut board = Board::<()>::new(4, 8);
+
+        // row 5 and 7 is full, row 6 is not
+        board.set(0, 5, ());
+        board.set(1, 5, ());
+        board.set(2, 5, ());
+        board.set(3, 5, ());
+        board.set(0, 6, ());
+        board.set(1, 6, ());
+        board.set(3, 6, ());
+        board.set(0, 7, ());
+        board.set(1, 7, ());
+        board.set(2, 7, ());
+        board.set(3, 7, ());
+
+        assert_eq!(board.filled_rows(), [5, 7]);
+        assert_eq!(board.clear_filled_rows(), 2);
+        assert!(board.filled_rows().is_empty());
+
+        for x in 0..4 {
+            for y in 0..7 {
+                assert_eq!(board.get(x, y), &None);
+            }
+        }
+        assert_eq!(board.get(0, 7), &Some(()));
+        assert_eq!(board.get(1, 7), &Some(()));
+        assert_eq!(board.get(2, 7), &None);
+        assert_eq!(board.get(3, 7), &Some(()));
+    }
+
+    #[test]
+    fn test_clear_rotate_row() {
+        let mut board = Board::<()>::new(4, 3);
+        board.set(0, 1, ());
+        board.set(1, 2, ());
+        board.set(2, 2, ());
+
+        board.rotate_row(2, 5);
+        assert_eq!(board.get(1, 2), &None);
+        assert_eq!(board.get(2, 2), &Some(()));
+        assert_eq!(board.get(3, 2), &Some(()));
+
+        board.clear_row(2);
+        assert_eq!(board.get(0, 2), &Some(()));
+        assert_eq!(board.get(3, 2), &None);
+        assert!((0..4).all(|x| board.get(x, 0).is_none() && board.get(x, 1).is_none()));
+    }
+
+    #[test]
+    fn test_sink_filled_rows() {
+        let mut board = Board::<()>::new(4, 8);
+
+        // row 5 is full, row 6 is not, row 7 is full
+        for x in 0..4 {
+            board.set(x, 5, ());
+            board.set(x, 7, ());
+        }
+        board.set(2, 6, ());
+
+        assert_eq!(board.sink_filled_rows(), 2);
+        assert_eq!(board.filled_rows(), [6, 7]);
+        assert_eq!(board.get(2, 5), &Some(()));
+        assert_eq!(board.get(0, 5), &None);
+        assert_eq!(board.sink_filled_rows(), 2);
+        assert_eq!(board.filled_rows(), [6, 7]);
+    }
+
+    #[test]
+    fn test_stack_height_holes() {
+        let board = board! {
+            0 0 0 0;
+            0 1 0 0;
+            1 0 0 0;
+            1 0 1 0;
+        };
+        assert_eq!(board.stack_height(), 3);
+        assert_eq!(board.holes(), 2);
+        assert_eq!(Board::<()>::new(4, 4).stack_height(), 0);
+    }
+
+    #[test]
+    fn test_unclearable_rows() {
+        let mut board = Board::new(3, 4);
+        for x in 0..3 {
+            board.set(x, 2, Solid(x == 1));
+            board.set(x, 3, Solid(false));
+        }
+
+        assert_eq!(board.filled_rows(), [3]);
+        assert_eq!(board.clear_filled_rows(), 1);
+        assert_eq!(board.get(1, 3), &Some(Solid(true)));
+
+        board.set(0, 2, Solid(false));
+        board.set(2, 2, Solid(false));
+        assert_eq!(board.sink_filled_rows(), 0);
+    }
+
+    #[test]
+    fn test_text() {
+        let board = board! {
+            0 0 0;
+            0 12 0;
+            3 3 0;
+        };
+        let text = board.to_text(|&x| x as u32);
+        assert_eq!(text, "0 0 0;\n0 12 0;\n3 3 0;\n");
+
+        let parsed = Board::from_text(&format!("# comment\n\n{text}"), |x| Ok(x as i32)).unwrap();
+        assert_eq!(parsed.board, board.board);
+        assert_eq!((parsed.width(), parsed.height()), (3, 3));
+
+        assert!(Board::from_text("1 x 0;", |x| Ok(x as i32)).is_err());
+        assert!(Board::from_text("1 0;\n0;", |x| Ok(x as i32)).is_err());
+        assert!(Board::from_text("# nothing", |x| Ok(x as i32)).is_err());
+        assert!(Board::<i32>::from_text("7;", |_| bail!("unknown cell")).is_err());
+    }
+
+    #[test]
+    fn test_display_from_str() {
+        let board = board! {
+            0 0 0;
+            0 7 0;
+            3 3 0;
+        };
+        let text = board.to_string();
+        assert_eq!(text, "0 0 0;\n0 7 0;\n3 3 0;\n");
+        let parsed: Board<i32> = text.parse().unwrap();
+        assert_eq!(parsed.board, board.board);
+        assert!("0 8 0;".parse::<Board<i32>>().is_err());
+
+        let stack: Board<()> = "0 5;\n2 0;".parse().unwrap();
+        assert_eq!(stack.to_string(), "0 1;\n1 0;\n");
+    }
+
+    #[test]
+    fn test_add_garbage() {
+        let mut board = board! {
+            0 0 0 0;
+            0 0 0 0;
+            0 1 1 0;
+        };
+
+        assert!(board.add_garbage(1, 2, 9).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0;
+                0 1 1 0;
+                9 9 0 9;
+            }
+            .board
+        );
+
+        assert!(board.add_garbage(2, 0, 9).is_err());
+        assert_eq!(
+            board.board,
+            board! {
+                9 9 0 9;
+                0 9 9 9;
+                0 9 9 9;
+            }
+            .board
+        );
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut board = board! {
+            0 1 1 0;
+            0 1 1 0;
+            0 0 0 0;
+        };
+        assert!(board.check_invariants().is_ok());
+
+        board.current_block = Some(PieceKind::O.block().translate(1, 0));
+        assert!(board.check_invariants().is_ok());
+        board.current_block = Some(PieceKind::O.block().translate(3, 0));
+        assert!(board.check_invariants().is_err());
+        board.current_block = Some(PieceKind::O.block().translate(1, 1));
+        assert!(board.check_invariants().is_err());
+        board.current_block = Some(Block::new(&[(1, 0), (1, 0), (2, 0), (2, 1)]));
+        assert!(board.check_invariants().is_err());
+
+        board.current_block = None;
+        board.board[2].pop();
+        assert!(board.check_invariants().is_err());
+    }
+
+    #[test]
+    fn test_board_macro() {
+        let b = board! {
+            0 2 0;
+            1 1 1;
+        };
+
+        assert_eq!(b.width(), 3);
+        assert_eq!(b.height(), 2);
+        assert_eq!(b.get(0, 0), &None);
+        assert_eq!(b.get(1, 0), &Some(2));
+        assert_eq!(b.get(2, 0), &None);
+        assert_eq!(b.get(0, 1), &Some(1));
+        assert_eq!(b.get(1, 1), &Some(1));
+        assert_eq!(b.get(2, 1), &Some(1));
+    }
+
+    #[test]
+    fn test_check_block() {
+        let board = board! {
+            0 0 0;
+            0 0 0;
+            0 1 1;
+            0 1 1;
+        };
+
+        assert!(board.check_block(&PieceKind::I.block()).is_err());
+        assert!(board
+            .check_block(&PieceKind::I.block().rotate_about((0, 0)))
+            .is_ok());
+        assert!(board
+            .check_block(&PieceKind::I.block().rotate_about((0, 0)).down())
+            .is_err());
+
+        assert!(board.check_block(&PieceKind::O.block()).is_ok());
+        assert!(board.check_block(&PieceKind::O.block().down()).is_err());
+        assert!(board
+            .check_block(&PieceKind::O.block().right().right())
+            .is_err());
+    }
+
+    #[test]
+    fn test_set_block() {
+        let gen_board = || {
+            board! {
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+        };
+
+        let mut board = gen_board();
+        assert!(board.set_block(PieceKind::Z.block(), 2).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                2 2 0 0 0;
+                0 2 2 0 0;
+                0 0 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+
+        let mut board = gen_board();
+        assert!(board.set_block(PieceKind::L.block(), 2).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                2 0 0 0 0;
+                2 0 0 0 0;
+                2 2 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+
+        let mut board = gen_board();
+        assert!(board.set_block(PieceKind::L.block().down(), 2).is_err());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+
+        let mut board = gen_board();
+        assert!(board
+            .set_block(PieceKind::I.block().translate(2, 0), 2)
+            .is_err());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+    }
+
+    #[test]
+    fn test_update_block() {
+        let mut board = board! {
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 1 0 1 1;
+            1 1 1 0 1;
+        };
+
+        assert!(board.set_block(PieceKind::I.block(), 2).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                2 2 2 2 0;
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+        assert!(board.update_block(|b| b.down()).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0;
+                2 2 2 2 0;
+                0 0 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+        assert!(board.update_block(|b| b.translate(0, 2)).is_err());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0;
+                2 2 2 2 0;
+                0 0 0 0 0;
+                0 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+        assert!(board
+            .update_block(|b| b.translate(0, -1).rotate_about((0, 0)))
+            .is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                2 0 0 0 0;
+                2 0 0 0 0;
+                2 0 0 0 0;
+                2 1 0 1 1;
+                1 1 1 0 1;
+            }
+            .board
+        );
+    }
+
+    #[test]
+    fn test_spawn() {
+        let mut board = board! {
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 0 0 0 0;
+        };
+        assert!(board.spawn(PieceKind::I.block(), 1).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 1 1 1 1;
+                0 0 0 0 0;
+                0 0 0 0 0;
+                0 0 0 0 0;
+            }
+            .board
+        );
+        assert!(board.spawn(PieceKind::O.block(), 2).is_err());
+
+        let mut board2 = board! {
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 0 0 0 0;
+        };
+        assert!(board2.spawn(PieceKind::J.block(), 1).is_ok());
+        assert_eq!(
+            board2.board,
+            board! {
+                0 0 1 0 0;
+                0 0 1 0 0;
+                0 1 1 0 0;
+                0 0 0 0 0;
+            }
+            .board
+        );
+
+        let mut board3 = board! {
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 0 0 0 0;
+            0 0 0 0 0;
+        };
+        assert!(board3.spawn(PieceKind::Z.block(), 1).is_ok());
+        assert_eq!(
+            board3.board,
+            board! {
+                0 1 1 0 0;
+                0 0 1 1 0;
+                0 0 0 0 0;
+                0 0 0 0 0;
+            }
+            .board
+        );
+    }
+
+    #[test]
+    fn test_ghost() {
+        let mut board = board! {
+            0 0 0 0;
+            0 0 0 0;
+            0 0 0 0;
+            0 0 0 0;
+            0 0 1 0;
+        };
+        assert_eq!(board.ghost(), None);
+
+        assert!(board.spawn(PieceKind::O.block(), 2).is_ok());
+        assert_eq!(board.ghost(), Some(PieceKind::O.block().translate(2, 2)));
+        assert!(board.left().is_ok());
+        assert_eq!(board.ghost(), Some(PieceKind::O.block().translate(1, 2)));
+        assert!(board.left().is_ok());
+        assert_eq!(board.ghost(), Some(PieceKind::O.block().translate(0, 3)));
+        assert_eq!(board.hard_drop(), 3);
+        assert_eq!(board.ghost(), board.current_block().cloned());
+    }
+
+    #[test]
+    fn test_holes_under_block() {
+        let mut board = board! {
+            0 0 0 0;
+            0 0 0 0;
+            0 0 0 0;
+            0 1 0 0;
+            0 0 0 0;
+            1 0 0 0;
+        };
+        assert_eq!(board.holes_under_block(), 0);
+        assert!(board.spawn(PieceKind::O.block(), 2).is_ok());
+        assert!(board.left().is_ok());
+        assert!(board.left().is_ok());
+        board.hard_drop();
+        assert_eq!(board.holes_under_block(), 2);
+
+        // cells under an overhang were holes already
+        let mut board = board! {
+            0 1 0 0;
+            0 0 0 0;
+            0 0 0 0;
+            0 0 0 0;
+        };
+        board.current_block = Some(PieceKind::O.block().translate(0, 1));
+        for (x, y) in [(0, 1), (1, 1), (0, 2), (1, 2)] {
+            board.set(x, y, 2);
+        }
+        assert_eq!(board.holes_under_block(), 1);
+    }
+
+    #[test]
+    fn test_rotate_kicked() {
+        // a T-spin triple, slid in pointing up under the overhang
+        let mut board = board! {
+            1 1 0 0 0 0 0 0 0 0;
+            1 0 0 0 0 0 0 0 0 0;
+            1 0 1 1 1 1 1 1 1 1;
+            1 0 0 1 1 1 1 1 1 1;
+            1 0 1 1 1 1 1 1 1 1;
+        };
+        let t = PieceKind::T.block().rotate().rotate().translate(1, 1);
+        for &(x, y) in t.coords() {
+            board.set(x as usize, y as usize, 2);
+        }
+        board.current_block = Some(t.clone());
+        assert!(board.rotate().is_err());
+
+        let kicks = PieceKind::T.kicks(t.orientation());
+        assert!(board.rotate_kicked(&kicks[..4]).is_err());
+        assert!(board.rotate_kicked(kicks).is_ok());
+        assert_eq!(board.current_block(), Some(&t.rotate().translate(-1, 2)));
+        assert_eq!(board.filled_rows(), [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_is_immobile() {
+        let mut board = board! {
+            0 0 0 0;
+            1 0 0 1;
+            1 0 0 1;
+            1 1 1 1;
+        };
+        assert!(!board.is_immobile());
+
+        board.current_block = Some(PieceKind::O.block().translate(1, 1));
+        for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+            board.set(x, y, 2);
+        }
+        assert!(!board.is_immobile());
+
+        board.set(2, 0, 1);
+        assert!(board.is_immobile());
+    }
+
+    #[test]
+    fn test_remove_block() {
+        let mut board = board! {
+            0 0 0 0;
+            0 0 0 0;
+            1 0 0 1;
+        };
+        assert_eq!(board.remove_block(), None);
+
+        assert!(board.spawn(PieceKind::O.block(), 2).is_ok());
+        assert_eq!(
+            board.remove_block(),
+            Some(PieceKind::O.block().translate(2, 0))
+        );
+        assert_eq!(board.current_block(), None);
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0;
+                0 0 0 0;
+                1 0 0 1;
+            }
+            .board
+        );
+    }
+
+    #[test]
+    fn test_actions() {
+        let mut board = board! {
+            0 0 0 0 0 0 0 0;
+            0 0 0 0 0 0 0 0;
+            0 0 0 0 0 0 0 0;
+            0 0 0 0 0 0 0 0;
+            0 0 0 0 0 0 0 0;
+            0 1 0 0 0 0 0 0;
+            0 1 0 1 1 1 1 1;
+            1 1 1 0 1 1 1 1;
+        };
+
+        assert!(board.spawn(PieceKind::I.block(), 2).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 2 2 2 2 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 1 0 0 0 0 0 0;
+                0 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        assert!(board.down().is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 2 2 2 2 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 1 0 0 0 0 0 0;
+                0 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        // an I turns about the point between its middle cells
+        assert!(board.rotate().is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 2 0 0;
+                0 0 0 0 0 2 0 0;
+                0 0 0 0 0 2 0 0;
+                0 0 0 0 0 2 0 0;
+                0 0 0 0 0 0 0 0;
+                0 1 0 0 0 0 0 0;
+                0 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        for _ in 0..5 {
+            assert!(board.left().is_ok());
+        }
+        assert!(board.left().is_err());
+        assert_eq!(
+            board.board,
+            board! {
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 1 0 0 0 0 0 0;
+                0 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        assert!(board.try_down().is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 1 0 0 0 0 0 0;
+                0 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        board.hard_drop();
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 1 0 0 0 0 0 0;
+                2 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        assert!(board.spawn(PieceKind::Z.block(), 3).is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 3 3 0 0 0;
+                0 0 0 0 3 3 0 0;
+                0 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 1 0 0 0 0 0 0;
+                2 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        assert!(board.down().is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 3 3 0 0 0;
+                0 0 0 0 3 3 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 1 0 0 0 0 0 0;
+                2 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        // a Z turns about the middle of its bottom row
+        assert!(board.rotate().is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 3 0 0;
+                0 0 0 0 3 3 0 0;
+                2 0 0 0 3 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 1 0 0 0 0 0 0;
+                2 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        assert!(board.right().is_ok());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 3 0;
+                0 0 0 0 0 3 3 0;
+                2 0 0 0 0 3 0 0;
+                2 0 0 0 0 0 0 0;
+                2 1 0 0 0 0 0 0;
+                2 1 0 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        for _ in 0..3 {
+            assert!(board.left().is_ok());
+        }
+        board.hard_drop();
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 3 0 0 0 0;
+                2 1 3 3 0 0 0 0;
+                2 1 3 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        assert!(board.try_down().is_err());
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 3 0 0 0 0;
+                2 1 3 3 0 0 0 0;
+                2 1 3 1 1 1 1 1;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+        assert_eq!(board.clear_filled_rows(), 1);
+        assert_eq!(
+            board.board,
+            board! {
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                0 0 0 0 0 0 0 0;
+                2 0 0 0 0 0 0 0;
+                2 0 0 3 0 0 0 0;
+                2 1 3 3 0 0 0 0;
+                1 1 1 0 1 1 1 1;
+            }
+            .board
+        );
+    }
+}
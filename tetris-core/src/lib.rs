@@ -0,0 +1,12 @@
+//! The pieces, the board and the scoring of the Tetris game implemented in
+//! Rust: moves, rotations with their wall kicks, line clears, garbage and the
+//! points they score. Only `alloc` is needed, so they run on embedded targets
+//! too. The game around them, with its clock, modes and input, needs `std`
+//! and stays in the `tetris-rust` crate.
+#![no_std]
+
+extern crate alloc;
+
+pub mod block;
+pub mod board;
+pub mod scoring;
@@ -0,0 +1,54 @@
+/// What clearing lines scores.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Scoring {
+    /// A point per line for every level.
+    #[default]
+    Level,
+    /// 40, 100, 300 and 1200 points for a single, double, triple and Tetris
+    /// for every level, as on the NES.
+    Classic,
+    /// 100, 300, 500 and 800 points for a single, double, triple and Tetris
+    /// for every level, as in the guideline.
+    Guideline,
+}
+
+impl Scoring {
+    /// Points for clearing `lines` at once at `level`.
+    pub fn points(self, lines: usize, level: usize) -> usize {
+        let base = match self {
+            Scoring::Level => lines,
+            Scoring::Classic => [0, 40, 100, 300, 1200][lines.min(4)],
+            Scoring::Guideline => [0, 100, 300, 500, 800][lines.min(4)],
+        };
+        base * level
+    }
+}
+
+/// Points for dropping a piece `rows` rows, by hard drop if `hard`: a point
+/// per row soft dropped and two per row hard dropped, whatever the scoring.
+pub fn drop_points(rows: usize, hard: bool) -> usize {
+    if hard {
+        2 * rows
+    } else {
+        rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{drop_points, Scoring};
+
+    #[test]
+    fn test_scoring() {
+        assert_eq!(Scoring::Level.points(3, 2), 6);
+        assert_eq!(Scoring::Classic.points(1, 3), 120);
+        assert_eq!(Scoring::Guideline.points(4, 1), 800);
+        assert_eq!(drop_points(5, false), 5);
+        assert_eq!(drop_points(5, true), 10);
+    }
+}